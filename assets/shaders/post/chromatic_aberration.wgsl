@@ -0,0 +1,24 @@
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+
+@group(0) @binding(0) var screen_texture: texture_2d<f32>;
+@group(0) @binding(1) var texture_sampler: sampler;
+
+// Mirrors `ChromaticAberrationSettings` in src/post_process.rs.
+struct ChromaticAberrationSettings {
+    amount: f32,
+    strength: f32,
+}
+@group(0) @binding(2) var<uniform> settings: ChromaticAberrationSettings;
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    // Red is scaled up around the center and blue down, so they fringe at the edges.
+    let scale = 1.0 + settings.amount * settings.strength;
+    let centered = in.uv - vec2<f32>(0.5);
+
+    let red = textureSample(screen_texture, texture_sampler, centered * scale + vec2<f32>(0.5)).r;
+    let green = textureSample(screen_texture, texture_sampler, in.uv).g;
+    let blue = textureSample(screen_texture, texture_sampler, centered / scale + vec2<f32>(0.5)).b;
+
+    return vec4<f32>(red, green, blue, 1.0);
+}
@@ -0,0 +1,53 @@
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+
+@group(0) @binding(0) var screen_texture: texture_2d<f32>;
+@group(0) @binding(1) var texture_sampler: sampler;
+
+// Mirrors `CurvatureSettings` in src/post_process.rs.
+struct CurvatureSettings {
+    curvature: f32,
+    edge_blur: f32,
+    strength: f32,
+    pixel_size: vec2<f32>,
+    resolution: vec2<f32>,
+}
+@group(0) @binding(2) var<uniform> settings: CurvatureSettings;
+
+fn curveRemapUV(uv: vec2<f32>, curvature: f32) -> vec2<f32> {
+    var crtUV = uv * 2.0 - vec2<f32>(1.0, 1.0);
+    let offset = crtUV.yx * curvature;
+    crtUV += crtUV * offset * offset;
+    crtUV = crtUV * 0.5 + vec2<f32>(0.5, 0.5);
+    return crtUV;
+}
+
+fn edgeMask(uv: vec2<f32>, blur: f32) -> f32 {
+    // Outside the curved screen is black even without a soft edge.
+    if (any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0))) {
+        return 0.0;
+    }
+    if (blur <= 0.0) {
+        return 1.0;
+    }
+    let edge = smoothstep(0.0, blur, uv.x) * (1.0 - smoothstep(1.0 - blur, 1.0, uv.x)) *
+               smoothstep(0.0, blur, uv.y) * (1.0 - smoothstep(1.0 - blur, 1.0, uv.y));
+    return edge;
+}
+
+fn applyPixelation(uv: vec2<f32>, resolution: vec2<f32>, pixel_size: vec2<f32>) -> vec2<f32> {
+    let pixelUV = floor(uv * resolution / pixel_size) * pixel_size / resolution;
+    return pixelUV;
+}
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let strength = settings.strength;
+    let pixel_size = max(mix(vec2<f32>(1.0), settings.pixel_size, min(strength, 1.0)), vec2<f32>(1.0));
+
+    let crtUV = curveRemapUV(in.uv, settings.curvature * strength);
+    let pixelUV = applyPixelation(crtUV, max(settings.resolution, vec2<f32>(1.0)), pixel_size);
+    let edge = edgeMask(crtUV, settings.edge_blur * strength);
+
+    let color = textureSample(screen_texture, texture_sampler, pixelUV).rgb * edge;
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,37 @@
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+
+@group(0) @binding(0) var screen_texture: texture_2d<f32>;
+@group(0) @binding(1) var texture_sampler: sampler;
+
+// Mirrors `PhosphorSettings` in src/post_process.rs.
+struct PhosphorSettings {
+    half_life: f32,
+    persistence: f32,
+    strength: f32,
+}
+@group(0) @binding(2) var<uniform> settings: PhosphorSettings;
+
+// What the screen showed last frame, trails included.
+@group(0) @binding(3) var history_texture: texture_2d<f32>;
+
+struct Output {
+    @location(0) screen: vec4<f32>,
+    // Becomes the history of the next frame.
+    @location(1) history: vec4<f32>,
+}
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> Output {
+    let color = textureSample(screen_texture, texture_sampler, in.uv).rgb;
+    let history = textureSample(history_texture, texture_sampler, in.uv).rgb;
+
+    // A lit phosphor stays as bright as it was lit and only fades once the beam has
+    // moved on, so the trail never brightens what is drawn this frame.
+    let afterglow = history * settings.persistence * min(settings.strength, 1.0);
+    let glow = vec4<f32>(max(color, afterglow), 1.0);
+
+    var out: Output;
+    out.screen = glow;
+    out.history = glow;
+    return out;
+}
@@ -0,0 +1,33 @@
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+
+@group(0) @binding(0) var screen_texture: texture_2d<f32>;
+@group(0) @binding(1) var texture_sampler: sampler;
+
+// Mirrors `ScanlineSettings` in src/post_process.rs.
+struct ScanlineSettings {
+    scanlines: f32,
+    noise: f32,
+    flicker: f32,
+    time: f32,
+    strength: f32,
+}
+@group(0) @binding(2) var<uniform> settings: ScanlineSettings;
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let strength = settings.strength;
+    var color = textureSample(screen_texture, texture_sampler, in.uv).rgb;
+
+    let fragCoord = in.position.xy;
+    if (fragCoord.y % 2.0 < 1.0) {
+        color *= 1.0 - settings.scanlines * strength;
+    }
+
+    let flicker = sin(settings.time * 60.0) * 0.5 + 0.5;
+    color *= 1.0 - settings.flicker * strength * flicker;
+
+    let noise = fract(sin(dot(fragCoord, vec2<f32>(12.9898, 78.233))) * 758.53);
+    color += noise * settings.noise * strength;
+
+    return vec4<f32>(color, 1.0);
+}
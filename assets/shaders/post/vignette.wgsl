@@ -0,0 +1,24 @@
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+
+@group(0) @binding(0) var screen_texture: texture_2d<f32>;
+@group(0) @binding(1) var texture_sampler: sampler;
+
+// Mirrors `VignetteSettings` in src/post_process.rs.
+struct VignetteSettings {
+    radius: f32,
+    softness: f32,
+    darkness: f32,
+    strength: f32,
+}
+@group(0) @binding(2) var<uniform> settings: VignetteSettings;
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(screen_texture, texture_sampler, in.uv).rgb;
+
+    // 0 in the center, 1 in the corners.
+    let distance = length(in.uv - vec2<f32>(0.5)) * sqrt(2.0);
+    let shade = smoothstep(settings.radius, settings.radius + settings.softness, distance);
+
+    return vec4<f32>(color * (1.0 - shade * min(settings.darkness * settings.strength, 1.0)), 1.0);
+}
@@ -1,23 +1,58 @@
 #import bevy_sprite::mesh2d_vertex_output::VertexOutput
 
+// Mirrors `StarLayerUniform` in src/background.rs.
+struct StarLayer {
+    drift: vec2<f32>,
+    density: f32,
+    star_size: f32,
+    brightness: f32,
+    twinkle: f32,
+    twinkle_speed: f32,
+    seed: f32,
+}
+
+// Mirrors `StarLayersUniform` in src/background.rs.
+struct StarLayers {
+    layers: array<StarLayer, 4>,
+    count: u32,
+}
+
 @group(2) @binding(0) var<uniform> material_color: vec4<f32>;
 @group(2) @binding(1) var<uniform> time: f32;
+@group(2) @binding(2) var<uniform> stars: StarLayers;
+
+const TAU: f32 = 6.28318530718;
 
 fn random2(p: vec2<f32>) -> f32 {
     let dot_product = dot(p, vec2<f32>(12.9898, 78.233));
     return fract(sin(dot_product) * 43758.5453);
 }
 
-@fragment
-fn fragment(vertex: VertexOutput) -> @location(0) vec4<f32> {
-    let uv = vertex.position.xy;
+// Brightness of `layer` at `position`, in arena units.
+fn layer_brightness(layer: StarLayer, position: vec2<f32>) -> f32 {
+    // Drift one whole star at a time so the stars keep their shape.
+    let offset = floor(layer.drift * time / layer.star_size) * layer.star_size;
+    let cell = floor((position + offset) / layer.star_size);
+
+    let chance = random2(cell + vec2<f32>(layer.seed, 0.0));
+    if (chance >= layer.density) {
+        return 0.0;
+    }
 
-    let motion_offset = vec2<f32>(0.01, 0.0);
+    // Every star twinkles at its own phase.
+    let phase = random2(cell + vec2<f32>(0.0, layer.seed)) * TAU;
+    let wave = sin(time * layer.twinkle_speed * TAU + phase) * 0.5 + 0.5;
+    return layer.brightness * (1.0 - layer.twinkle * wave);
+}
 
-    let scaled_uv = uv * 0.2; // Further decrease the scale for much larger stars
+@fragment
+fn fragment(vertex: VertexOutput) -> @location(0) vec4<f32> {
+    let position = vertex.world_position.xy;
 
-    let brightness = step(0.995, random2(scaled_uv + motion_offset));
-    let density_factor = step(0.05, fract(scaled_uv.x) * fract(scaled_uv.y)); 
+    var brightness = 0.0;
+    for (var i = 0u; i < min(stars.count, 4u); i++) {
+        brightness = max(brightness, layer_brightness(stars.layers[i], position));
+    }
 
-    return material_color * vec4<f32>(brightness * density_factor, brightness * density_factor, brightness * density_factor, 0.8);
+    return material_color * vec4<f32>(brightness, brightness, brightness, 0.8);
 }
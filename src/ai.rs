@@ -0,0 +1,184 @@
+//! Computer pilot for single-player matches.
+//!
+//! The pilot only ever presses the same buttons a player could: it turns towards
+//! where the opponent is heading, thrusts to close in, fires once lined up and
+//! burns away from a sun when it drifts too close. Lower difficulties aim off by a
+//! wandering error, turn slower and don't lead their shots.
+
+use bevy::prelude::*;
+
+use crate::arena::GravityWell;
+use crate::input::ShipInput;
+use crate::rocket::{Player, Rocket};
+use crate::{SimClock, SimRng};
+
+/// Closer than this to the kill radius of a gravity well and the pilot stops hunting
+/// and gets clear.
+const DANGER_MARGIN: f32 = 140.0;
+/// Thrusts towards the target while further away than this.
+const CHASE_DISTANCE: f32 = 260.0;
+/// Fires when the target is within this angle of the nose, in radians.
+const FIRE_CONE: f32 = 0.12;
+const FIRE_RANGE: f32 = 450.0;
+/// Torpedo speed, used to lead the target.
+const TORPEDO_SPEED: f32 = 300.0;
+/// Fuel the pilot keeps back for escaping a sun.
+const FUEL_RESERVE: f32 = 3.0;
+/// Ticks between changes of a pilot's aiming error.
+const AIM_ERROR_TICKS: u32 = 30;
+
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Normal => "normal",
+            Difficulty::Hard => "hard",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|difficulty| difficulty.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Largest aiming error, in radians.
+    fn aim_error(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.35,
+            Difficulty::Normal => 0.1,
+            Difficulty::Hard => 0.0,
+        }
+    }
+
+    /// Fastest spin the pilot allows itself, in radians per second.
+    fn max_spin(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.8,
+            Difficulty::Normal | Difficulty::Hard => 1.2,
+        }
+    }
+
+    /// Whether the pilot aims where the target is going rather than where it is.
+    fn leads_shots(&self) -> bool {
+        *self != Difficulty::Easy
+    }
+}
+
+/// Puts the computer in control of some of the rockets.
+#[derive(Resource, Clone)]
+pub struct AiOpponent {
+    pub players: Vec<Player>,
+    /// Current aiming error of each player's pilot.
+    aim_error: [f32; 2],
+}
+
+impl AiOpponent {
+    pub fn new(players: &[Player]) -> Self {
+        Self {
+            players: players.to_vec(),
+            aim_error: [0.0; 2],
+        }
+    }
+}
+
+/// Overrides the input of the computer-controlled rockets for the coming tick.
+pub fn apply_ai_input(
+    mut ai: ResMut<AiOpponent>,
+    difficulty: Res<Difficulty>,
+    clock: Res<SimClock>,
+    mut rng: ResMut<SimRng>,
+    mut rockets: Query<(&Player, &Rocket, &Transform, &mut ShipInput)>,
+    wells: Query<&GravityWell>,
+) {
+    let wells: Vec<GravityWell> = wells.iter().copied().collect();
+    let states: Vec<(Player, Vec2, Vec2)> = rockets
+        .iter()
+        .map(|(player, rocket, transform, _)| {
+            (*player, transform.translation.truncate(), rocket.velocity)
+        })
+        .collect();
+
+    if clock.tick.is_multiple_of(AIM_ERROR_TICKS) {
+        let limit = difficulty.aim_error();
+        for player in ai.players.clone() {
+            ai.aim_error[player.0.min(1)] = rng.range(-limit, limit);
+        }
+    }
+
+    for (player, rocket, transform, mut input) in rockets.iter_mut() {
+        if !ai.players.contains(player) {
+            continue;
+        }
+        let target = states
+            .iter()
+            .find(|(other, ..)| other != player)
+            .map(|(_, position, velocity)| (*position, *velocity));
+        let aim_error = ai.aim_error[player.0.min(1)];
+        *input = pilot(&difficulty, aim_error, rocket, transform, target, &wells);
+    }
+}
+
+fn pilot(
+    difficulty: &Difficulty,
+    aim_error: f32,
+    rocket: &Rocket,
+    transform: &Transform,
+    target: Option<(Vec2, Vec2)>,
+    wells: &[GravityWell],
+) -> ShipInput {
+    let position = transform.translation.truncate();
+    let forward = (transform.rotation * Vec3::Y).truncate();
+    let mut bits = 0;
+
+    let danger = wells
+        .iter()
+        .find(|well| position.distance(well.position) < well.kill_radius + DANGER_MARGIN);
+    let escaping = danger.is_some();
+    let aim = if let Some(well) = danger {
+        // Away from the well, bent sideways so the escape doesn't fight gravity head on.
+        let away = position - well.position;
+        away.normalize_or_zero() + away.perp().normalize_or_zero() * 0.5
+    } else if let Some((target, velocity)) = target {
+        let lead = if difficulty.leads_shots() {
+            target.distance(position) / TORPEDO_SPEED
+        } else {
+            0.0
+        };
+        Vec2::from_angle(aim_error).rotate(target + velocity * lead - position)
+    } else {
+        -position
+    };
+
+    // Spin only builds up while a key is held, so steer the spin rate, not the heading.
+    let error = forward.angle_between(aim);
+    let max_spin = difficulty.max_spin();
+    let wanted_spin = (error * 2.0).clamp(-max_spin, max_spin);
+    if rocket.rotation_speed < wanted_spin - 0.05 {
+        bits |= ShipInput::ROTATE_LEFT;
+    } else if rocket.rotation_speed > wanted_spin + 0.05 {
+        bits |= ShipInput::ROTATE_RIGHT;
+    }
+
+    let distance = aim.length();
+    let lined_up = error.abs() < 0.5;
+    if escaping || (lined_up && distance > CHASE_DISTANCE && rocket.fuel > FUEL_RESERVE) {
+        bits |= ShipInput::ACCELERATE;
+    }
+
+    if !escaping && target.is_some() && error.abs() < FIRE_CONE && distance < FIRE_RANGE {
+        bits |= ShipInput::FIRE;
+    }
+
+    ShipInput(bits)
+}
@@ -0,0 +1,426 @@
+//! Gravity wells and the layouts that place them.
+//!
+//! A layout lists the bodies of an arena, each a [`GravityWell`]: stars and black
+//! holes stay where they are put, planets and moons orbit another body of the layout.
+//! It can also link pairs of [`Wormhole`]s. Layouts are read from `.arena` files, by
+//! default from `assets/arenas/`, with one `key = value` per line:
+//!
+//! ```text
+//! # Comments start with #.
+//! name = Binary
+//! # star = <x> <y> <mass> <kill radius>
+//! star = -110 0 0.6 20
+//! star = 110 0 0.6 20
+//! # planet = <around> <semi-major axis> <eccentricity> <period> <periapsis> <phase>
+//! #          <mass> <kill radius>
+//! planet = 0 90 0 12 0 0 0.1 10
+//! # black_hole = <x> <y> <mass> <event horizon>
+//! black_hole = 0 250 3 24
+//! # wormhole = <x> <y> <other x> <other y> <radius>
+//! wormhole = -400 -300 400 300 28
+//! # asteroids = <count> <radius> <splits>
+//! asteroids = 5 30 2
+//! ```
+//!
+//! `around` counts the bodies before the planet from 0, so planets can have moons.
+//! Periods are in seconds, negative for clockwise orbits, and both angles are in
+//! degrees: the direction of the closest approach and how far along its orbit the
+//! planet starts each round. A black hole is a well with a heavier pull and a
+//! different look. Asteroids are scattered at random at the start of every round, see
+//! [`crate::asteroid`].
+//!
+//! A mass of 1 pulls as hard as the classic sun. A layout without bodies is an empty
+//! arena. Like the rules, the layout isn't sent over the network, so both sides of an
+//! online match have to pick the same one.
+
+use bevy::{asset::io::file::FileAssetReader, prelude::*};
+use std::f32::consts::TAU;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+use crate::asteroid::AsteroidField;
+use crate::bullet::Bullet;
+use crate::rocket::Rocket;
+use crate::{AppState, SimClock};
+
+/// Asteroids one `asteroids` line may scatter, before any of them break apart.
+const MAX_FIELD_ASTEROIDS: u32 = 50;
+
+/// A body pulling rockets and torpedoes towards it and destroying any that come too
+/// close.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct GravityWell {
+    /// Where the body is now; kept up to date by [`move_orbits`] for orbiting ones.
+    pub position: Vec2,
+    /// Strength of the pull; 1 is the classic sun.
+    pub mass: f32,
+    /// Rockets whose collision circle reaches this close to the center are destroyed,
+    /// and so are torpedoes that get this close. The event horizon of a black hole.
+    pub kill_radius: f32,
+    pub kind: WellKind,
+    pub orbit: Option<Orbit>,
+}
+
+impl GravityWell {
+    /// The single sun in the middle of the original game.
+    pub const SUN: GravityWell = GravityWell {
+        position: Vec2::ZERO,
+        mass: 1.0,
+        kill_radius: 30.0,
+        kind: WellKind::Sun,
+        orbit: None,
+    };
+}
+
+/// What a gravity well is, which decides how it is drawn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WellKind {
+    Sun,
+    Planet,
+    BlackHole,
+}
+
+/// One mouth of a wormhole. Rockets and torpedoes flying into it come out of the
+/// other mouth with the same heading and speed.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct Wormhole {
+    pub position: Vec2,
+    /// Where the other mouth is.
+    pub exit: Vec2,
+    pub radius: f32,
+}
+
+/// Sent whenever a rocket or torpedo goes through a wormhole, at the mouth it comes
+/// out of.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct Warped {
+    pub position: Vec3,
+}
+
+/// Keplerian path of a body around another one of the layout.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Orbit {
+    /// Index in [`ArenaLayout::wells`] of the body orbited, which comes before this one.
+    pub around: usize,
+    pub semi_major_axis: f32,
+    /// 0 for a circle, closer to 1 for longer ellipses.
+    pub eccentricity: f32,
+    /// Seconds per revolution, negative for clockwise.
+    pub period: f32,
+    /// Direction of the closest approach, in radians.
+    pub periapsis: f32,
+    /// Mean anomaly at the start of a round, in radians.
+    pub phase: f32,
+}
+
+impl Orbit {
+    /// Position relative to the orbited body `seconds` into a round.
+    pub fn offset(&self, seconds: f32) -> Vec2 {
+        let mean = (self.phase + TAU * seconds / self.period).rem_euclid(TAU);
+        let e = self.eccentricity;
+        // Kepler's equation, M = E - e sin E, solved with a fixed number of Newton
+        // steps so every peer lands on the same position.
+        let mut eccentric = if e > 0.8 { std::f32::consts::PI } else { mean };
+        for _ in 0..8 {
+            eccentric -= (eccentric - e * eccentric.sin() - mean) / (1.0 - e * eccentric.cos());
+        }
+        let local = Vec2::new(
+            self.semi_major_axis * (eccentric.cos() - e),
+            self.semi_major_axis * (1.0 - e * e).sqrt() * eccentric.sin(),
+        );
+        Vec2::from_angle(self.periapsis).rotate(local)
+    }
+}
+
+/// Position of a well in [`ArenaLayout::wells`].
+#[derive(Component, Clone, Copy, Debug)]
+pub struct WellIndex(pub usize);
+
+/// The wells of the arena a match is played in.
+#[derive(Resource, Clone, Debug, PartialEq)]
+pub struct ArenaLayout {
+    pub name: String,
+    pub wells: Vec<GravityWell>,
+    /// Both mouths of every wormhole.
+    pub wormholes: Vec<Wormhole>,
+    pub asteroids: Vec<AsteroidField>,
+}
+
+impl Default for ArenaLayout {
+    fn default() -> Self {
+        Self {
+            name: "Classic".into(),
+            wells: vec![GravityWell::SUN],
+            wormholes: Vec::new(),
+            asteroids: Vec::new(),
+        }
+    }
+}
+
+impl ArenaLayout {
+    /// Folder the layouts that can be picked by name are in.
+    pub fn dir() -> PathBuf {
+        FileAssetReader::get_base_path().join("assets/arenas")
+    }
+
+    /// Reads `<name>.arena` from [`ArenaLayout::dir`], or the file at `name` if it
+    /// is a path to one.
+    pub fn load(name: &str) -> Result<Self, String> {
+        let path = if name.ends_with(".arena") {
+            PathBuf::from(name)
+        } else {
+            Self::dir().join(format!("{name}.arena"))
+        };
+        let text = std::fs::read_to_string(&path)
+            .map_err(|err| format!("could not read {}: {err}", path.display()))?;
+        Self::parse(&text, &path)
+    }
+
+    /// Unlike settings, a layout with mistakes is refused rather than half used, since
+    /// it changes how the match plays.
+    pub fn parse(text: &str, path: &Path) -> Result<Self, String> {
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut layout = Self {
+            name,
+            wells: Vec::new(),
+            wormholes: Vec::new(),
+            asteroids: Vec::new(),
+        };
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |what: &str| format!("{} line {}: {what}", path.display(), number + 1);
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(error("expected key = value"));
+            };
+            let key = key.trim();
+            // `nan` and `inf` parse too, and would spread to everything they pull on.
+            let numbers: Result<Vec<f32>, String> = value
+                .split_whitespace()
+                .map(|number| {
+                    number
+                        .parse()
+                        .ok()
+                        .filter(|number: &f32| number.is_finite())
+                })
+                .collect::<Option<_>>()
+                .ok_or_else(|| error(&format!("{key} takes numbers")));
+            let well = match key {
+                "name" => {
+                    layout.name = value.trim().to_string();
+                    continue;
+                }
+                "star" => {
+                    let [x, y, mass, kill_radius] = numbers?[..] else {
+                        return Err(error("star takes x, y, mass and kill radius"));
+                    };
+                    GravityWell {
+                        position: Vec2::new(x, y),
+                        mass,
+                        kill_radius,
+                        kind: WellKind::Sun,
+                        orbit: None,
+                    }
+                }
+                "black_hole" => {
+                    let [x, y, mass, horizon] = numbers?[..] else {
+                        return Err(error("black_hole takes x, y, mass and event horizon"));
+                    };
+                    GravityWell {
+                        position: Vec2::new(x, y),
+                        mass,
+                        kill_radius: horizon,
+                        kind: WellKind::BlackHole,
+                        orbit: None,
+                    }
+                }
+                "wormhole" => {
+                    let [x, y, other_x, other_y, radius] = numbers?[..] else {
+                        return Err(error("wormhole takes x, y, other x, other y and radius"));
+                    };
+                    let (one, other) = (Vec2::new(x, y), Vec2::new(other_x, other_y));
+                    // Coming out of one mouth mustn't mean falling into the other.
+                    if radius <= 0.0 || one.distance(other) <= radius * 2.0 {
+                        return Err(error("wormhole mouths need a radius and room between them"));
+                    }
+                    layout.wormholes.extend([
+                        Wormhole {
+                            position: one,
+                            exit: other,
+                            radius,
+                        },
+                        Wormhole {
+                            position: other,
+                            exit: one,
+                            radius,
+                        },
+                    ]);
+                    continue;
+                }
+                "asteroids" => {
+                    let [count, radius, splits] = numbers?[..] else {
+                        return Err(error("asteroids takes count, radius and splits"));
+                    };
+                    let Some(count) = whole(count, 1..=MAX_FIELD_ASTEROIDS) else {
+                        return Err(error(&format!(
+                            "asteroids come in whole numbers from 1 to {MAX_FIELD_ASTEROIDS}"
+                        )));
+                    };
+                    // Radii go over the network as whole bytes.
+                    let splits = match whole(splits, 0..=3) {
+                        Some(splits) if (1.0..=100.0).contains(&radius) => splits,
+                        _ => {
+                            return Err(error(
+                                "asteroids are 1 to 100 across and split up to 3 times",
+                            ))
+                        }
+                    };
+                    layout.asteroids.push(AsteroidField {
+                        count,
+                        radius,
+                        splits,
+                    });
+                    continue;
+                }
+                "planet" => {
+                    let [around, a, e, period, periapsis, phase, mass, kill_radius] = numbers?[..]
+                    else {
+                        return Err(error(
+                            "planet takes around, semi-major axis, eccentricity, period, \
+                             periapsis, phase, mass and kill radius",
+                        ));
+                    };
+                    let Some(around) = whole(around, 0..=u32::MAX)
+                        .map(|around| around as usize)
+                        .filter(|around| *around < layout.wells.len())
+                    else {
+                        return Err(error("planets orbit a body listed before them"));
+                    };
+                    let center = &layout.wells[around];
+                    if a <= 0.0 || period == 0.0 {
+                        return Err(error("orbits need a size and a period"));
+                    }
+                    if !(0.0..1.0).contains(&e) {
+                        return Err(error(
+                            "eccentricity goes from 0 up to, but not including, 1",
+                        ));
+                    }
+                    let orbit = Orbit {
+                        around,
+                        semi_major_axis: a,
+                        eccentricity: e,
+                        period,
+                        periapsis: periapsis.to_radians(),
+                        phase: phase.to_radians(),
+                    };
+                    GravityWell {
+                        position: center.position + orbit.offset(0.0),
+                        mass,
+                        kill_radius,
+                        kind: WellKind::Planet,
+                        orbit: Some(orbit),
+                    }
+                }
+                other => return Err(error(&format!("unknown key {other}"))),
+            };
+            let mass_ok = well.mass.is_finite() && well.mass >= 0.0;
+            if !mass_ok || !well.kill_radius.is_finite() || well.kill_radius <= 0.0 {
+                return Err(error(
+                    "mass can't be negative and kill radius must be positive",
+                ));
+            }
+            layout.wells.push(well);
+        }
+        Ok(layout)
+    }
+
+    /// Where every well is `seconds` into a round, in the order of [`Self::wells`].
+    pub fn positions(&self, seconds: f32) -> Vec<Vec2> {
+        let mut positions: Vec<Vec2> = Vec::with_capacity(self.wells.len());
+        for well in &self.wells {
+            let position = match well.orbit {
+                Some(orbit) => positions[orbit.around] + orbit.offset(seconds),
+                None => well.position,
+            };
+            positions.push(position);
+        }
+        positions
+    }
+}
+
+/// `number` as a whole number within `range`, if it is one.
+fn whole(number: f32, range: RangeInclusive<u32>) -> Option<u32> {
+    let within = *range.start() as f32 <= number && number <= *range.end() as f32;
+    (within && number.fract() == 0.0).then_some(number as u32)
+}
+
+/// Puts the bodies and wormholes of the [`ArenaLayout`] in place for a match.
+pub fn spawn_wells(mut commands: Commands, layout: Res<ArenaLayout>) {
+    for (index, well) in layout.wells.iter().enumerate() {
+        commands.spawn((*well, WellIndex(index), StateScoped(AppState::InGame)));
+    }
+    for wormhole in &layout.wormholes {
+        commands.spawn((*wormhole, StateScoped(AppState::InGame)));
+    }
+}
+
+/// Moves whatever is inside a wormhole mouth out of the other one, keeping its
+/// heading, and returns where it came out.
+pub fn warp(wormholes: &[Wormhole], transform: &mut Transform) -> Option<Vec3> {
+    let position = transform.translation.truncate();
+    let mouth = wormholes
+        .iter()
+        .find(|mouth| position.distance(mouth.position) < mouth.radius)?;
+    // Just past the rim of the exit, so it doesn't fall straight back in.
+    let heading = (transform.rotation * Vec3::Y).truncate();
+    let exit = mouth.exit + heading * (mouth.radius + 1.0);
+    transform.translation = exit.extend(transform.translation.z);
+    Some(transform.translation)
+}
+
+/// What wormholes carry.
+type Travellers = Or<(With<Rocket>, With<Bullet>)>;
+
+/// Sends rockets and torpedoes through the wormholes they have flown into. Their
+/// velocity is left alone, so it carries over on its own.
+pub fn travel_wormholes(
+    wormholes: Query<&Wormhole>,
+    mut travellers: Query<&mut Transform, Travellers>,
+    mut warped: EventWriter<Warped>,
+) {
+    let wormholes: Vec<Wormhole> = wormholes.iter().copied().collect();
+    if wormholes.is_empty() {
+        return;
+    }
+    for mut transform in &mut travellers {
+        if let Some(position) = warp(&wormholes, &mut transform) {
+            warped.send(Warped { position });
+        }
+    }
+}
+
+/// Moves orbiting bodies to where they are at the current tick. Their position only
+/// depends on the match clock, so rolling it back rolls them back too.
+pub fn move_orbits(
+    clock: Res<SimClock>,
+    layout: Res<ArenaLayout>,
+    mut wells: Query<(&WellIndex, &mut GravityWell, Option<&mut Transform>)>,
+) {
+    let positions = layout.positions(clock.elapsed().as_secs_f32());
+    for (index, mut well, transform) in &mut wells {
+        let Some(position) = positions.get(index.0) else {
+            continue;
+        };
+        if well.position != *position {
+            well.position = *position;
+        }
+        if let Some(mut transform) = transform {
+            transform.translation = position.extend(transform.translation.z);
+        }
+    }
+}
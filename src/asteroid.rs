@@ -0,0 +1,280 @@
+//! Asteroids drifting through the arena.
+//!
+//! An arena file can scatter fields of them at the start of every round. They are
+//! pulled by the gravity wells the way torpedoes are, wrap around the edges like
+//! rockets, destroy rockets they run into and stop torpedoes. A torpedo breaks an
+//! asteroid into two smaller ones as often as its field allows; after that the pieces
+//! crumble.
+
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+    },
+    sprite::Mesh2dHandle,
+};
+use std::f32::consts::TAU;
+
+use crate::arena::{ArenaLayout, GravityWell};
+use crate::bullet::Bullet;
+use crate::hull::{take_hit, Hit};
+use crate::rocket::{wrap_position, Player, Rocket, RocketDestroyed, SPAWNS};
+use crate::vector::VectorShape;
+use crate::{gravity_at, AppState, ArenaBounds, Rules, SimClock, SimRng};
+
+/// Pieces are this much of the size of the asteroid they broke off.
+const PIECE_SCALE: f32 = 0.6;
+/// How far the course of each piece turns away from the asteroid's, in radians.
+const PIECE_SPREAD: f32 = 0.6;
+/// Pieces fly off this much faster than the asteroid was drifting.
+const PIECE_SPEEDUP: f32 = 1.4;
+const MIN_DRIFT: f32 = 20.0;
+const MAX_DRIFT: f32 = 60.0;
+/// Asteroids don't start closer than this to a rocket or the kill radius of a well.
+const CLEARANCE: f32 = 150.0;
+/// Tries at finding a clear spot for an asteroid before leaving it out.
+const PLACEMENT_TRIES: usize = 20;
+/// How close the middle of a rocket gets to the rock before they touch.
+const HULL_RADIUS: f32 = 16.0;
+/// Drawn above the rockets and torpedoes, below the suns.
+const ASTEROID_Z: f32 = 2.0;
+
+/// Outline of an asteroid with a radius of 1, ending where it starts. The mesh and the
+/// vector outline share it.
+pub const ROCK: [Vec2; 10] = [
+    Vec2::new(0.0, 1.0),
+    Vec2::new(0.65, 0.8),
+    Vec2::new(1.0, 0.2),
+    Vec2::new(0.8, -0.55),
+    Vec2::new(0.3, -0.95),
+    Vec2::new(-0.35, -0.85),
+    Vec2::new(-0.95, -0.45),
+    Vec2::new(-0.85, 0.3),
+    Vec2::new(-0.5, 0.85),
+    Vec2::new(0.0, 1.0),
+];
+
+/// A rock drifting through the arena.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct Asteroid {
+    pub velocity: Vec2,
+    pub radius: f32,
+    /// Times it can still break into two.
+    pub splits: u32,
+}
+
+impl Asteroid {
+    /// Everything the simulation needs of an asteroid at `position`. Its transform is
+    /// scaled by the radius, so [`ROCK`] comes out at the right size.
+    pub fn bundle(self, position: Vec2) -> (Asteroid, SpatialBundle, StateScoped<AppState>) {
+        let transform = Transform::from_translation(position.extend(ASTEROID_Z))
+            .with_scale(Vec3::splat(self.radius));
+        (
+            self,
+            SpatialBundle::from_transform(transform),
+            StateScoped(AppState::InGame),
+        )
+    }
+
+    /// The two pieces a torpedo breaks it into, if it is big enough to break.
+    fn pieces(&self, position: Vec2) -> Option<[(Asteroid, Vec2); 2]> {
+        if self.splits == 0 {
+            return None;
+        }
+        let radius = self.radius * PIECE_SCALE;
+        Some([-1.0, 1.0].map(|side| {
+            let velocity =
+                Vec2::from_angle(side * PIECE_SPREAD).rotate(self.velocity) * PIECE_SPEEDUP;
+            let offset = velocity.normalize_or_zero().perp() * side * radius;
+            let piece = Asteroid {
+                velocity,
+                radius,
+                splits: self.splits - 1,
+            };
+            (piece, position + offset)
+        }))
+    }
+}
+
+/// Asteroids an arena scatters at the start of every round.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AsteroidField {
+    pub count: u32,
+    pub radius: f32,
+    /// Times each asteroid can break into two.
+    pub splits: u32,
+}
+
+/// Scatters the asteroid fields of the [`ArenaLayout`] with the match's random
+/// numbers, clear of the rockets and wells.
+pub fn spawn_asteroids(
+    mut commands: Commands,
+    layout: Res<ArenaLayout>,
+    bounds: Res<ArenaBounds>,
+    mut rng: ResMut<SimRng>,
+) {
+    let wells: Vec<(Vec2, f32)> = layout
+        .positions(0.0)
+        .into_iter()
+        .zip(layout.wells.iter().map(|well| well.kill_radius))
+        .collect();
+    let half = bounds.half_size;
+    for field in &layout.asteroids {
+        let clear = |spot: Vec2| {
+            let room = CLEARANCE + field.radius;
+            SPAWNS.iter().all(|spawn| spot.distance(*spawn) > room)
+                && wells
+                    .iter()
+                    .all(|(position, kill_radius)| spot.distance(*position) > room + kill_radius)
+        };
+        for _ in 0..field.count {
+            // A crowded layout just gets fewer asteroids.
+            let Some(position) = (0..PLACEMENT_TRIES)
+                .map(|_| Vec2::new(rng.range(-half.x, half.x), rng.range(-half.y, half.y)))
+                .find(|spot| clear(*spot))
+            else {
+                continue;
+            };
+            let velocity = Vec2::from_angle(rng.range(0.0, TAU)) * rng.range(MIN_DRIFT, MAX_DRIFT);
+            let asteroid = Asteroid {
+                velocity,
+                radius: field.radius,
+                splits: field.splits,
+            };
+            commands.spawn(asteroid.bundle(position));
+        }
+    }
+}
+
+/// Drifts asteroids along, bent by the wells, and lets the wells swallow the ones
+/// that fall in.
+pub fn move_asteroids(
+    mut commands: Commands,
+    time: Res<Time>,
+    bounds: Res<ArenaBounds>,
+    rules: Res<Rules>,
+    mut asteroids: Query<(Entity, &mut Asteroid, &mut Transform)>,
+    wells: Query<&GravityWell>,
+) {
+    let wells: Vec<GravityWell> = wells.iter().copied().collect();
+    let dt = time.delta_seconds();
+    for (entity, mut asteroid, mut transform) in &mut asteroids {
+        let pull = gravity_at(&wells, transform.translation.truncate(), rules.gravity);
+        asteroid.velocity += pull * dt;
+        transform.translation += (asteroid.velocity * dt).extend(0.0);
+        wrap_position(&bounds, &mut transform);
+        let position = transform.translation.truncate();
+        if wells
+            .iter()
+            .any(|well| position.distance(well.position) < well.kill_radius + asteroid.radius)
+        {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Torpedoes and rockets running into asteroids. Either breaks the asteroid; the
+/// torpedo is spent and the rocket takes the damage.
+pub fn asteroid_collisions(
+    mut commands: Commands,
+    clock: Res<SimClock>,
+    rules: Res<Rules>,
+    mut destroyed: EventWriter<RocketDestroyed>,
+    asteroids: Query<(Entity, &Asteroid, &Transform)>,
+    bullets: Query<(Entity, &Transform), With<Bullet>>,
+    mut rockets: Query<(Entity, &Player, &mut Rocket, &Transform)>,
+) {
+    let mut spent: Vec<Entity> = Vec::new();
+    for (entity, asteroid, transform) in &asteroids {
+        let position = transform.translation.truncate();
+        let touches = |other: &Transform, reach: f32| {
+            position.distance(other.translation.truncate()) < asteroid.radius + reach
+        };
+
+        let torpedo = bullets
+            .iter()
+            .find(|(bullet, transform)| !spent.contains(bullet) && touches(transform, 0.0));
+        let rocket = rockets.iter_mut().find(|(rocket, _, _, transform)| {
+            !spent.contains(rocket) && touches(transform, HULL_RADIUS)
+        });
+        let hit = rocket.is_some();
+        if let Some((bullet, _)) = torpedo {
+            commands.entity(bullet).despawn();
+            spent.push(bullet);
+        }
+        if let Some((rocket, player, mut ship, transform)) = rocket {
+            if take_hit(&mut ship, Hit::Asteroid, clock.tick, &rules) {
+                destroyed.send(RocketDestroyed {
+                    player: *player,
+                    position: transform.translation,
+                });
+                commands.entity(rocket).despawn();
+                spent.push(rocket);
+            }
+        }
+        if torpedo.is_none() && !hit {
+            continue;
+        }
+
+        commands.entity(entity).despawn();
+        for (piece, position) in asteroid.pieces(position).into_iter().flatten() {
+            commands.spawn(piece.bundle(position));
+        }
+    }
+}
+
+/// Mesh and material every asteroid shares, scaled to size by its transform.
+#[derive(Resource)]
+pub struct RockLook {
+    pub mesh: Handle<Mesh>,
+    pub material: Handle<ColorMaterial>,
+}
+
+impl FromWorld for RockLook {
+    fn from_world(world: &mut World) -> Self {
+        // A fan of triangles from the middle to the outline, which ends where it began.
+        let mut positions = vec![[0.0, 0.0, 0.0]];
+        positions.extend(ROCK.iter().map(|point| [point.x, point.y, 0.0]));
+        let indices = (1..ROCK.len() as u32)
+            .flat_map(|corner| [0, corner, corner + 1])
+            .collect();
+        let mesh = Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::RENDER_WORLD,
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_indices(Indices::U32(indices));
+
+        Self {
+            mesh: world.resource_mut::<Assets<Mesh>>().add(mesh),
+            material: world
+                .resource_mut::<Assets<ColorMaterial>>()
+                .add(ColorMaterial::from(Color::srgb(0.45, 0.4, 0.35))),
+        }
+    }
+}
+
+/// Draws asteroids, which the simulation spawns without any looks.
+pub struct AsteroidPlugin;
+
+impl Plugin for AsteroidPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RockLook>()
+            .add_systems(Update, dress_asteroids);
+    }
+}
+
+fn dress_asteroids(
+    mut commands: Commands,
+    look: Res<RockLook>,
+    asteroids: Query<Entity, Added<Asteroid>>,
+) {
+    for entity in &asteroids {
+        commands.entity(entity).insert((
+            Mesh2dHandle(look.mesh.clone()),
+            look.material.clone(),
+            VectorShape::Asteroid,
+        ));
+    }
+}
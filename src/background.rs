@@ -0,0 +1,502 @@
+//! The starfield behind the arena.
+//!
+//! Stars are drawn by `stars_material.wgsl` in up to [`MAX_STAR_LAYERS`] layers. Each
+//! layer drifts at its own speed, so slow, dim, small stars read as far away behind
+//! faster and brighter ones. Everything about the layers comes from
+//! [`BackgroundSettings`] and can be changed while the game runs.
+//!
+//! [`BackgroundMode::Planetarium`] replaces the random stars with a chart of the real
+//! sky, like the "Expensive Planetarium" of the original Spacewar!. The stars come
+//! from a [`StarCatalog`] in the assets, are laid out by right ascension and
+//! declination, and the chart scrolls slowly sideways, wrapping around after a full
+//! turn of the sky.
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    render::{
+        mesh::{Indices, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+        render_resource::{AsBindGroup, ShaderRef, ShaderType},
+    },
+    sprite::{Material2d, Material2dPlugin, MaterialMesh2dBundle, Mesh2dHandle},
+};
+use std::io;
+
+use crate::ArenaBounds;
+
+/// Layers past this many are not drawn.
+pub const MAX_STAR_LAYERS: usize = 4;
+
+pub struct BackgroundPlugin;
+
+impl Plugin for BackgroundPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(Material2dPlugin::<MovingPatternMaterial>::default())
+            .init_asset::<StarCatalog>()
+            .register_asset_loader(StarCatalogLoader)
+            .init_resource::<BackgroundSettings>()
+            .add_systems(Startup, add_background)
+            .add_systems(
+                Update,
+                (
+                    animate_background,
+                    switch_background.run_if(resource_changed::<BackgroundSettings>),
+                    build_planetarium,
+                    scroll_planetarium,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// One layer of stars.
+#[derive(Clone, Copy, Debug)]
+pub struct StarLayer {
+    /// Share of the grid cells that hold a star, from 0 to 1.
+    pub density: f32,
+    /// Size of a star, and of the grid cells, in arena units.
+    pub star_size: f32,
+    /// Speed and direction the layer moves in, in arena units per second.
+    pub drift: Vec2,
+    pub brightness: f32,
+    /// How much a star dims at the bottom of its twinkle, from 0 to 1.
+    pub twinkle: f32,
+    /// Twinkles per second.
+    pub twinkle_speed: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum BackgroundMode {
+    /// Random stars in parallax layers.
+    #[default]
+    Stars,
+    /// A scrolling chart of real stars.
+    Planetarium,
+}
+
+impl BackgroundMode {
+    pub const ALL: [BackgroundMode; 2] = [BackgroundMode::Stars, BackgroundMode::Planetarium];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BackgroundMode::Stars => "Stars",
+            BackgroundMode::Planetarium => "Planetarium",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PlanetariumSettings {
+    /// Asset path of the [`StarCatalog`].
+    pub catalog: String,
+    /// Degrees of right ascension the chart scrolls per second.
+    pub scroll_speed: f32,
+    /// Stars this many degrees north or south of the celestial equator are at the top
+    /// and bottom edges of the arena; ones beyond are left out. At most 90.
+    pub declination_range: f32,
+    /// Stars of a higher magnitude than this are left out.
+    pub faintest: f32,
+}
+
+impl Default for PlanetariumSettings {
+    fn default() -> Self {
+        Self {
+            catalog: "stars/planetarium.stars".into(),
+            scroll_speed: 0.5,
+            declination_range: 45.0,
+            faintest: 3.5,
+        }
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct BackgroundSettings {
+    pub mode: BackgroundMode,
+    pub color: Color,
+    /// Farthest first; each layer is drawn over the ones before it.
+    pub layers: Vec<StarLayer>,
+    pub planetarium: PlanetariumSettings,
+}
+
+impl Default for BackgroundSettings {
+    fn default() -> Self {
+        Self {
+            mode: BackgroundMode::default(),
+            color: Color::WHITE,
+            layers: vec![
+                StarLayer {
+                    density: 0.012,
+                    star_size: 2.0,
+                    drift: Vec2::new(1.5, 0.0),
+                    brightness: 0.35,
+                    twinkle: 0.6,
+                    twinkle_speed: 0.7,
+                },
+                StarLayer {
+                    density: 0.008,
+                    star_size: 3.0,
+                    drift: Vec2::new(4.0, 0.0),
+                    brightness: 0.6,
+                    twinkle: 0.4,
+                    twinkle_speed: 1.1,
+                },
+                StarLayer {
+                    density: 0.004,
+                    star_size: 4.0,
+                    drift: Vec2::new(9.0, 0.0),
+                    brightness: 0.9,
+                    twinkle: 0.2,
+                    twinkle_speed: 1.6,
+                },
+            ],
+            planetarium: PlanetariumSettings::default(),
+        }
+    }
+}
+
+#[derive(Asset, TypePath, AsBindGroup, Clone)]
+pub struct MovingPatternMaterial {
+    #[uniform(0)]
+    color: LinearRgba,
+    #[uniform(1)]
+    time: f32,
+    #[uniform(2)]
+    stars: StarLayersUniform,
+}
+
+impl Material2d for MovingPatternMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/stars_material.wgsl".into()
+    }
+}
+
+/// [`StarLayer`] as the shader reads it.
+#[derive(ShaderType, Clone, Copy, Default)]
+struct StarLayerUniform {
+    drift: Vec2,
+    density: f32,
+    star_size: f32,
+    brightness: f32,
+    twinkle: f32,
+    twinkle_speed: f32,
+    /// Keeps layers with the same star size from lining up.
+    seed: f32,
+}
+
+#[derive(ShaderType, Clone, Copy, Default)]
+struct StarLayersUniform {
+    layers: [StarLayerUniform; MAX_STAR_LAYERS],
+    count: u32,
+}
+
+impl From<&BackgroundSettings> for StarLayersUniform {
+    fn from(settings: &BackgroundSettings) -> Self {
+        let mut stars = Self::default();
+        for (i, layer) in settings.layers.iter().take(MAX_STAR_LAYERS).enumerate() {
+            stars.layers[i] = StarLayerUniform {
+                drift: layer.drift,
+                density: layer.density.clamp(0.0, 1.0),
+                star_size: layer.star_size.max(1.0),
+                brightness: layer.brightness,
+                twinkle: layer.twinkle.clamp(0.0, 1.0),
+                twinkle_speed: layer.twinkle_speed,
+                seed: 17.0 * (i + 1) as f32,
+            };
+            stars.count = i as u32 + 1;
+        }
+        stars
+    }
+}
+
+/// One star of a [`StarCatalog`].
+#[derive(Clone, Copy, Debug)]
+pub struct CatalogStar {
+    /// In hours, from 0 to 24.
+    pub right_ascension: f32,
+    /// In degrees, from -90 to 90.
+    pub declination: f32,
+    /// Apparent magnitude; brighter stars have lower ones.
+    pub magnitude: f32,
+}
+
+/// Stars for the planetarium, read from a `.stars` file: one star per line, with
+/// right ascension in hours, declination in degrees and magnitude separated by
+/// whitespace, and optionally a name after them. Blank lines and lines starting with
+/// `#` are skipped.
+#[derive(Asset, TypePath, Clone, Debug)]
+pub struct StarCatalog {
+    pub stars: Vec<CatalogStar>,
+}
+
+impl StarCatalog {
+    /// Lines that can't be read are skipped with a warning.
+    pub fn parse(text: &str) -> Self {
+        let mut stars = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split_whitespace().map(str::parse::<f32>);
+            match (fields.next(), fields.next(), fields.next()) {
+                (Some(Ok(right_ascension)), Some(Ok(declination)), Some(Ok(magnitude))) => {
+                    stars.push(CatalogStar {
+                        right_ascension: right_ascension.rem_euclid(24.0),
+                        declination: declination.clamp(-90.0, 90.0),
+                        magnitude,
+                    });
+                }
+                _ => warn!("skipped unreadable star catalog line {}", number + 1),
+            }
+        }
+        Self { stars }
+    }
+}
+
+#[derive(Default)]
+struct StarCatalogLoader;
+
+impl AssetLoader for StarCatalogLoader {
+    type Asset = StarCatalog;
+    type Settings = ();
+    type Error = io::Error;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> io::Result<StarCatalog> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text).await?;
+        Ok(StarCatalog::parse(&text))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["stars"]
+    }
+}
+
+/// The random starfield.
+#[derive(Component)]
+struct Starfield;
+
+/// One copy of the planetarium chart. There are two side by side, so the chart
+/// wraps around seamlessly as it scrolls.
+#[derive(Component)]
+struct PlanetariumStrip(usize);
+
+#[derive(Resource)]
+struct Planetarium {
+    /// Mesh of the chart, filled in once the catalog is loaded.
+    mesh: Handle<Mesh>,
+    catalog: Option<Handle<StarCatalog>>,
+    /// The chart needs to be laid out again.
+    stale: bool,
+}
+
+fn add_background(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<MovingPatternMaterial>>,
+    mut chart_materials: ResMut<Assets<ColorMaterial>>,
+    bounds: Res<ArenaBounds>,
+    settings: Res<BackgroundSettings>,
+) {
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes
+                .add(Mesh::from(Rectangle::from_size(bounds.size())))
+                .into(),
+            material: materials.add(MovingPatternMaterial {
+                color: settings.color.into(),
+                time: 0.0,
+                stars: settings.as_ref().into(),
+            }),
+            ..default()
+        },
+        Starfield,
+    ));
+
+    let mesh = meshes.reserve_handle();
+    // The stars bring their own color and brightness.
+    let material = chart_materials.add(ColorMaterial::from(Color::WHITE));
+    for copy in 0..2 {
+        commands.spawn((
+            MaterialMesh2dBundle {
+                mesh: Mesh2dHandle(mesh.clone()),
+                material: material.clone(),
+                transform: Transform::from_xyz(0.0, 0.0, 0.5),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            PlanetariumStrip(copy),
+        ));
+    }
+    commands.insert_resource(Planetarium {
+        mesh,
+        catalog: None,
+        stale: true,
+    });
+}
+
+/// Moves the random stars along and picks up changes to [`BackgroundSettings`].
+fn animate_background(
+    time: Res<Time>,
+    settings: Res<BackgroundSettings>,
+    mut materials: ResMut<Assets<MovingPatternMaterial>>,
+) {
+    for (_, material) in materials.iter_mut() {
+        material.time = time.elapsed_seconds_wrapped();
+        if settings.is_changed() {
+            material.color = settings.color.into();
+            material.stars = settings.as_ref().into();
+        }
+    }
+}
+
+/// Shows the background picked in [`BackgroundSettings`], loading the star catalog the
+/// first time the planetarium is shown.
+fn switch_background(
+    settings: Res<BackgroundSettings>,
+    asset_server: Res<AssetServer>,
+    mut planetarium: ResMut<Planetarium>,
+    mut starfield: Query<&mut Visibility, With<Starfield>>,
+    mut strips: Query<&mut Visibility, (With<PlanetariumStrip>, Without<Starfield>)>,
+) {
+    let chart = settings.mode == BackgroundMode::Planetarium;
+    let (starfield_visibility, chart_visibility) = if chart {
+        (Visibility::Hidden, Visibility::Inherited)
+    } else {
+        (Visibility::Inherited, Visibility::Hidden)
+    };
+    for mut visibility in &mut starfield {
+        visibility.set_if_neq(starfield_visibility);
+    }
+    for mut visibility in &mut strips {
+        visibility.set_if_neq(chart_visibility);
+    }
+
+    if chart {
+        let path = &settings.planetarium.catalog;
+        let loaded = planetarium
+            .catalog
+            .as_ref()
+            .and_then(|catalog| catalog.path())
+            .is_some_and(|loaded| loaded.path().to_str() == Some(path));
+        if !loaded {
+            planetarium.catalog = Some(asset_server.load(path.clone()));
+        }
+    }
+    planetarium.stale = true;
+}
+
+/// Lays the catalog out as the chart mesh once it has loaded, and again whenever it
+/// or the settings change.
+fn build_planetarium(
+    settings: Res<BackgroundSettings>,
+    bounds: Res<ArenaBounds>,
+    catalogs: Res<Assets<StarCatalog>>,
+    mut catalog_events: EventReader<AssetEvent<StarCatalog>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut planetarium: ResMut<Planetarium>,
+) {
+    let Some(handle) = &planetarium.catalog else {
+        return;
+    };
+    let updated = catalog_events
+        .read()
+        .any(|event| event.is_loaded_with_dependencies(handle) || event.is_modified(handle));
+    if !planetarium.stale && !updated {
+        return;
+    }
+    let Some(catalog) = catalogs.get(handle) else {
+        return;
+    };
+    let mesh = chart_mesh(catalog, &settings, &bounds);
+    meshes.insert(&planetarium.mesh, mesh);
+    planetarium.stale = false;
+}
+
+/// Arena units per degree of the sky, the same across and up.
+fn chart_scale(settings: &PlanetariumSettings, bounds: &ArenaBounds) -> f32 {
+    bounds.half_size.y / settings.declination_range.clamp(1.0, 90.0)
+}
+
+/// The whole sky as one strip, 24 hours of right ascension wide, starting at x = 0
+/// and running to the left like a sky chart held overhead, so east is on the left.
+/// Every star is a square sized and lit by its magnitude.
+fn chart_mesh(catalog: &StarCatalog, settings: &BackgroundSettings, bounds: &ArenaBounds) -> Mesh {
+    let planetarium = &settings.planetarium;
+    let scale = chart_scale(planetarium, bounds);
+    let color = settings.color.to_linear();
+
+    let mut positions = Vec::new();
+    let mut colors = Vec::new();
+    let mut indices = Vec::new();
+    for star in &catalog.stars {
+        if star.magnitude > planetarium.faintest
+            || star.declination.abs() > planetarium.declination_range
+        {
+            continue;
+        }
+        let center = Vec2::new(
+            -star.right_ascension * 15.0 * scale,
+            star.declination * scale,
+        );
+        // Sirius is about 5 units across, the faintest stars 1.
+        let glare = (planetarium.faintest - star.magnitude).max(0.0);
+        let half = 0.5 + 0.4 * glare;
+        let brightness = (0.3 + 0.15 * glare).min(1.0);
+
+        let first = positions.len() as u32;
+        for corner in [
+            Vec2::new(-half, -half),
+            Vec2::new(half, -half),
+            Vec2::new(half, half),
+            Vec2::new(-half, half),
+        ] {
+            positions.push((center + corner).extend(0.0).to_array());
+            colors.push((color * brightness).with_alpha(1.0).to_f32_array());
+        }
+        indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, colors)
+    .with_inserted_indices(Indices::U32(indices))
+}
+
+/// Turns the sky westward, moving the chart to the right.
+fn scroll_planetarium(
+    time: Res<Time>,
+    settings: Res<BackgroundSettings>,
+    bounds: Res<ArenaBounds>,
+    mut strips: Query<(&PlanetariumStrip, &mut Transform)>,
+) {
+    if settings.mode != BackgroundMode::Planetarium {
+        return;
+    }
+    let planetarium = &settings.planetarium;
+    let scale = chart_scale(planetarium, &bounds);
+    let width = 360.0 * scale;
+    let shift =
+        (time.elapsed_seconds_wrapped() * planetarium.scroll_speed * scale).rem_euclid(width);
+    // The strip runs left from its origin, so the first copy starts at the right edge
+    // and the second one continues past its left end. The chart is at least twice as
+    // wide as the arena is tall, so two copies cover any arena narrower than that.
+    for (strip, mut transform) in &mut strips {
+        transform.translation.x = bounds.half_size.x + shift - strip.0 as f32 * width;
+    }
+}
@@ -0,0 +1,82 @@
+//! Headless authoritative server for LAN matches.
+//!
+//! Usage: `spacewars-server [port] [name] [--spectator-delay <seconds>]
+//! [--stream <file | tcp:host:port>] [--arena <name | file.arena>] [--rules <preset>]`,
+//! then start two clients with `spacewars --lobby` to find it, or
+//! `spacewars --connect <server address>:<port>`, adding `--tcp` where UDP doesn't get
+//! through. Anyone else can watch with `spacewars --spectate <server address>:<port>`.
+//! Clients are turned away unless they were started with the same `--arena` and
+//! `--rules`.
+
+use bevy::{app::ScheduleRunnerPlugin, prelude::*};
+use std::time::Duration;
+
+use spacewars::arena::ArenaLayout;
+use spacewars::headless::windowless_plugins;
+use spacewars::protocol::DEFAULT_PORT;
+use spacewars::server::ServerPlugin;
+use spacewars::{AppState, RulesPreset, SimulationPlugin, TICK_RATE};
+
+/// Options that take a value, as opposed to the positional port and name.
+const FLAGS: [&str; 4] = ["--spectator-delay", "--stream", "--arena", "--rules"];
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let flag = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .and_then(|i| args.get(i + 1))
+    };
+    let mut positional = args.iter().enumerate().filter(|(i, arg)| {
+        let is_value = *i > 0 && FLAGS.contains(&args[i - 1].as_str());
+        !FLAGS.contains(&arg.as_str()) && !is_value
+    });
+
+    let port = positional
+        .next()
+        .and_then(|(_, arg)| arg.parse().ok())
+        .unwrap_or(DEFAULT_PORT);
+    let name = positional
+        .next()
+        .map(|(_, arg)| arg.clone())
+        .unwrap_or_else(|| "SpaceWars server".into());
+    let spectator_delay = flag("--spectator-delay")
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0.0);
+    let stream = flag("--stream").cloned();
+    // Clients have to be started with the same arena and rules.
+    let arena = match flag("--arena").map(|name| ArenaLayout::load(name)) {
+        Some(Ok(arena)) => arena,
+        Some(Err(err)) => {
+            eprintln!("spacewars-server: {err}");
+            std::process::exit(2);
+        }
+        None => ArenaLayout::default(),
+    };
+    let rules = match flag("--rules") {
+        Some(name) => RulesPreset::from_name(name).unwrap_or_else(|| {
+            eprintln!("spacewars-server: unknown rules preset {name}");
+            std::process::exit(2);
+        }),
+        None => RulesPreset::default(),
+    };
+
+    App::new()
+        .add_plugins(windowless_plugins())
+        .add_plugins(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(
+            1.0 / TICK_RATE,
+        )))
+        .add_plugins((
+            SimulationPlugin,
+            ServerPlugin {
+                port,
+                name,
+                spectator_delay,
+                stream,
+            },
+        ))
+        .insert_resource(arena)
+        .insert_resource(rules.rules())
+        .insert_state(AppState::Lobby)
+        .run();
+}
@@ -1,101 +1,130 @@
 use bevy::prelude::*;
-use crate::rocket::Rocket;
-use bevy_hanabi::prelude::*;
+use crate::rocket::{Player, Rocket, RocketDestroyed};
+use crate::input::ShipInput;
+use crate::arena::GravityWell;
+use crate::hull::{take_hit, Hit};
+use crate::powerup::{PowerUp, SPREAD_ANGLE};
+use crate::{gravity_at, AppState, ArenaBounds, Rules, SimClock};
 
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub struct Bullet {
-    pub speed: f32,
+    /// Starts straight ahead and is bent from there by the gravity wells.
+    pub velocity: Vec2,
 }
 
 pub fn spawn_bullet(
-    time: Res<Time>,
+    clock: Res<SimClock>,
     mut commands: Commands,
-    keys: Res<ButtonInput<KeyCode>>,
     asset_server: Res<AssetServer>,
-    mut query: Query<(&mut Transform, &mut Rocket)>,
+    mut query: Query<(&mut Transform, &mut Rocket, &ShipInput)>,
 ) {
     let bullet_sprite: Handle<Image> = asset_server.load("Bullet.png");
 
-    for (transform, mut rocket) in query.iter_mut() {
-        let current_time = time.elapsed();
+    for (transform, mut rocket, input) in query.iter_mut() {
+        let current_time = clock.elapsed();
 
-        if keys.just_pressed(rocket.spawn_key)
-           && current_time - rocket.last_shot_time >= rocket.cooldown
+        if input.pressed(ShipInput::FIRE)
+           && rocket.ammo > 0
+           && current_time - rocket.last_shot_time
+               >= rocket.boosts.reload_time(rocket.cooldown, clock.tick)
         {
-            let direction = transform.rotation.mul_vec3(Vec3::Y); // Rocket's forward direction
-            let bullet_spawn_pos = transform.translation + direction * 50.0; // Padding of 50 units
+            // A spread shot fans two more torpedoes out for the price of one.
+            let spread: &[f32] = if rocket.boosts.active(PowerUp::SpreadShot, clock.tick) {
+                &[0.0, -SPREAD_ANGLE, SPREAD_ANGLE]
+            } else {
+                &[0.0]
+            };
+            for angle in spread {
+                let rotation = transform.rotation * Quat::from_rotation_z(*angle);
+                let direction = rotation.mul_vec3(Vec3::Y); // Torpedo's forward direction
+                // Padding of 50 units
+                let bullet_spawn_pos = transform.translation + direction * 50.0;
 
-            commands.spawn((
-                SpriteBundle {
-                    texture: bullet_sprite.clone(),
-                    transform: Transform {
-                        translation: bullet_spawn_pos,
-                        scale: Vec3::new(2., 2., 1.0),
-                        rotation: transform.rotation,
+                commands.spawn((
+                    SpriteBundle {
+                        texture: bullet_sprite.clone(),
+                        transform: Transform {
+                            translation: bullet_spawn_pos,
+                            scale: Vec3::new(2., 2., 1.0),
+                            rotation,
+                            ..default()
+                        },
                         ..default()
                     },
-                    ..default()
-                },
-                Bullet {
-                    speed: 300.0, // Example bullet speed
-                },
-            ));
+                    Bullet {
+                        velocity: direction.truncate() * 300.0, // Example bullet speed
+                    },
+                    StateScoped(AppState::InGame),
+                ));
+            }
 
             // Update the last shot time
             rocket.last_shot_time = current_time;
+            rocket.ammo -= 1;
         }
     }
 }
 
 
+/// Torpedoes fly straight ahead, bent by the same pull rockets feel, and are gone
+/// once they leave the arena or hit a gravity well. They turn to face where they are
+/// heading.
 pub fn handle_bullet_movement(
     time: Res<Time>,
+    bounds: Res<ArenaBounds>,
+    rules: Res<Rules>,
     mut commands: Commands,
     mut query: Query<(Entity, &mut Transform, &mut Bullet)>,
+    wells: Query<&GravityWell>,
 ) {
-    for (entity, mut transform, bullet) in query.iter_mut() {
-        let forward = transform.rotation.mul_vec3(Vec3::Y).normalize();
-        transform.translation += forward * bullet.speed * time.delta_seconds();
-        if transform.translation.y > 510.0 || transform.translation.y < -380.0 {
+    let wells: Vec<GravityWell> = wells.iter().copied().collect();
+    for (entity, mut transform, mut bullet) in query.iter_mut() {
+        let dt = time.delta_seconds();
+        let pull = gravity_at(&wells, transform.translation.truncate(), rules.gravity);
+        bullet.velocity += pull * dt;
+        transform.translation += bullet.velocity.extend(0.0) * dt;
+        if let Some(heading) = bullet.velocity.try_normalize() {
+            transform.rotation = Quat::from_rotation_arc_2d(Vec2::Y, heading);
+        }
+        let position = transform.translation.truncate();
+        let swallowed = wells
+            .iter()
+            .any(|well| position.distance(well.position) < well.kill_radius);
+        if !bounds.contains(position) || swallowed {
             commands.entity(entity).despawn();
         }
     }
 }
 
+/// Torpedoes are spent on the rockets they hit, which take the damage.
 pub fn check_bullet_coll(
+    clock: Res<SimClock>,
+    rules: Res<Rules>,
     mut commands: Commands,
-    rocket_query: Query<(Entity, &Transform), (With<Rocket>, Without<EffectProperties>)>,
-    bullet_query: Query<&Transform, (With<Bullet>, Without<EffectProperties>)>,
-    mut effect: Query<(
-        &mut EffectProperties,
-        &mut EffectInitializers,
-        &mut Transform,
-    )>,
+    mut destroyed: EventWriter<RocketDestroyed>,
+    mut rocket_query: Query<(Entity, &Player, &mut Rocket, &Transform)>,
+    bullet_query: Query<(Entity, &Transform), With<Bullet>>,
 ) {
-    let Ok((mut properties, mut initializers, mut effect_transform)) = effect.get_single_mut()
-    else {
-        return;
-    };
-
-    for (entity, enemy_transform) in rocket_query.iter() {
-        for bullet_transform in bullet_query.iter() {            
+    let mut spent: Vec<Entity> = Vec::new();
+    for (entity, player, mut rocket, enemy_transform) in rocket_query.iter_mut() {
+        for (bullet, bullet_transform) in bullet_query.iter() {            
             let distance = enemy_transform
             .translation
             .truncate()
             .distance(bullet_transform.translation.truncate());
-            if distance < 30.
+            if distance < 30. && !spent.contains(&bullet)
             {
-                effect_transform.translation = enemy_transform.translation;
-
-                let r = 255.;
-                let g = 165.;
-                let b = 0.;
-                let color = 0xFF000000u32 | (b as u32) << 16 | (g as u32) << 8 | (r as u32);
-                properties.set("spawn_color", color.into());
-                initializers.reset();
-    
-                commands.entity(entity).despawn();
+                commands.entity(bullet).despawn();
+                spent.push(bullet);
+                if take_hit(&mut rocket, Hit::Torpedo, clock.tick, &rules) {
+                    destroyed.send(RocketDestroyed {
+                        player: *player,
+                        position: enemy_transform.translation,
+                    });
+                    commands.entity(entity).despawn();
+                    break;
+                }
             }
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,273 @@
+//! Command-line options of the game.
+//!
+//! Everything here only sets up how the game starts; none of it is saved.
+
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::PathBuf;
+
+use crate::ai::Difficulty;
+use crate::arena::ArenaLayout;
+use crate::client::Transport;
+use crate::netcode::{PeerLink, RollbackSettings};
+use crate::powerup::PowerUps;
+use crate::respawn::Respawn;
+use crate::rocket::Player;
+use crate::settings::{parse_resolution, DisplayMode};
+use crate::{Rules, RulesPreset};
+
+pub const USAGE: &str = "\
+Usage: spacewars [options]
+
+Without options the game opens on the title screen.
+
+Display:
+  --window <windowed|borderless|fullscreen>
+  --resolution <width>x<height>
+
+Local match:
+  --players <0|1|2>          start a match right away with this many players at the
+                             keyboard; computer pilots fly the other rockets
+  --difficulty <easy|normal|hard>
+                             skill of computer pilots, also used by Versus AI
+  --rules <classic|scarce|drift|arcade|armored|deathmatch>
+                             torpedoes, fuel, gravity, power-ups, armor and lives of
+                             the match
+  --power-ups <all|none|list>
+                             power-ups that turn up, overriding the rules; a list
+                             takes any of rapid-fire, shield, hyperspace, fuel and
+                             spread-shot, separated by commas
+  --armor <on|off>           hull points and regenerating shields, or the classic
+                             one-hit kills, overriding the rules
+  --lives <number|unlimited> destroyed rockets come back until their player has lost
+                             this many, overriding the rules
+  --arena <name|file.arena>  suns, planets, black holes, wormholes and asteroids,
+                             from assets/arenas: classic, binary, triangle, solar,
+                             singularity, belt or empty
+  --seed <number>            makes everything random in the match repeatable
+  --headless                 no window: simulate the match as fast as possible and
+                             print the score once it is decided
+  --replay <file>            play back a match saved with the server's --stream
+
+Online:
+  --p2p <player 0|1> <local port> <peer address>
+  --input-delay <ticks>      with --p2p
+  --max-prediction <ticks>   with --p2p
+  --connect <address>:<port>
+  --spectate <address>:<port>
+  --tcp                      with --connect or --spectate: reach the server over TCP
+                             instead of UDP
+  --lobby
+
+  -h, --help                 show this text
+";
+
+#[derive(Default, Debug)]
+pub struct LaunchOptions {
+    pub display: Option<DisplayMode>,
+    pub resolution: Option<(u32, u32)>,
+    /// Players at the keyboard, if a local match should start right away.
+    pub players: Option<usize>,
+    pub difficulty: Difficulty,
+    pub rules: RulesPreset,
+    /// Overrides the power-ups of the rules preset.
+    pub power_ups: Option<PowerUps>,
+    /// Overrides whether the rules preset gives rockets armor.
+    pub armor: Option<bool>,
+    /// Overrides the lives of the rules preset, turning respawns on. `Some(None)` for
+    /// unlimited lives.
+    pub lives: Option<Option<u32>>,
+    pub arena: Option<ArenaLayout>,
+    pub seed: Option<u64>,
+    pub headless: bool,
+    pub replay: Option<PathBuf>,
+    /// Peer-to-peer match to play.
+    pub p2p: Option<PeerLink>,
+    /// Input delay and prediction of the `--p2p` session.
+    pub rollback: RollbackSettings,
+    /// Server to play on.
+    pub connect: Option<SocketAddr>,
+    /// Server to watch.
+    pub spectate: Option<SocketAddr>,
+    /// How to reach the server of `connect` or `spectate`.
+    pub transport: Transport,
+    /// Whether to open the server browser.
+    pub lobby: bool,
+}
+
+impl LaunchOptions {
+    /// Reads the options of this process. Prints the usage and exits on `--help` or
+    /// on anything it can't make sense of.
+    pub fn from_args() -> Self {
+        match Self::parse(std::env::args().skip(1)) {
+            Ok(Some(options)) => options,
+            Ok(None) => {
+                print!("{USAGE}");
+                std::process::exit(0);
+            }
+            Err(err) => {
+                eprintln!("spacewars: {err}\n\n{USAGE}");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    /// `Ok(None)` when help was asked for.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Option<Self>, String> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = |what: &str| args.next().ok_or_else(|| format!("{arg} needs {what}"));
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "--window" => {
+                    let name = value("a window mode")?;
+                    options.display = Some(
+                        DisplayMode::from_name(&name)
+                            .ok_or_else(|| format!("unknown window mode {name}"))?,
+                    );
+                }
+                "--resolution" => {
+                    let size = value("a size like 1280x720")?;
+                    options.resolution = Some(
+                        parse_resolution(&size)
+                            .filter(|(width, height)| *width > 0 && *height > 0)
+                            .ok_or_else(|| format!("invalid resolution {size}"))?,
+                    );
+                }
+                "--players" => {
+                    let count = value("a number of players")?;
+                    options.players = Some(
+                        count
+                            .parse()
+                            .ok()
+                            .filter(|count| *count <= 2)
+                            .ok_or_else(|| format!("--players takes 0, 1 or 2, not {count}"))?,
+                    );
+                }
+                "--difficulty" => {
+                    let name = value("a difficulty")?;
+                    options.difficulty = Difficulty::from_name(&name)
+                        .ok_or_else(|| format!("unknown difficulty {name}"))?;
+                }
+                "--rules" => {
+                    let name = value("a rules preset")?;
+                    options.rules = RulesPreset::from_name(&name)
+                        .ok_or_else(|| format!("unknown rules preset {name}"))?;
+                }
+                "--power-ups" => options.power_ups = Some(PowerUps::parse(&value("power-ups")?)?),
+                "--armor" => {
+                    let toggle = value("on or off")?;
+                    options.armor = Some(match toggle.as_str() {
+                        "on" => true,
+                        "off" => false,
+                        _ => return Err(format!("--armor takes on or off, not {toggle}")),
+                    });
+                }
+                "--lives" => {
+                    let lives = value("a number of lives")?;
+                    options.lives = Some(match lives.as_str() {
+                        "unlimited" => None,
+                        _ => Some(
+                            lives
+                                .parse()
+                                .ok()
+                                .filter(|lives| *lives > 0)
+                                .ok_or_else(|| format!("invalid number of lives {lives}"))?,
+                        ),
+                    });
+                }
+                "--arena" => options.arena = Some(ArenaLayout::load(&value("an arena")?)?),
+                "--seed" => {
+                    let seed = value("a number")?;
+                    options.seed = Some(seed.parse().map_err(|_| format!("invalid seed {seed}"))?);
+                }
+                "--headless" => options.headless = true,
+                "--replay" => options.replay = Some(PathBuf::from(value("a file")?)),
+                "--p2p" => {
+                    let player = value("a player, 0 or 1")?;
+                    let port = value("a local port")?;
+                    let remote = value("a peer address")?;
+                    options.p2p = Some(PeerLink {
+                        local_player: match player.as_str() {
+                            "0" => Player(0),
+                            "1" => Player(1),
+                            _ => return Err(format!("--p2p takes player 0 or 1, not {player}")),
+                        },
+                        local_port: port.parse().map_err(|_| format!("invalid port {port}"))?,
+                        remote: parse_address(&remote)?,
+                    });
+                }
+                "--input-delay" => {
+                    let ticks = value("a number of ticks")?;
+                    options.rollback.input_delay = ticks
+                        .parse()
+                        .map_err(|_| format!("invalid input delay {ticks}"))?;
+                }
+                "--max-prediction" => {
+                    let ticks = value("a number of ticks")?;
+                    options.rollback.max_prediction = ticks
+                        .parse()
+                        .ok()
+                        .filter(|ticks| *ticks > 0)
+                        .ok_or_else(|| format!("invalid prediction window {ticks}"))?;
+                }
+                "--connect" => options.connect = Some(parse_address(&value("an address")?)?),
+                "--spectate" => options.spectate = Some(parse_address(&value("an address")?)?),
+                "--tcp" => options.transport = Transport::Tcp,
+                "--lobby" => options.lobby = true,
+                other => return Err(format!("unknown option {other}")),
+            }
+        }
+
+        if options.headless && options.replay.is_some() {
+            return Err("--replay needs a window".into());
+        }
+        if options.connect.is_some() && options.spectate.is_some() {
+            return Err("--connect and --spectate can't be combined".into());
+        }
+        if options.transport == Transport::Tcp
+            && options.connect.is_none()
+            && options.spectate.is_none()
+        {
+            return Err("--tcp needs --connect or --spectate".into());
+        }
+        Ok(Some(options))
+    }
+
+    /// Rules of the picked preset, with the power-ups, armor and lives overridden if
+    /// asked for.
+    pub fn match_rules(&self) -> Rules {
+        let mut rules = self.rules.rules();
+        if let Some(power_ups) = self.power_ups {
+            rules.power_ups = power_ups;
+        }
+        if let Some(armor) = self.armor {
+            rules.armor = armor.then(|| rules.armor.unwrap_or_default());
+        }
+        if let Some(lives) = self.lives {
+            rules.respawn = Some(Respawn {
+                lives,
+                ..rules.respawn.unwrap_or_default()
+            });
+        }
+        rules
+    }
+
+    /// Keyboard players in a headless match default to none, since nobody can press
+    /// anything there.
+    pub fn human_players(&self) -> Option<usize> {
+        match self.players {
+            None if self.headless => Some(0),
+            players => players,
+        }
+    }
+}
+
+/// `<address>:<port>`, where the address may also be a host name.
+fn parse_address(value: &str) -> Result<SocketAddr, String> {
+    value
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addresses| addresses.next())
+        .ok_or_else(|| format!("invalid address {value}"))
+}
@@ -0,0 +1,642 @@
+//! Client side of a match hosted by the dedicated server.
+//!
+//! The client never decides collisions. It sends its input every tick and predicts
+//! its own rocket by applying that input locally right away; when a snapshot
+//! arrives the rocket is reset to the server's state and the inputs the server hasn't
+//! processed yet are applied again on top. Everything else is drawn a few ticks in
+//! the past, interpolated between the two snapshots around that moment.
+//!
+//! The connection is a UDP socket, or a TCP connection where datagrams don't get
+//! through; the messages are the same either way.
+//!
+//! A spectator connection never takes a seat or sends input; every rocket is drawn
+//! from the (delayed) snapshots the server sends to spectators. A match stream saved
+//! to a file is played back the same way, as a spectator of a recording.
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use std::collections::VecDeque;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, TcpStream, UdpSocket};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::arena::{warp, ArenaLayout, GravityWell, Wormhole};
+use crate::asteroid::Asteroid;
+use crate::input::{LocalInput, ShipInput};
+use crate::protocol::{
+    match_setup, ClientMessage, LobbyPlayer, RocketState, ServerMessage, Snapshot,
+    INPUT_REDUNDANCY, MAX_PACKET,
+};
+use crate::respawn::Respawns;
+use crate::rocket::{wrap_position, Hangar, Loadouts, Player, Rocket, RocketDestroyed};
+use crate::stream::read_recording;
+use crate::tcp::TcpLink;
+use crate::{
+    apply_gravity, handle_rocket_movement, AppState, ArenaBounds, Rules, Scores, SimClock,
+    TICK_RATE,
+};
+
+/// How far behind the newest snapshot remote objects are drawn.
+const INTERPOLATION_DELAY_TICKS: f64 = 6.0;
+const SNAPSHOT_BUFFER: usize = 32;
+/// How long connecting over TCP may take before giving up.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How a client reaches the server, which takes both.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Transport {
+    #[default]
+    Udp,
+    Tcp,
+}
+
+/// Where server messages come from.
+enum Link {
+    Udp {
+        socket: UdpSocket,
+        server: SocketAddr,
+    },
+    Tcp(TcpLink),
+    /// The TCP connection to the server is gone, or we left; nothing more will come.
+    Closed,
+    /// A saved match stream, handed out at the pace it was recorded.
+    Recording {
+        messages: VecDeque<ServerMessage>,
+        started: Instant,
+        /// Tick of the first recorded snapshot.
+        first_tick: u32,
+    },
+}
+
+#[derive(Resource)]
+pub struct ServerConnection {
+    link: Link,
+    /// Assigned by the server once it accepted us. Always `None` for spectators.
+    pub player: Option<Player>,
+    pub spectator: bool,
+    /// Latest lobby roster sent by the server.
+    pub lobby: Vec<LobbyPlayer>,
+    /// Whether we left again because the server plays another arena or other rules.
+    pub mismatched: bool,
+    seq: u32,
+    /// Inputs the server hasn't confirmed yet, replayed on top of every snapshot.
+    pending: VecDeque<(u32, ShipInput)>,
+    snapshots: VecDeque<Snapshot>,
+    render_tick: f64,
+    ticks_since_join: u32,
+}
+
+impl ServerConnection {
+    pub fn connect(server: SocketAddr, transport: Transport) -> std::io::Result<Self> {
+        Self::open(server, transport, false)
+    }
+
+    /// Watches the match on `server` without taking part.
+    pub fn spectate(server: SocketAddr, transport: Transport) -> std::io::Result<Self> {
+        Self::open(server, transport, true)
+    }
+
+    /// Plays back a match stream saved by the server's `--stream`.
+    pub fn replay(path: &Path) -> std::io::Result<Self> {
+        let messages = read_recording(path)?;
+        let first_tick = messages
+            .iter()
+            .find_map(|message| match message {
+                ServerMessage::Snapshot(snapshot) => Some(snapshot.tick),
+                _ => None,
+            })
+            .unwrap_or(0);
+        Ok(Self::with_link(
+            Link::Recording {
+                messages: messages.into(),
+                started: Instant::now(),
+                first_tick,
+            },
+            true,
+        ))
+    }
+
+    fn open(server: SocketAddr, transport: Transport, spectator: bool) -> std::io::Result<Self> {
+        let link = match transport {
+            Transport::Udp => {
+                let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+                socket.set_nonblocking(true)?;
+                Link::Udp { socket, server }
+            }
+            Transport::Tcp => {
+                let stream = TcpStream::connect_timeout(&server, CONNECT_TIMEOUT)?;
+                Link::Tcp(TcpLink::new(stream)?)
+            }
+        };
+        Ok(Self::with_link(link, spectator))
+    }
+
+    fn with_link(link: Link, spectator: bool) -> Self {
+        Self {
+            link,
+            player: None,
+            spectator,
+            lobby: Vec::new(),
+            mismatched: false,
+            seq: 0,
+            pending: VecDeque::new(),
+            snapshots: VecDeque::new(),
+            render_tick: 0.0,
+            ticks_since_join: u32::MAX,
+        }
+    }
+
+    pub fn send(&mut self, message: &ClientMessage) {
+        match &mut self.link {
+            Link::Udp { socket, server } => {
+                if let Err(err) = socket.send_to(&message.encode(), *server) {
+                    if err.kind() != ErrorKind::WouldBlock {
+                        warn!("client socket error: {err}");
+                    }
+                }
+            }
+            Link::Tcp(link) => {
+                if let Err(err) = link.send(&message.encode()) {
+                    warn!("lost the connection to the server: {err}");
+                    self.link = Link::Closed;
+                }
+            }
+            Link::Recording { .. } | Link::Closed => {}
+        }
+    }
+
+    fn receive(&mut self) -> Vec<ServerMessage> {
+        let (socket, server) = match &mut self.link {
+            Link::Udp { socket, server } => (socket, *server),
+            Link::Tcp(link) => {
+                let mut messages = Vec::new();
+                let status = link.receive(&mut messages);
+                if let Err(err) = status {
+                    warn!("lost the connection to the server: {err}");
+                    self.link = Link::Closed;
+                }
+                return messages
+                    .iter()
+                    .filter_map(|message| ServerMessage::decode(message))
+                    .collect();
+            }
+            Link::Closed => return Vec::new(),
+            Link::Recording {
+                messages,
+                started,
+                first_tick,
+            } => {
+                let elapsed = (started.elapsed().as_secs_f64() * TICK_RATE) as u32;
+                return replay_due(messages, first_tick.saturating_add(elapsed));
+            }
+        };
+        let mut buf = [0u8; MAX_PACKET];
+        let mut messages = Vec::new();
+        loop {
+            match socket.recv_from(&mut buf) {
+                Ok((len, from)) if from == server => {
+                    messages.extend(ServerMessage::decode(&buf[..len]));
+                }
+                Ok(_) => {}
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::ConnectionReset => {}
+                Err(err) => {
+                    warn!("client socket error: {err}");
+                    break;
+                }
+            }
+        }
+        messages
+    }
+}
+
+/// Takes the recorded messages up to the snapshot of `tick`.
+fn replay_due(messages: &mut VecDeque<ServerMessage>, tick: u32) -> Vec<ServerMessage> {
+    let mut due = Vec::new();
+    while let Some(message) = messages.front() {
+        if matches!(message, ServerMessage::Snapshot(snapshot) if snapshot.tick > tick) {
+            break;
+        }
+        due.extend(messages.pop_front());
+    }
+    due
+}
+
+impl Drop for ServerConnection {
+    fn drop(&mut self) {
+        if self.player.is_some() || self.spectator {
+            self.send(&ClientMessage::Leave);
+        }
+    }
+}
+
+/// Marks a torpedo drawn from snapshots, keyed by the server's id for it.
+#[derive(Component)]
+pub struct NetBullet(pub u64);
+
+/// Same for asteroids.
+#[derive(Component)]
+pub struct NetAsteroid(pub u64);
+
+/// Same for power-ups.
+#[derive(Component)]
+pub struct NetPowerUp(pub u64);
+
+type NetAsteroids<'a> = (Entity, &'a NetAsteroid, &'a mut Transform);
+
+fn apply_rocket_state(state: &RocketState, rocket: &mut Rocket, transform: &mut Transform) {
+    transform.translation = state.translation.extend(transform.translation.z);
+    transform.rotation = Quat::from_rotation_z(state.angle);
+    rocket.velocity = state.velocity;
+    rocket.speed = state.speed;
+    rocket.rotation_speed = state.rotation_speed;
+    apply_rocket_supplies(state, rocket);
+}
+
+fn apply_rocket_supplies(state: &RocketState, rocket: &mut Rocket) {
+    rocket.ammo = state.ammo;
+    rocket.fuel = state.fuel;
+    rocket.last_shot_time = state.last_shot_time;
+    rocket.hyperspace = state.hyperspace;
+    rocket.boosts = state.boosts;
+    rocket.hull = state.hull;
+    rocket.shield = state.shield;
+    rocket.protected_until = state.protected_until;
+}
+
+/// What's needed to take the same steps the server's simulation takes.
+#[derive(SystemParam)]
+pub struct Prediction<'w, 's> {
+    bounds: Res<'w, ArenaBounds>,
+    layout: Res<'w, ArenaLayout>,
+    rules: Res<'w, Rules>,
+    wells: Query<'w, 's, &'static GravityWell>,
+    wormholes: Query<'w, 's, &'static Wormhole>,
+}
+
+impl Prediction<'_, '_> {
+    /// Same steps the server's simulation takes for one rocket in one tick.
+    fn predict(&self, input: &ShipInput, rocket: &mut Rocket, transform: &mut Transform) {
+        let dt = (1.0 / TICK_RATE) as f32;
+        handle_rocket_movement(dt, input, rocket, transform);
+        wrap_position(&self.bounds, transform);
+        let wells: Vec<GravityWell> = self.wells.iter().copied().collect();
+        apply_gravity(rocket, transform, &wells, self.rules.gravity, dt);
+        let wormholes: Vec<Wormhole> = self.wormholes.iter().copied().collect();
+        warp(&wormholes, transform);
+    }
+
+    /// Whether a server with `setup` plays the arena and rules predicted with.
+    fn agrees(&self, setup: u64) -> bool {
+        setup == match_setup(&self.layout, &self.rules)
+    }
+}
+
+pub fn client_receive(
+    mut commands: Commands,
+    mut connection: ResMut<ServerConnection>,
+    prediction: Prediction,
+    mut destroyed: EventWriter<RocketDestroyed>,
+    mut rockets: Query<(Entity, &Player, &mut Rocket, &mut Transform)>,
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    for message in connection.receive() {
+        match message {
+            ServerMessage::Welcome { player, setup } => {
+                if !prediction.agrees(setup) {
+                    error!("the server plays another arena or other rules");
+                    connection.send(&ClientMessage::Leave);
+                    connection.link = Link::Closed;
+                    connection.mismatched = true;
+                    return;
+                }
+                if connection.player.is_none() {
+                    info!("joined the server as player {}", player.0);
+                }
+                connection.player = Some(player);
+            }
+            ServerMessage::Full => {
+                if connection.player.is_none() {
+                    warn!("the server is full");
+                }
+            }
+            ServerMessage::Lobby(players) => {
+                connection.lobby = players;
+            }
+            ServerMessage::MatchStart(loadouts) => {
+                if *state.get() == AppState::Lobby {
+                    commands.insert_resource(Loadouts(loadouts));
+                    next_state.set(AppState::InGame);
+                }
+            }
+            ServerMessage::Announce { .. } => {}
+            ServerMessage::Snapshot(snapshot) => {
+                if connection
+                    .snapshots
+                    .back()
+                    .is_some_and(|latest| latest.tick >= snapshot.tick)
+                {
+                    continue;
+                }
+
+                for (entity, player, mut rocket, mut transform) in rockets.iter_mut() {
+                    let Some(state) = snapshot.rockets.iter().find(|s| s.player == *player) else {
+                        destroyed.send(RocketDestroyed {
+                            player: *player,
+                            position: transform.translation,
+                        });
+                        commands.entity(entity).despawn();
+                        continue;
+                    };
+
+                    if Some(*player) == connection.player {
+                        connection.pending.retain(|(seq, _)| *seq > snapshot.ack);
+                        apply_rocket_state(state, &mut rocket, &mut transform);
+                        for (_, input) in connection.pending.iter() {
+                            prediction.predict(input, &mut rocket, &mut transform);
+                        }
+                    }
+                }
+
+                connection.snapshots.push_back(snapshot);
+                while connection.snapshots.len() > SNAPSHOT_BUFFER {
+                    connection.snapshots.pop_front();
+                }
+            }
+        }
+    }
+}
+
+/// Asks to join about once a second until the server answers, and keeps repeating it
+/// in the lobby so the server knows we are still there. Spectators repeat it for the
+/// whole match, since they send nothing else.
+pub fn client_join(mut connection: ResMut<ServerConnection>) {
+    connection.ticks_since_join = connection.ticks_since_join.saturating_add(1);
+    if connection.ticks_since_join as f64 >= TICK_RATE {
+        connection.ticks_since_join = 0;
+        let message = if connection.spectator {
+            ClientMessage::Spectate
+        } else {
+            ClientMessage::Join
+        };
+        connection.send(&message);
+    }
+}
+
+pub fn client_tick(
+    mut connection: ResMut<ServerConnection>,
+    prediction: Prediction,
+    mut local: ResMut<LocalInput>,
+    mut rockets: Query<(&Player, &mut Rocket, &mut Transform)>,
+) {
+    let Some(player) = connection.player else {
+        return;
+    };
+
+    let input = local.take(player);
+    connection.seq += 1;
+    let seq = connection.seq;
+    connection.pending.push_back((seq, input));
+
+    let inputs = connection
+        .pending
+        .iter()
+        .rev()
+        .take(INPUT_REDUNDANCY)
+        .rev()
+        .map(|(_, input)| *input)
+        .collect();
+    connection.send(&ClientMessage::Input { seq, inputs });
+
+    for (rocket_player, mut rocket, mut transform) in rockets.iter_mut() {
+        if *rocket_player == player {
+            prediction.predict(&input, &mut rocket, &mut transform);
+        }
+    }
+}
+
+pub fn client_interpolate(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    asset_server: Res<AssetServer>,
+    mut connection: ResMut<ServerConnection>,
+    mut rockets: Query<(&Player, &mut Rocket, &mut Transform)>,
+    mut bullets: Query<(Entity, &NetBullet, &mut Transform), Without<Rocket>>,
+    mut asteroids: Query<NetAsteroids, (Without<Rocket>, Without<NetBullet>)>,
+) {
+    let Some(latest) = connection.snapshots.back().map(|s| s.tick as f64) else {
+        return;
+    };
+
+    let target = latest - INTERPOLATION_DELAY_TICKS;
+    connection.render_tick += time.delta_seconds_f64() * TICK_RATE;
+    if (connection.render_tick - target).abs() > INTERPOLATION_DELAY_TICKS {
+        connection.render_tick = target;
+    }
+    connection.render_tick = connection.render_tick.min(latest);
+    let render_tick = connection.render_tick;
+
+    let snapshots = &connection.snapshots;
+    let next = snapshots
+        .iter()
+        .position(|s| s.tick as f64 > render_tick)
+        .unwrap_or(snapshots.len() - 1);
+    let to = &snapshots[next];
+    let from = &snapshots[next.saturating_sub(1)];
+    let alpha = if to.tick > from.tick {
+        ((render_tick - from.tick as f64) / (to.tick - from.tick) as f64).clamp(0.0, 1.0) as f32
+    } else {
+        1.0
+    };
+
+    for (player, mut rocket, mut transform) in rockets.iter_mut() {
+        if Some(*player) == connection.player {
+            continue;
+        }
+        let (Some(a), Some(b)) = (
+            from.rockets.iter().find(|s| s.player == *player),
+            to.rockets.iter().find(|s| s.player == *player),
+        ) else {
+            continue;
+        };
+        apply_rocket_supplies(b, &mut rocket);
+        // Don't smear a rocket across the screen when it wraps around the edge.
+        let translation = if a.translation.distance(b.translation) > 100.0 {
+            b.translation
+        } else {
+            a.translation.lerp(b.translation, alpha)
+        };
+        transform.translation = translation.extend(transform.translation.z);
+        transform.rotation =
+            Quat::from_rotation_z(a.angle).slerp(Quat::from_rotation_z(b.angle), alpha);
+    }
+
+    let mut drawn: HashMap<u64, Entity> = HashMap::new();
+    for (entity, asteroid, mut transform) in asteroids.iter_mut() {
+        let Some(b) = to.asteroids.iter().find(|s| s.id == asteroid.0) else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+        let translation = match from.asteroids.iter().find(|s| s.id == asteroid.0) {
+            // Don't smear an asteroid across the screen when it wraps around the edge.
+            Some(a) if a.translation.distance(b.translation) <= 100.0 => {
+                a.translation.lerp(b.translation, alpha)
+            }
+            _ => b.translation,
+        };
+        transform.translation = translation.extend(transform.translation.z);
+        drawn.insert(asteroid.0, entity);
+    }
+    for state in to.asteroids.iter().filter(|s| !drawn.contains_key(&s.id)) {
+        let asteroid = Asteroid {
+            velocity: Vec2::ZERO,
+            radius: state.radius,
+            splits: 0,
+        };
+        commands.spawn((asteroid.bundle(state.translation), NetAsteroid(state.id)));
+    }
+
+    let mut drawn: HashMap<u64, Entity> = HashMap::new();
+    for (entity, bullet, mut transform) in bullets.iter_mut() {
+        let Some(b) = to.bullets.iter().find(|s| s.id == bullet.0) else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+        let translation = match from.bullets.iter().find(|s| s.id == bullet.0) {
+            Some(a) => a.translation.lerp(b.translation, alpha),
+            None => b.translation,
+        };
+        transform.translation = translation.extend(transform.translation.z);
+        // Torpedoes turn as the gravity wells bend their path.
+        transform.rotation = Quat::from_rotation_z(b.angle);
+        drawn.insert(bullet.0, entity);
+    }
+
+    let bullet_sprite: Handle<Image> = asset_server.load("Bullet.png");
+    for state in to.bullets.iter().filter(|s| !drawn.contains_key(&s.id)) {
+        commands.spawn((
+            SpriteBundle {
+                texture: bullet_sprite.clone(),
+                transform: Transform {
+                    translation: state.translation.extend(1.0),
+                    scale: Vec3::new(2., 2., 1.0),
+                    rotation: Quat::from_rotation_z(state.angle),
+                },
+                ..default()
+            },
+            NetBullet(state.id),
+            StateScoped(AppState::InGame),
+        ));
+    }
+}
+
+/// Shows the power-ups of the newest snapshot. They don't move, so there is nothing
+/// to interpolate.
+pub fn client_sync_power_ups(
+    mut commands: Commands,
+    connection: Res<ServerConnection>,
+    power_ups: Query<(Entity, &NetPowerUp)>,
+) {
+    let Some(latest) = connection.snapshots.back() else {
+        return;
+    };
+    let mut drawn: Vec<u64> = Vec::new();
+    for (entity, power_up) in &power_ups {
+        if latest.power_ups.iter().any(|s| s.id == power_up.0) {
+            drawn.push(power_up.0);
+        } else {
+            commands.entity(entity).despawn();
+        }
+    }
+    for state in latest.power_ups.iter().filter(|s| !drawn.contains(&s.id)) {
+        commands.spawn((state.kind.bundle(state.translation), NetPowerUp(state.id)));
+    }
+}
+
+/// Follows the server's match clock and score, which the HUD shows.
+pub fn client_sync_match(
+    connection: Res<ServerConnection>,
+    mut clock: ResMut<SimClock>,
+    mut scores: ResMut<Scores>,
+    mut respawns: ResMut<Respawns>,
+) {
+    if let Some(latest) = connection.snapshots.back() {
+        clock.tick = latest.tick;
+        scores.0 = latest.scores;
+        *respawns = latest.respawns;
+    }
+}
+
+/// Launches the rockets the server brought back after they were destroyed. They are
+/// put in place by the next snapshot, like any other rocket.
+pub fn client_respawn(
+    mut commands: Commands,
+    connection: Res<ServerConnection>,
+    hangar: Hangar,
+    rockets: Query<&Player, With<Rocket>>,
+) {
+    let Some(latest) = connection.snapshots.back() else {
+        return;
+    };
+    for state in &latest.rockets {
+        if !rockets.iter().any(|player| *player == state.player) {
+            hangar.launch(&mut commands, state.player, state.translation);
+        }
+    }
+}
+
+pub struct ClientPlugin;
+
+impl Plugin for ClientPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PreUpdate,
+            client_receive.run_if(resource_exists::<ServerConnection>),
+        )
+        .add_systems(
+            FixedUpdate,
+            (
+                client_join.run_if(
+                    in_state(AppState::Lobby)
+                        .or_else(|connection: Res<ServerConnection>| connection.player.is_none()),
+                ),
+                client_tick.run_if(in_state(AppState::InGame)),
+            )
+                .run_if(resource_exists::<ServerConnection>),
+        )
+        .add_systems(
+            Update,
+            (
+                client_interpolate,
+                client_sync_power_ups,
+                client_sync_match,
+                client_respawn,
+            )
+                .run_if(resource_exists::<ServerConnection>)
+                .run_if(in_state(AppState::InGame)),
+        );
+    }
+}
+
+/// Connects to `server` to play, or to watch as a `spectator`. Logs why it couldn't.
+pub fn open_connection(
+    server: SocketAddr,
+    transport: Transport,
+    spectator: bool,
+) -> Option<ServerConnection> {
+    let connection = if spectator {
+        ServerConnection::spectate(server, transport)
+    } else {
+        ServerConnection::connect(server, transport)
+    };
+    match connection {
+        Ok(connection) => Some(connection),
+        Err(err) => {
+            error!("could not connect to {server}: {err}");
+            None
+        }
+    }
+}
@@ -0,0 +1,82 @@
+//! Running without a window, for the dedicated server and for simulated matches.
+//!
+//! A headless match runs its ticks back to back instead of in real time, so scripts
+//! can play out computer-vs-computer matches in a fraction of a second. It ends as
+//! soon as a rocket is destroyed for good, or as a draw after [`TIME_LIMIT`], and
+//! prints the score on a single line.
+
+use bevy::app::PluginGroupBuilder;
+use bevy::prelude::*;
+use bevy::render::{settings::WgpuSettings, RenderPlugin};
+use bevy::time::TimeUpdateStrategy;
+use bevy::window::ExitCondition;
+use bevy::winit::WinitPlugin;
+use std::time::Duration;
+
+use crate::respawn::Respawns;
+use crate::rocket::Rocket;
+use crate::{AppState, Scores, SimClock, TICK_RATE};
+
+/// Simulated time after which an undecided match is called a draw.
+pub const TIME_LIMIT: Duration = Duration::from_secs(300);
+
+/// Bevy's default plugins without a window or a GPU.
+pub fn windowless_plugins() -> PluginGroupBuilder {
+    DefaultPlugins
+        .set(WindowPlugin {
+            primary_window: None,
+            exit_condition: ExitCondition::DontExit,
+            ..default()
+        })
+        .set(RenderPlugin {
+            render_creation: WgpuSettings {
+                backends: None,
+                ..default()
+            }
+            .into(),
+            ..default()
+        })
+        .disable::<WinitPlugin>()
+}
+
+/// Advances the game by exactly one tick per update and quits once the match is
+/// decided.
+pub struct HeadlessPlugin;
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+            1.0 / TICK_RATE,
+        )))
+        .add_systems(Update, report_result.run_if(in_state(AppState::InGame)));
+    }
+}
+
+fn report_result(
+    clock: Res<SimClock>,
+    scores: Res<Scores>,
+    respawns: Res<Respawns>,
+    rockets: Query<(), With<Rocket>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if clock.tick == 0 {
+        return;
+    }
+    let decided = rockets.iter().count() < 2 && !respawns.pending();
+    if !decided && clock.elapsed() < TIME_LIMIT {
+        return;
+    }
+    let outcome = match scores.0 {
+        [p1, p2] if p1 > p2 => "P1 wins",
+        [p1, p2] if p2 > p1 => "P2 wins",
+        _ => "draw",
+    };
+    println!(
+        "{outcome}: {} - {} after {:.2}s ({} ticks)",
+        scores.0[0],
+        scores.0[1],
+        clock.elapsed().as_secs_f32(),
+        clock.tick
+    );
+    exit.send(AppExit::Success);
+}
@@ -0,0 +1,252 @@
+//! In-game overlay: each player's score, torpedo reload, torpedoes, fuel, power-ups
+//! and, with armor or lives in the rules, hull, shield and lives in their own corner,
+//! and the round timer at the top.
+
+use bevy::prelude::*;
+
+use crate::powerup::PowerUp;
+use crate::respawn::Respawns;
+use crate::rocket::{Loadouts, Player, Rocket};
+use crate::{AppState, Rules, Scores, SimClock};
+
+/// Player 0 starts top left and player 1 bottom right; their panels sit there too.
+const PANEL_CORNERS: [(JustifyContent, AlignItems); 2] = [
+    (JustifyContent::FlexStart, AlignItems::FlexStart),
+    (JustifyContent::FlexEnd, AlignItems::FlexEnd),
+];
+/// Used for a player who kept the untinted hull, so the two panels still differ.
+const DEFAULT_PLAYER_COLORS: [Color; 2] = [Color::srgb(0.4, 0.9, 1.0), Color::srgb(1.0, 0.6, 0.2)];
+const BAR_WIDTH: f32 = 120.0;
+const BAR_HEIGHT: f32 = 6.0;
+/// Shield bars get their own color, so they can't be mistaken for the hull.
+const SHIELD_COLOR: Color = Color::srgb(0.3, 0.8, 1.0);
+
+#[derive(Component)]
+enum HudText {
+    Score(Player),
+    Ammo(Player),
+    /// Power-ups in effect, with the seconds they have left.
+    Boosts(Player),
+    Timer,
+}
+
+#[derive(Component)]
+enum HudBar {
+    Reload(Player),
+    Fuel(Player),
+    Hull(Player),
+    Shield(Player),
+}
+
+/// Color identifying `player` in the HUD: their hull tint, unless they kept it white.
+pub fn player_color(loadouts: &Loadouts, player: Player) -> Color {
+    let loadout = loadouts.0[player.0];
+    if loadout.color == 0 {
+        DEFAULT_PLAYER_COLORS[player.0]
+    } else {
+        loadout.color()
+    }
+}
+
+fn spawn_hud(mut commands: Commands, loadouts: Res<Loadouts>, rules: Res<Rules>) {
+    let text = |value: &str, color: Color| {
+        TextBundle::from_section(
+            value,
+            TextStyle {
+                font_size: 18.0,
+                color,
+                ..default()
+            },
+        )
+    };
+    let bar = |color: Color| {
+        (
+            NodeBundle {
+                style: Style {
+                    width: Val::Px(BAR_WIDTH),
+                    height: Val::Px(BAR_HEIGHT),
+                    margin: UiRect::vertical(Val::Px(2.0)),
+                    ..default()
+                },
+                background_color: color.with_alpha(0.2).into(),
+                ..default()
+            },
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    ..default()
+                },
+                background_color: color.into(),
+                ..default()
+            },
+        )
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    justify_content: JustifyContent::Center,
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..default()
+                },
+                ..default()
+            },
+            StateScoped(AppState::InGame),
+        ))
+        .with_children(|parent| {
+            parent.spawn((text("00:00", Color::WHITE), HudText::Timer));
+        });
+
+    for (index, (justify, align)) in PANEL_CORNERS.into_iter().enumerate() {
+        let player = Player(index);
+        let color = player_color(&loadouts, player);
+        commands
+            .spawn((
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        width: Val::Percent(100.),
+                        height: Val::Percent(100.),
+                        justify_content: justify,
+                        align_items: align,
+                        padding: UiRect::all(Val::Px(8.0)),
+                        ..default()
+                    },
+                    ..default()
+                },
+                StateScoped(AppState::InGame),
+            ))
+            .with_children(|parent| {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            align_items: align,
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|panel| {
+                        panel.spawn((text("", color), HudText::Score(player)));
+                        panel.spawn((text("", color), HudText::Ammo(player)));
+                        panel.spawn((text("", color), HudText::Boosts(player)));
+                        let mut bars = vec![
+                            (HudBar::Reload(player), color),
+                            (HudBar::Fuel(player), color),
+                        ];
+                        if rules.armor.is_some() {
+                            bars.push((HudBar::Hull(player), color));
+                            bars.push((HudBar::Shield(player), SHIELD_COLOR));
+                        }
+                        for (kind, color) in bars {
+                            let (track, fill) = bar(color);
+                            panel.spawn(track).with_children(|track| {
+                                track.spawn((fill, kind));
+                            });
+                        }
+                    });
+            });
+    }
+}
+
+fn update_hud_text(
+    clock: Res<SimClock>,
+    scores: Res<Scores>,
+    respawns: Res<Respawns>,
+    rockets: Query<(&Player, &Rocket)>,
+    mut texts: Query<(&HudText, &mut Text)>,
+) {
+    for (kind, mut text) in texts.iter_mut() {
+        let value = match kind {
+            HudText::Score(player) => match respawns.lives[player.0] {
+                Some(lives) => format!("P{}  {}  LIVES {lives}", player.0 + 1, scores.0[player.0]),
+                None => format!("P{}  {}", player.0 + 1, scores.0[player.0]),
+            },
+            HudText::Ammo(player) => match rockets.iter().find(|(p, _)| *p == player) {
+                Some((_, rocket)) => format!("TORPEDOES {}", rocket.ammo),
+                None => match respawns.remaining(*player, clock.tick) {
+                    Some(remaining) => format!("RESPAWN IN {}", remaining.as_secs_f32().ceil()),
+                    None => "DESTROYED".to_string(),
+                },
+            },
+            HudText::Boosts(player) => match rockets.iter().find(|(p, _)| *p == player) {
+                Some((_, rocket)) => boosts_label(rocket, clock.tick),
+                None => String::new(),
+            },
+            HudText::Timer => {
+                let seconds = clock.elapsed().as_secs();
+                format!("{:02}:{:02}", seconds / 60, seconds % 60)
+            }
+        };
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+/// Like `RAPID 7  SHIELD 3  HYPERSPACE 2`: seconds left of the timed power-ups and the
+/// hyperspace charges.
+fn boosts_label(rocket: &Rocket, tick: u32) -> String {
+    let timed = [
+        (PowerUp::RapidFire, "RAPID"),
+        (PowerUp::Shield, "SHIELD"),
+        (PowerUp::SpreadShot, "SPREAD"),
+    ];
+    let mut parts: Vec<String> = timed
+        .into_iter()
+        .filter_map(|(kind, name)| {
+            let remaining = rocket.boosts.remaining(kind, tick)?;
+            Some(format!("{name} {}", remaining.as_secs_f32().ceil()))
+        })
+        .collect();
+    if rocket.hyperspace > 0 {
+        parts.push(format!("HYPERSPACE {}", rocket.hyperspace));
+    }
+    parts.join("  ")
+}
+
+fn update_hud_bars(
+    clock: Res<SimClock>,
+    rules: Res<Rules>,
+    rockets: Query<(&Player, &Rocket)>,
+    mut bars: Query<(&HudBar, &mut Style)>,
+) {
+    for (kind, mut style) in bars.iter_mut() {
+        let player = match kind {
+            HudBar::Reload(player)
+            | HudBar::Fuel(player)
+            | HudBar::Hull(player)
+            | HudBar::Shield(player) => player,
+        };
+        let armor = rules.armor.unwrap_or_default();
+        let fill = match (kind, rockets.iter().find(|(p, _)| *p == player)) {
+            (_, None) => 0.0,
+            (HudBar::Reload(_), Some((_, rocket))) if rocket.ammo == 0 => 0.0,
+            (HudBar::Reload(_), Some((_, rocket))) => {
+                let since_shot = clock.elapsed().saturating_sub(rocket.last_shot_time);
+                let reload = rocket.boosts.reload_time(rocket.cooldown, clock.tick);
+                (since_shot.as_secs_f32() / reload.as_secs_f32()).min(1.0)
+            }
+            (HudBar::Fuel(_), Some((_, rocket))) => rocket.fuel / rules.fuel,
+            (HudBar::Hull(_), Some((_, rocket))) => rocket.hull / armor.hull,
+            (HudBar::Shield(_), Some((_, rocket))) => rocket.shield / armor.shield,
+        };
+        style.width = Val::Percent(fill * 100.0);
+    }
+}
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::InGame), spawn_hud)
+            .add_systems(
+                Update,
+                (update_hud_text, update_hud_bars).run_if(in_state(AppState::InGame)),
+            );
+    }
+}
@@ -0,0 +1,115 @@
+//! Hull points and regenerating shields, for matches whose [`Rules`] ask for more
+//! than one hit to bring a rocket down.
+//!
+//! Every hit deals the damage of what caused it. The shield soaks it up first and
+//! the hull takes the rest; the rocket is destroyed once the hull is gone. The shield
+//! recharges after the rocket has gone a while without being hit. Falling into a
+//! gravity well destroys a rocket whatever is left of it.
+//!
+//! Rockets can't be hurt for a moment after they respawn, and after every collision
+//! so that two rockets or a rocket and an asteroid overlapping for a few ticks only
+//! count once. Without armor in the rules any hit is a kill, as in the original.
+
+use bevy::prelude::*;
+use std::time::Duration;
+
+use crate::powerup::PowerUp;
+use crate::rocket::Rocket;
+use crate::{Rules, SimClock, TICK_RATE};
+
+/// How long a rocket can't be hurt after it respawns. Rockets starting a round get
+/// none, as in the original.
+pub const SPAWN_PROTECTION: Duration = Duration::from_secs(3);
+/// How long a rocket can't be hurt after running into something.
+const COLLISION_GRACE: Duration = Duration::from_millis(500);
+
+/// Hull and shield every rocket starts with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Armor {
+    pub hull: f32,
+    pub shield: f32,
+    /// Shield points regained per second.
+    pub shield_regen: f32,
+    /// Seconds without a hit before the shield starts recharging.
+    pub shield_delay: f32,
+}
+
+impl Default for Armor {
+    fn default() -> Self {
+        Self {
+            hull: 100.0,
+            shield: 50.0,
+            shield_regen: 10.0,
+            shield_delay: 3.0,
+        }
+    }
+}
+
+/// What hit a rocket.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Hit {
+    Torpedo,
+    Asteroid,
+    /// The other rocket.
+    Ram,
+}
+
+impl Hit {
+    pub fn damage(&self) -> f32 {
+        match self {
+            Hit::Torpedo => 40.0,
+            Hit::Asteroid => 30.0,
+            Hit::Ram => 50.0,
+        }
+    }
+}
+
+/// Match tick `duration` after `tick`.
+pub fn ticks_after(tick: u32, duration: Duration) -> u32 {
+    tick + (duration.as_secs_f64() * TICK_RATE) as u32
+}
+
+/// Whether `rocket` can't be hurt at `tick`, thanks to spawn protection, a recent
+/// collision or a shield power-up.
+pub fn protected(rocket: &Rocket, tick: u32) -> bool {
+    tick < rocket.protected_until || rocket.boosts.active(PowerUp::Shield, tick)
+}
+
+/// Deals the damage of `hit` to `rocket` at `tick`. Returns whether that destroyed
+/// it.
+pub fn take_hit(rocket: &mut Rocket, hit: Hit, tick: u32, rules: &Rules) -> bool {
+    if protected(rocket, tick) {
+        return false;
+    }
+    if rules.armor.is_none() {
+        return true;
+    }
+    let damage = hit.damage();
+    let soaked = damage.min(rocket.shield);
+    rocket.shield -= soaked;
+    rocket.hull -= damage - soaked;
+    rocket.last_hit = tick;
+    if hit != Hit::Torpedo {
+        rocket.protected_until = ticks_after(tick, COLLISION_GRACE);
+    }
+    rocket.hull <= 0.0
+}
+
+/// Recharges the shields of rockets that haven't been hit for a while.
+pub fn regenerate_shields(
+    clock: Res<SimClock>,
+    rules: Res<Rules>,
+    time: Res<Time>,
+    mut rockets: Query<&mut Rocket>,
+) {
+    let Some(armor) = rules.armor else {
+        return;
+    };
+    let delay = Duration::from_secs_f32(armor.shield_delay);
+    for mut rocket in &mut rockets {
+        if clock.tick >= ticks_after(rocket.last_hit, delay) && rocket.shield < armor.shield {
+            rocket.shield =
+                (rocket.shield + armor.shield_regen * time.delta_seconds()).min(armor.shield);
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::rocket::{Player, Rocket, RocketControls};
+
+/// The controls a rocket acts on during one simulation tick, packed into a single byte
+/// so it can be sent over the network as is.
+#[derive(Component, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ShipInput(pub u8);
+
+impl ShipInput {
+    pub const ROTATE_LEFT: u8 = 1 << 0;
+    pub const ROTATE_RIGHT: u8 = 1 << 1;
+    pub const ACCELERATE: u8 = 1 << 2;
+    /// Set only on the tick after the fire key went down, not while it is held.
+    pub const FIRE: u8 = 1 << 3;
+    /// Same as [`Self::FIRE`], for the hyperspace key.
+    pub const HYPERSPACE: u8 = 1 << 4;
+    /// The presses that act once, which must be neither repeated nor lost.
+    pub const EDGE_TRIGGERED: u8 = Self::FIRE | Self::HYPERSPACE;
+
+    pub fn pressed(&self, flag: u8) -> bool {
+        self.0 & flag != 0
+    }
+}
+
+/// Keys one player flies with.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PlayerKeys {
+    pub rotate_left: KeyCode,
+    pub rotate_right: KeyCode,
+    pub accelerate: KeyCode,
+    pub fire: KeyCode,
+    pub hyperspace: KeyCode,
+}
+
+impl PlayerKeys {
+    pub fn controls(&self) -> RocketControls {
+        RocketControls {
+            rotate_left: self.rotate_left,
+            rotate_right: self.rotate_right,
+            accelerate: self.accelerate,
+            hyperspace: self.hyperspace,
+        }
+    }
+}
+
+/// Keys of both players, indexed by [`Player`]. Rockets take theirs when spawned.
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct KeyBindings(pub [PlayerKeys; 2]);
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self([
+            PlayerKeys {
+                rotate_left: KeyCode::KeyA,
+                rotate_right: KeyCode::KeyD,
+                accelerate: KeyCode::KeyS,
+                fire: KeyCode::KeyW,
+                hyperspace: KeyCode::KeyQ,
+            },
+            PlayerKeys {
+                rotate_left: KeyCode::KeyJ,
+                rotate_right: KeyCode::KeyL,
+                accelerate: KeyCode::KeyK,
+                fire: KeyCode::KeyI,
+                hyperspace: KeyCode::KeyU,
+            },
+        ])
+    }
+}
+
+/// Keyboard state of every local player, collected each frame and handed to the
+/// simulation once per tick.
+#[derive(Resource, Default)]
+pub struct LocalInput {
+    held: HashMap<Player, u8>,
+    /// Fire and hyperspace presses not handed to a tick yet.
+    pressed: HashMap<Player, u8>,
+}
+
+impl LocalInput {
+    /// Input for `player`'s next tick. A fire or hyperspace press is only reported
+    /// once even when several ticks run in the same frame.
+    pub fn take(&mut self, player: Player) -> ShipInput {
+        let held = self.held.get(&player).copied().unwrap_or_default();
+        let pressed = self.pressed.insert(player, 0).unwrap_or_default();
+        ShipInput(held | pressed)
+    }
+}
+
+pub fn sample_local_input(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut local: ResMut<LocalInput>,
+    rockets: Query<(&Player, &Rocket)>,
+) {
+    for (player, rocket) in rockets.iter() {
+        let mut bits = 0;
+        if keys.pressed(rocket.controls.rotate_left) {
+            bits |= ShipInput::ROTATE_LEFT;
+        }
+        if keys.pressed(rocket.controls.rotate_right) {
+            bits |= ShipInput::ROTATE_RIGHT;
+        }
+        if keys.pressed(rocket.controls.accelerate) {
+            bits |= ShipInput::ACCELERATE;
+        }
+        local.held.insert(*player, bits);
+
+        let pressed = local.pressed.entry(*player).or_default();
+        if keys.just_pressed(rocket.spawn_key) {
+            *pressed |= ShipInput::FIRE;
+        }
+        if keys.just_pressed(rocket.controls.hyperspace) {
+            *pressed |= ShipInput::HYPERSPACE;
+        }
+    }
+}
+
+/// Hands every rocket its local input for the coming tick.
+pub fn apply_local_input(
+    mut local: ResMut<LocalInput>,
+    mut rockets: Query<(&Player, &mut ShipInput)>,
+) {
+    for (player, mut input) in rockets.iter_mut() {
+        *input = local.take(*player);
+    }
+}
@@ -0,0 +1,597 @@
+use bevy::{
+    ecs::{schedule::ScheduleLabel, system::RunSystemOnce},
+    prelude::*,
+};
+use bevy_hanabi::prelude::*;
+
+pub mod ai;
+pub mod arena;
+pub mod asteroid;
+pub mod background;
+pub mod bullet;
+pub mod cli;
+pub mod client;
+pub mod headless;
+pub mod hud;
+pub mod hull;
+pub mod input;
+pub mod lobby;
+pub mod menu;
+pub mod netcode;
+pub mod pause;
+pub mod post_process;
+pub mod powerup;
+pub mod protocol;
+pub mod replay;
+pub mod respawn;
+pub mod rocket;
+pub mod server;
+pub mod settings;
+pub mod sound;
+pub mod stream;
+pub mod tcp;
+pub mod vector;
+
+use arena::{move_orbits, spawn_wells, travel_wormholes, ArenaLayout, GravityWell, Warped};
+use asteroid::{asteroid_collisions, move_asteroids, spawn_asteroids, Asteroid};
+use bullet::{check_bullet_coll, handle_bullet_movement, spawn_bullet, Bullet};
+use input::{KeyBindings, ShipInput};
+use hull::{regenerate_shields, take_hit, Armor, Hit};
+use powerup::{collect_power_ups, hyperspace_jump, spawn_power_ups, PowerUp, PowerUps};
+use replay::forget_replays;
+use respawn::{respawn_rockets, schedule_respawns, Respawn, Respawns};
+use rocket::{add_rockets, clip_rockets, Loadouts, Player, Rocket, RocketDestroyed};
+
+/// Top-level flow of the game.
+#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum AppState {
+    /// Title screen and main menu.
+    Title,
+    Settings,
+    /// Finding a network game and picking a ship before the match.
+    Lobby,
+    #[default]
+    InGame,
+}
+
+/// Advances the match by exactly one fixed tick. Run once per `FixedUpdate` by
+/// [`step_simulation`], or several times in a row by the rollback session.
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Simulation;
+
+/// Simulation ticks per second.
+pub const TICK_RATE: f64 = 60.0;
+
+/// Deterministic match clock, counted in simulation ticks instead of frame time so it
+/// can be saved and restored along with the rest of the state.
+#[derive(Resource, Default, Clone, Copy)]
+pub struct SimClock {
+    pub tick: u32,
+}
+
+impl SimClock {
+    pub fn elapsed(&self) -> std::time::Duration {
+        std::time::Duration::from_secs_f64(self.tick as f64 / TICK_RATE)
+    }
+}
+
+/// Logical size of the arena in world units. The camera scales to keep all of it in
+/// view whatever the window size, so a bigger window doesn't mean a bigger arena.
+pub const ARENA_SIZE: Vec2 = Vec2::new(1020.0, 760.0);
+
+/// Edges of the arena, centered on the origin. Rockets leaving it come back on the
+/// opposite side and torpedoes leaving it are gone.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct ArenaBounds {
+    pub half_size: Vec2,
+}
+
+impl Default for ArenaBounds {
+    fn default() -> Self {
+        Self {
+            half_size: ARENA_SIZE / 2.0,
+        }
+    }
+}
+
+impl ArenaBounds {
+    pub fn size(&self) -> Vec2 {
+        self.half_size * 2.0
+    }
+
+    pub fn contains(&self, position: Vec2) -> bool {
+        position.abs().cmple(self.half_size).all()
+    }
+
+    /// Mirrors `position` to the opposite edge on every axis it left the arena on.
+    pub fn wrap(&self, position: Vec3) -> Vec3 {
+        let mut wrapped = position;
+        if position.x.abs() > self.half_size.x {
+            wrapped.x = -position.x;
+        }
+        if position.y.abs() > self.half_size.y {
+            wrapped.y = -position.y;
+        }
+        wrapped
+    }
+}
+
+/// Kills scored by each player, indexed by [`Player`].
+#[derive(Resource, Default, Clone, Copy, Debug, PartialEq)]
+pub struct Scores(pub [u32; 2]);
+
+/// Limits and physics a match is played with.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct Rules {
+    /// Torpedoes each rocket starts with.
+    pub ammo: u32,
+    /// Seconds of thrust each rocket starts with.
+    pub fuel: f32,
+    /// Multiplier of the pull of every gravity well.
+    pub gravity: f32,
+    /// Power-ups that turn up during the match.
+    pub power_ups: PowerUps,
+    /// Hull and shield of every rocket. Without it any hit is a kill.
+    pub armor: Option<Armor>,
+    /// Whether destroyed rockets come back. Without it they are out until the next
+    /// round.
+    pub respawn: Option<Respawn>,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        RulesPreset::Classic.rules()
+    }
+}
+
+/// Named sets of [`Rules`]. Both sides of an online match have to pick the same one.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum RulesPreset {
+    #[default]
+    Classic,
+    /// Few torpedoes and little fuel, so every shot and burn counts.
+    Scarce,
+    /// Half the gravity; rockets drift around the sun in wide orbits.
+    Drift,
+    /// Classic, with every power-up turning up.
+    Arcade,
+    /// Classic, but rockets take a few hits before they go down.
+    Armored,
+    /// Classic, but destroyed rockets come back until their player runs out of lives.
+    Deathmatch,
+}
+
+impl RulesPreset {
+    pub const ALL: [RulesPreset; 6] = [
+        RulesPreset::Classic,
+        RulesPreset::Scarce,
+        RulesPreset::Drift,
+        RulesPreset::Arcade,
+        RulesPreset::Armored,
+        RulesPreset::Deathmatch,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RulesPreset::Classic => "classic",
+            RulesPreset::Scarce => "scarce",
+            RulesPreset::Drift => "drift",
+            RulesPreset::Arcade => "arcade",
+            RulesPreset::Armored => "armored",
+            RulesPreset::Deathmatch => "deathmatch",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|preset| preset.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn rules(&self) -> Rules {
+        match self {
+            RulesPreset::Classic => Rules {
+                ammo: 32,
+                fuel: 30.0,
+                gravity: 1.0,
+                power_ups: PowerUps::NONE,
+                armor: None,
+                respawn: None,
+            },
+            RulesPreset::Scarce => Rules {
+                ammo: 8,
+                fuel: 10.0,
+                gravity: 1.0,
+                power_ups: PowerUps::NONE,
+                armor: None,
+                respawn: None,
+            },
+            RulesPreset::Drift => Rules {
+                ammo: 32,
+                fuel: 30.0,
+                gravity: 0.5,
+                power_ups: PowerUps::NONE,
+                armor: None,
+                respawn: None,
+            },
+            RulesPreset::Arcade => Rules {
+                power_ups: PowerUps::ALL,
+                ..RulesPreset::Classic.rules()
+            },
+            RulesPreset::Armored => Rules {
+                armor: Some(Armor::default()),
+                ..RulesPreset::Classic.rules()
+            },
+            RulesPreset::Deathmatch => Rules {
+                respawn: Some(Respawn::default()),
+                ..RulesPreset::Classic.rules()
+            },
+        }
+    }
+}
+
+/// Seed for everything random in a match. Online matches keep the default on both
+/// sides, so they agree without exchanging it.
+#[derive(Resource, Clone, Copy, Debug, Default)]
+pub struct MatchSeed(pub u64);
+
+/// Deterministic random numbers for the simulation, reseeded from [`MatchSeed`] when
+/// a match starts so the same seed plays out the same way.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SimRng {
+    state: u64,
+}
+
+impl SimRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// SplitMix64, small and good enough for gameplay.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `min..max`.
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        let unit = (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32;
+        min + (max - min) * unit
+    }
+}
+
+/// Match rules and state shared by the game client and the dedicated server.
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<AppState>()
+            .enable_state_scoped_entities::<AppState>()
+            .add_event::<RocketDestroyed>()
+            .add_event::<Explosion>()
+            .add_event::<Warped>()
+            .insert_resource(Time::<Fixed>::from_hz(TICK_RATE))
+            .init_resource::<SimClock>()
+            .init_resource::<ArenaBounds>()
+            .init_resource::<Rules>()
+            .init_resource::<ArenaLayout>()
+            .init_resource::<MatchSeed>()
+            .init_resource::<SimRng>()
+            .init_resource::<Scores>()
+            .init_resource::<Respawns>()
+            .init_resource::<Loadouts>()
+            .init_resource::<KeyBindings>()
+            .add_systems(
+                OnEnter(AppState::InGame),
+                (reset_match, spawn_wells, add_rockets, spawn_asteroids).chain(),
+            )
+            // Also outside the simulation, for clients following the server's clock.
+            .add_systems(Update, move_orbits.run_if(resource_changed::<SimClock>))
+            .add_systems(
+                Simulation,
+                (
+                    move_orbits,
+                    check_bullet_coll,
+                    spawn_bullet,
+                    hyperspace_jump,
+                    update_rocket_status,
+                    clip_rockets,
+                    gravitational_pull,
+                    handle_bullet_movement,
+                    travel_wormholes,
+                    move_asteroids,
+                    asteroid_collisions,
+                    collect_power_ups,
+                    spawn_power_ups,
+                    regenerate_shields,
+                    award_kills,
+                    schedule_respawns,
+                    respawn_rockets,
+                    advance_clock,
+                )
+                    .chain(),
+            );
+    }
+}
+
+pub fn step_simulation(world: &mut World) {
+    world.run_schedule(Simulation);
+}
+
+fn reset_match(
+    seed: Res<MatchSeed>,
+    rules: Res<Rules>,
+    mut clock: ResMut<SimClock>,
+    mut scores: ResMut<Scores>,
+    mut respawns: ResMut<Respawns>,
+    mut rng: ResMut<SimRng>,
+) {
+    *clock = SimClock::default();
+    *scores = Scores::default();
+    *respawns = Respawns::new(&rules);
+    *rng = SimRng::new(seed.0);
+}
+
+/// What a new round clears away.
+type RoundField = Or<(With<Rocket>, With<Bullet>, With<Asteroid>, With<PowerUp>)>;
+
+/// Clears the arena, puts both rockets back at their starting positions with full
+/// lives and scatters new asteroids. The score carries over.
+pub fn restart_round(world: &mut World) {
+    let field: Vec<Entity> = world
+        .query_filtered::<Entity, RoundField>()
+        .iter(world)
+        .collect();
+    for entity in field {
+        world.despawn(entity);
+    }
+    *world.resource_mut::<SimClock>() = SimClock::default();
+    forget_replays(world);
+    let respawns = Respawns::new(world.resource::<Rules>());
+    *world.resource_mut::<Respawns>() = respawns;
+    world.run_system_once(add_rockets);
+    world.run_system_once(spawn_asteroids);
+}
+
+fn advance_clock(mut clock: ResMut<SimClock>) {
+    clock.tick += 1;
+}
+
+/// Every destroyed rocket scores a point for its opponent.
+fn award_kills(mut destroyed: EventReader<RocketDestroyed>, mut scores: ResMut<Scores>) {
+    // A rocket can be hit by several things in the same tick but only dies once.
+    let mut killed = [false; 2];
+    for event in destroyed.read() {
+        killed[event.player.0.min(1)] = true;
+    }
+    for (victim, _) in killed.iter().enumerate().filter(|(_, killed)| **killed) {
+        scores.0[1 - victim] += 1;
+    }
+}
+
+fn check_collision(rocket1: &Transform, rocket2: &Transform, radius_collison: f32) -> bool {
+    let distance = rocket1
+        .translation
+        .truncate()
+        .distance(rocket2.translation.truncate());
+    // println!("{}", distance);
+    distance < radius_collison
+}
+
+/// Whether a rocket has come inside the kill radius of any gravity well.
+fn check_sun_collision(
+    rocket: &Transform,
+    radius_collision: f32,
+    wells: &Query<&GravityWell>,
+) -> bool {
+    let position = rocket.translation.truncate();
+    wells
+        .iter()
+        .any(|well| position.distance(well.position) < radius_collision + well.kill_radius)
+}
+
+fn gravitational_pull(
+    mut rocket_query: Query<(&mut Rocket, &mut Transform)>,
+    wells: Query<&GravityWell>,
+    rules: Res<Rules>,
+    time: Res<Time>,
+) {
+    let wells: Vec<GravityWell> = wells.iter().copied().collect();
+    for (mut rocket, mut transform) in rocket_query.iter_mut() {
+        let dt = time.delta_seconds();
+        apply_gravity(&mut rocket, &mut transform, &wells, rules.gravity, dt);
+    }
+}
+
+/// Acceleration at `position` from the pull of every well. `gravity` scales it, see
+/// [`Rules::gravity`].
+pub fn gravity_at(wells: &[GravityWell], position: Vec2, gravity: f32) -> Vec2 {
+    const G_FORCE: f64 = 125000000.0;
+
+    let mut acceleration = Vec2::ZERO;
+    for well in wells {
+        let direction = well.position - position;
+        let distance = direction.length();
+
+        // Inside the kill radius the rocket or torpedo is being destroyed anyway, and
+        // the pull would grow without bound.
+        if distance < well.kill_radius {
+            continue;
+        }
+
+        let force = G_FORCE * (well.mass * gravity) as f64 / (distance * distance) as f64;
+        acceleration += direction.normalize() * force as f32;
+    }
+    acceleration
+}
+
+/// Pulls a rocket towards every well for `dt` seconds and moves it by the resulting
+/// velocity. `gravity` scales the pull, see [`Rules::gravity`].
+pub fn apply_gravity(
+    rocket: &mut Rocket,
+    transform: &mut Transform,
+    wells: &[GravityWell],
+    gravity: f32,
+    dt: f32,
+) {
+    let rocket_position = Vec2::new(transform.translation.x, transform.translation.y);
+    let acceleration = gravity_at(wells, rocket_position, gravity);
+
+    rocket.velocity += acceleration * dt;
+
+    if rocket.velocity.length() > rocket.max_speed {
+        rocket.velocity = rocket.velocity.normalize() * rocket.max_speed;
+    }
+    transform.translation.x += rocket.velocity.x * dt;
+    transform.translation.y += rocket.velocity.y * dt;
+}
+
+/// An explosion to show at `position`, live or in a replay.
+#[derive(Event)]
+pub struct Explosion {
+    pub position: Vec3,
+}
+
+/// Moves the explosion effect to `position` and fires a new burst of particles.
+pub fn trigger_explosion(
+    properties: &mut EffectProperties,
+    initializers: &mut EffectInitializers,
+    effect_transform: &mut Transform,
+    position: Vec3,
+) {
+    effect_transform.translation = position;
+
+    let r = 255.;
+    let g = 165.;
+    let b = 0.;
+    let color = 0xFF000000u32 | (b as u32) << 16 | (g as u32) << 8 | (r as u32);
+    properties.set("spawn_color", color.into());
+    initializers.reset();
+}
+
+/// Shows an explosion wherever a rocket was destroyed.
+pub fn explode_destroyed_rockets(
+    mut destroyed: EventReader<RocketDestroyed>,
+    mut explosions: EventWriter<Explosion>,
+) {
+    for event in destroyed.read() {
+        explosions.send(Explosion {
+            position: event.position,
+        });
+    }
+}
+
+/// Plays the particle effect for every [`Explosion`].
+pub fn play_explosions(
+    mut explosions: EventReader<Explosion>,
+    mut effect: Query<(
+        &mut EffectProperties,
+        &mut EffectInitializers,
+        &mut Transform,
+    )>,
+) {
+    let Ok((mut properties, mut initializers, mut effect_transform)) = effect.get_single_mut()
+    else {
+        return;
+    };
+
+    for explosion in explosions.read() {
+        trigger_explosion(
+            &mut properties,
+            &mut initializers,
+            &mut effect_transform,
+            explosion.position,
+        );
+    }
+}
+
+pub fn handle_rocket_movement(
+    dt: f32,
+    input: &ShipInput,
+    rocket: &mut Rocket,
+    transform: &mut Transform,
+) {
+    if input.pressed(ShipInput::ACCELERATE) && rocket.fuel > 0.0 {
+        rocket.fuel = (rocket.fuel - dt).max(0.0);
+        if rocket.speed < rocket.max_speed {
+            rocket.speed += 50.0 * dt;
+        }
+    } else {
+        if rocket.speed > 0.0 {
+            rocket.speed -= 50.0 * dt;
+        }
+    }
+
+    let mut rotation_input = 0.0;
+
+    if input.pressed(ShipInput::ROTATE_LEFT) {
+        rotation_input += 4.0;
+    }
+
+    if input.pressed(ShipInput::ROTATE_RIGHT) {
+        rotation_input -= 4.0;
+    }
+
+    let max_rotation_speed = f32::to_radians(70.0);
+    let rotation_acceleration = f32::to_radians(50.0 * dt);
+    rocket.rotation_speed += rotation_input * rotation_acceleration;
+    rocket.rotation_speed = rocket
+        .rotation_speed
+        .clamp(-max_rotation_speed, max_rotation_speed);
+
+    transform.rotation *= Quat::from_rotation_z(rocket.rotation_speed * dt);
+
+    let direction = transform.rotation * Vec3::Y;
+    rocket.velocity = Vec2::new(direction.x, direction.y) * rocket.speed;
+
+    transform.translation += rocket.velocity.extend(0.0) * dt;
+}
+
+fn update_rocket_status(
+    mut commands: Commands,
+    mut destroyed: EventWriter<RocketDestroyed>,
+    mut entities: Query<(Entity, &Player, &ShipInput, &mut Rocket, &mut Transform)>,
+    wells: Query<&GravityWell>,
+    time: Res<Time>,
+    clock: Res<SimClock>,
+    rules: Res<Rules>,
+) {
+    let mut rockets: Vec<_> = entities.iter_mut().collect();
+    // Query order can change after a rollback respawns a rocket, so pin it to the player.
+    rockets.sort_by_key(|(_, player, ..)| player.0);
+
+    // A rocket can be alone on the field while the other one waits to respawn.
+    for (entity, player, _, rocket, transform) in &rockets {
+        if check_sun_collision(transform, rocket.radius_collision, &wells) {
+            commands.entity(*entity).despawn();
+            destroyed.send(RocketDestroyed {
+                player: **player,
+                position: transform.translation,
+            });
+        }
+    }
+
+    if rockets.len() > 1 {
+        let (_, _, _, rocket1, transform1) = &rockets[0];
+        let (_, _, _, _, transform2) = &rockets[1];
+
+        if check_collision(transform1, transform2, rocket1.radius_collision) {
+            for (entity, player, _, rocket, transform) in rockets.iter_mut() {
+                if take_hit(rocket, Hit::Ram, clock.tick, &rules) {
+                    destroyed.send(RocketDestroyed {
+                        player: **player,
+                        position: transform.translation,
+                    });
+                    commands.entity(*entity).despawn();
+                }
+            }
+        }
+    }
+
+    for (_, _, input, mut rocket, mut transform) in entities.iter_mut() {
+        handle_rocket_movement(time.delta_seconds(), input, &mut rocket, &mut transform);
+    }
+}
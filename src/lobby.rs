@@ -0,0 +1,317 @@
+//! Lobby screen for network matches.
+//!
+//! Without a server picked yet, the lobby lists the servers announcing themselves on
+//! the local network (see [`DISCOVERY_PORTS`]). Once connected it shows both seats:
+//! players pick a ship class and color, ready up, and the host starts the match.
+//!
+//! Controls: Up/Down and Enter to pick a server; Left/Right for the ship class,
+//! Up/Down for the color, Space to toggle ready and Enter to start (host only).
+//! Escape goes back to the title screen.
+
+use bevy::prelude::*;
+use std::fmt::Write;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::Duration;
+
+use crate::client::{ServerConnection, Transport};
+use crate::protocol::{ClientMessage, LobbyPlayer, ServerMessage, DISCOVERY_PORTS, MAX_PACKET};
+use crate::rocket::{Player, ShipClass, SHIP_COLORS};
+use crate::AppState;
+
+/// Servers that haven't announced themselves for this long drop off the list.
+const ANNOUNCE_TIMEOUT: Duration = Duration::from_secs(3);
+
+struct DiscoveredGame {
+    /// The server's own id, see [`ServerMessage::Announce`].
+    id: u64,
+    addr: SocketAddr,
+    name: String,
+    players: u8,
+    last_seen: Duration,
+}
+
+/// Listens for server announcements while no server has been picked.
+#[derive(Resource)]
+pub struct LobbyBrowser {
+    socket: Option<UdpSocket>,
+    games: Vec<DiscoveredGame>,
+    selected: usize,
+}
+
+impl LobbyBrowser {
+    pub fn new() -> Self {
+        let socket = DISCOVERY_PORTS
+            .clone()
+            .find_map(|port| UdpSocket::bind(("0.0.0.0", port)).ok());
+        match &socket {
+            Some(socket) => {
+                let _ = socket.set_nonblocking(true);
+            }
+            None => warn!("all discovery ports are taken, no servers will be listed"),
+        }
+        Self {
+            socket,
+            games: Vec::new(),
+            selected: 0,
+        }
+    }
+}
+
+impl Default for LobbyBrowser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Component)]
+struct LobbyText;
+
+fn spawn_lobby_ui(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            StateScoped(AppState::Lobby),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 24.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ),
+                LobbyText,
+            ));
+        });
+}
+
+fn discover_games(time: Res<Time<Real>>, mut browser: ResMut<LobbyBrowser>) {
+    let now = time.elapsed();
+    let mut buf = [0u8; MAX_PACKET];
+
+    while let Some(socket) = &browser.socket {
+        let (len, from) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+            Err(_) => break,
+        };
+        let Some(ServerMessage::Announce { id, name, players }) =
+            ServerMessage::decode(&buf[..len])
+        else {
+            continue;
+        };
+        // A server on this machine is heard both over broadcast and over loopback, from
+        // two different addresses; it is still the same server.
+        match browser.games.iter_mut().find(|game| game.id == id) {
+            Some(game) => {
+                game.name = name;
+                game.players = players;
+                game.last_seen = now;
+            }
+            None => browser.games.push(DiscoveredGame {
+                id,
+                addr: from,
+                name,
+                players,
+                last_seen: now,
+            }),
+        }
+    }
+
+    browser
+        .games
+        .retain(|game| now.saturating_sub(game.last_seen) < ANNOUNCE_TIMEOUT);
+    browser.selected = browser.selected.min(browser.games.len().saturating_sub(1));
+}
+
+fn browse_input(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut browser: ResMut<LobbyBrowser>,
+) {
+    if keys.just_pressed(KeyCode::ArrowDown) && browser.selected + 1 < browser.games.len() {
+        browser.selected += 1;
+    }
+    if keys.just_pressed(KeyCode::ArrowUp) {
+        browser.selected = browser.selected.saturating_sub(1);
+    }
+
+    if keys.just_pressed(KeyCode::Enter) {
+        let Some(game) = browser.games.get(browser.selected) else {
+            return;
+        };
+        // Servers are found over UDP, so that is known to get through.
+        match ServerConnection::connect(game.addr, Transport::Udp) {
+            Ok(connection) => {
+                info!("joining \"{}\" at {}", game.name, game.addr);
+                commands.insert_resource(connection);
+            }
+            Err(err) => error!("could not open a socket: {err}"),
+        }
+    }
+}
+
+fn lobby_input(keys: Res<ButtonInput<KeyCode>>, mut connection: ResMut<ServerConnection>) {
+    let Some(player) = connection.player else {
+        return;
+    };
+    let Some(seat) = connection
+        .lobby
+        .iter()
+        .copied()
+        .find(|seat| seat.player == player)
+    else {
+        return;
+    };
+
+    let mut loadout = seat.loadout;
+    let class = ShipClass::ALL
+        .iter()
+        .position(|class| *class == loadout.class)
+        .unwrap_or_default();
+    let classes = ShipClass::ALL.len();
+    if keys.just_pressed(KeyCode::ArrowRight) {
+        loadout.class = ShipClass::ALL[(class + 1) % classes];
+    }
+    if keys.just_pressed(KeyCode::ArrowLeft) {
+        loadout.class = ShipClass::ALL[(class + classes - 1) % classes];
+    }
+    if keys.just_pressed(KeyCode::ArrowDown) {
+        loadout.color = (loadout.color + 1) % SHIP_COLORS.len();
+    }
+    if keys.just_pressed(KeyCode::ArrowUp) {
+        loadout.color = (loadout.color + SHIP_COLORS.len() - 1) % SHIP_COLORS.len();
+    }
+    if loadout != seat.loadout {
+        connection.send(&ClientMessage::SetLoadout(loadout));
+    }
+
+    if keys.just_pressed(KeyCode::Space) {
+        connection.send(&ClientMessage::Ready(!seat.ready));
+    }
+    if keys.just_pressed(KeyCode::Enter) && player == Player(0) {
+        connection.send(&ClientMessage::Start);
+    }
+}
+
+fn write_seat(out: &mut String, seat: &LobbyPlayer, own: bool) {
+    let _ = writeln!(
+        out,
+        "{} Player {}   {:<8} {:<8} {}",
+        if own { ">" } else { " " },
+        seat.player.0 + 1,
+        seat.loadout.class.name(),
+        SHIP_COLORS[seat.loadout.color % SHIP_COLORS.len()].0,
+        if seat.ready { "READY" } else { "..." },
+    );
+}
+
+fn update_lobby_text(
+    browser: Option<Res<LobbyBrowser>>,
+    connection: Option<Res<ServerConnection>>,
+    mut text: Query<&mut Text, With<LobbyText>>,
+) {
+    let Ok(mut text) = text.get_single_mut() else {
+        return;
+    };
+
+    let mut out = String::new();
+    match (connection, browser) {
+        (Some(connection), _) => {
+            if connection.spectator {
+                out.push_str("SPECTATING - waiting for the match to start\n\n");
+                for seat in &connection.lobby {
+                    write_seat(&mut out, seat, false);
+                }
+                text.sections[0].value = out;
+                return;
+            }
+            if connection.mismatched {
+                text.sections[0].value = "The server plays another arena or other rules.\n\
+                    Start with the same --arena and --rules as the server.\n\nEsc: back"
+                    .into();
+                return;
+            }
+            let Some(player) = connection.player else {
+                text.sections[0].value = "Connecting...".into();
+                return;
+            };
+            let _ = writeln!(out, "LOBBY - you are player {}\n", player.0 + 1);
+            for seat in &connection.lobby {
+                write_seat(&mut out, seat, seat.player == player);
+            }
+            if connection.lobby.len() < 2 {
+                out.push_str("  waiting for another player\n");
+            }
+            out.push_str("\nLeft/Right: ship   Up/Down: color   Space: ready");
+            if player == Player(0) {
+                out.push_str("   Enter: start");
+            }
+        }
+        (None, Some(browser)) => {
+            out.push_str("LAN GAMES\n\n");
+            if browser.games.is_empty() {
+                out.push_str("  searching...\n");
+            }
+            for (index, game) in browser.games.iter().enumerate() {
+                let _ = writeln!(
+                    out,
+                    "{} {}  ({}/2)  {}",
+                    if index == browser.selected { ">" } else { " " },
+                    game.name,
+                    game.players,
+                    game.addr,
+                );
+            }
+            out.push_str("\nUp/Down: select   Enter: join");
+        }
+        (None, None) => {}
+    }
+    text.sections[0].value = out;
+}
+
+/// Escape leaves the lobby, and the server if we joined one, for the title screen.
+fn leave_lobby(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if keys.just_pressed(KeyCode::Escape) {
+        commands.remove_resource::<ServerConnection>();
+        commands.remove_resource::<LobbyBrowser>();
+        next_state.set(AppState::Title);
+    }
+}
+
+pub struct LobbyPlugin;
+
+impl Plugin for LobbyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Lobby), spawn_lobby_ui)
+            .add_systems(
+                Update,
+                (
+                    (discover_games, browse_input)
+                        .chain()
+                        .run_if(resource_exists::<LobbyBrowser>)
+                        .run_if(not(resource_exists::<ServerConnection>)),
+                    lobby_input.run_if(resource_exists::<ServerConnection>),
+                    update_lobby_text,
+                    leave_lobby,
+                )
+                    .run_if(in_state(AppState::Lobby)),
+            );
+    }
+}
@@ -1,141 +1,217 @@
 use bevy::{
-    prelude::*, render::render_resource::*, sprite::{Material2d, Material2dPlugin, MaterialMesh2dBundle}, window::WindowMode
+    app::ScheduleRunnerPlugin,
+    prelude::*,
+    render::{camera::ScalingMode, view::RenderLayers},
+    sprite::MaterialMesh2dBundle,
+};
+use spacewars::ai::{apply_ai_input, AiOpponent};
+use spacewars::arena::{GravityWell, WellKind, Wormhole};
+use spacewars::asteroid::AsteroidPlugin;
+use spacewars::background::BackgroundPlugin;
+use spacewars::cli::LaunchOptions;
+use spacewars::client::{self, ClientPlugin, ServerConnection};
+use spacewars::headless::{windowless_plugins, HeadlessPlugin};
+use spacewars::hud::HudPlugin;
+use spacewars::input::{apply_local_input, sample_local_input, LocalInput};
+use spacewars::lobby::{LobbyBrowser, LobbyPlugin};
+use spacewars::menu::MenuPlugin;
+use spacewars::pause::{Pause, PausePlugin};
+use spacewars::netcode::{self, rollback_step, RollbackSession};
+use spacewars::post_process;
+use spacewars::powerup::PowerUpPlugin;
+use spacewars::settings::{Settings, SettingsPlugin};
+use spacewars::sound::SoundPlugin;
+use spacewars::vector::{VectorPlugin, VectorShape, SPRITE_LAYER};
+use spacewars::replay::{ReplayPlugin, ReplayState};
+use spacewars::respawn::RespawnPlugin;
+use spacewars::rocket::Player;
+use spacewars::{
+    explode_destroyed_rockets, play_explosions, step_simulation, AppState, ArenaBounds,
+    MatchSeed, SimulationPlugin,
 };
-mod bullet;
-mod post_process;
-mod rocket;
-use bullet::{check_bullet_coll, handle_bullet_movement, spawn_bullet};
-use rocket::{add_rockets, clip_rockets, Rocket};
 
 use bevy_hanabi::prelude::*;
 
 use std::f32::consts::TAU;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-#[derive(Asset, TypePath, AsBindGroup, Clone)]
-pub struct MovingPatternMaterial {
-    #[uniform(0)]
-    color: LinearRgba,
-    #[uniform(1)]
-    time: f32,
-}
-
-impl Material2d for MovingPatternMaterial {
-    fn fragment_shader() -> ShaderRef {
-        "shaders/stars_material.wgsl".into()
+fn main() {
+    let options = LaunchOptions::from_args();
+    if options.headless {
+        run_headless(&options);
+        return;
     }
-}
 
-fn main() {
-    App::new()
-        .add_plugins((
+    let mut settings = Settings::load();
+    settings.display = options.display.unwrap_or(settings.display);
+    settings.resolution = options.resolution.unwrap_or(settings.resolution);
+    let mut app = App::new();
+    app.add_plugins((
             DefaultPlugins.set(WindowPlugin {
                 primary_window: Some(Window {
                     title: "SpaceWars".into(),
                     name: Some("bevy.app".into()),
-                    resolution: (980., 735.).into(),
-                    mode: WindowMode::Windowed,
-                    resizable: false,
+                    resolution: (settings.resolution.0 as f32, settings.resolution.1 as f32)
+                        .into(),
+                    mode: settings.display.window_mode(),
+                    resizable: true,
                     ..default()
                 }),
                 ..default()
             }),
             post_process::PostProcessPlugin,
-            Material2dPlugin::<MovingPatternMaterial>::default(),
+            BackgroundPlugin,
+            AsteroidPlugin,
+            PowerUpPlugin,
+            RespawnPlugin,
+        ))
+        .add_plugins((
+            HanabiPlugin,
+            SimulationPlugin,
+            ReplayPlugin,
+            ClientPlugin,
+            LobbyPlugin,
+            HudPlugin,
+            MenuPlugin,
+            PausePlugin,
+            SettingsPlugin,
+            SoundPlugin,
+            VectorPlugin,
         ))
-        .add_plugins(HanabiPlugin)
+        .insert_resource(settings)
+        .insert_resource(ClearColor(Color::BLACK))
+        .init_resource::<LocalInput>()
+        .add_systems(Startup, setup)
+        .add_systems(
+            PreUpdate,
+            sample_local_input.run_if(in_state(Pause::Running)),
+        )
         .add_systems(
-            Startup,
-            (setup, add_background, add_sun, add_rockets).chain(),
+            FixedUpdate,
+            (
+                (
+                    apply_local_input,
+                    apply_ai_input.run_if(resource_exists::<AiOpponent>),
+                    step_simulation,
+                )
+                    .chain()
+                    .run_if(not(resource_exists::<RollbackSession>))
+                    .run_if(not(resource_exists::<ServerConnection>)),
+                rollback_step.run_if(resource_exists::<RollbackSession>),
+            )
+                .run_if(in_state(ReplayState::Live))
+                .run_if(in_state(AppState::InGame)),
         )
         .add_systems(
             Update,
             (
-                check_bullet_coll,
-                spawn_bullet,
-                update_rocket_status,
-                clip_rockets,
-                gravitational_pull,
-                handle_bullet_movement,
+                (explode_destroyed_rockets, play_explosions).chain(),
                 post_process::rotate,
                 post_process::update_settings,
-            )
-                .chain(),
-        )
-        .run();
-}
-
-fn check_collision(rocket1: &Transform, rocket2: &Transform, radius_collison: f32) -> bool {
-    let distance = rocket1
-        .translation
-        .truncate()
-        .distance(rocket2.translation.truncate());
-    // println!("{}", distance);
-    distance < radius_collison
-}
+                add_bodies,
+            ),
+        );
+
+    app.insert_state(AppState::Title);
+    let session = options
+        .p2p
+        .and_then(|link| netcode::open_session(link, options.rollback));
+    // Both peers must agree on the seed, so they keep the default.
+    configure_match(&mut app, &options, session.is_some());
+    if let Some(session) = session {
+        app.insert_resource(session).insert_state(AppState::InGame);
+    }
+    let connection = match (options.connect, options.spectate) {
+        (Some(server), _) => client::open_connection(server, options.transport, false),
+        (None, Some(server)) => client::open_connection(server, options.transport, true),
+        (None, None) => None,
+    };
+    if let Some(connection) = connection {
+        app.insert_resource(connection).insert_state(AppState::Lobby);
+    }
+    if options.lobby {
+        app.insert_resource(LobbyBrowser::new())
+            .insert_state(AppState::Lobby);
+    }
+    if let Some(path) = &options.replay {
+        match ServerConnection::replay(path) {
+            Ok(recording) => {
+                app.insert_resource(recording).insert_state(AppState::Lobby);
+            }
+            Err(err) => error!("could not read {}: {err}", path.display()),
+        }
+    }
 
-fn check_sun_collision(rocket: &Transform, radius_collision: f32) -> bool {
-    let distance = rocket.translation.truncate().distance(Vec2::new(0.0, 0.0));
-    distance < radius_collision
+    app.run();
 }
 
-fn gravitational_pull(mut rocket_query: Query<(&mut Rocket, &mut Transform)>, time: Res<Time>) {
-    let sun_position = Vec2::ZERO;
-    const G_FORCE: f64 = 125000000.0;
-
-    for (mut rocket, mut transform) in rocket_query.iter_mut() {
-        let rocket_position = Vec2::new(transform.translation.x, transform.translation.y);
-
-        let direction = sun_position - rocket_position;
-        let distance = direction.length();
-
-        if distance < 65.0 {
-            continue;
+/// Rules, seed and computer pilots from the command line. With `--players` the match
+/// starts right away.
+fn configure_match(app: &mut App, options: &LaunchOptions, keep_default_seed: bool) {
+    app.insert_resource(options.match_rules())
+        .insert_resource(options.difficulty);
+    if let Some(arena) = &options.arena {
+        app.insert_resource(arena.clone());
+    }
+    match options.seed {
+        Some(seed) => {
+            app.insert_resource(MatchSeed(seed));
         }
-
-        let force = G_FORCE / (distance * distance) as f64;
-
-        let acceleration = direction.normalize() * force as f32;
-
-        rocket.velocity += acceleration * time.delta_seconds();
-
-        if rocket.velocity.length() > rocket.max_speed {
-            rocket.velocity = rocket.velocity.normalize() * rocket.max_speed;
+        None if !keep_default_seed => {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            app.insert_resource(MatchSeed(now.as_nanos() as u64));
+        }
+        None => {}
+    }
+    if let Some(humans) = options.human_players() {
+        let computers: Vec<Player> = (humans..2).map(Player).collect();
+        if !computers.is_empty() {
+            app.insert_resource(AiOpponent::new(&computers));
         }
-        transform.translation.x += rocket.velocity.x * time.delta_seconds();
-        transform.translation.y += rocket.velocity.y * time.delta_seconds();
+        app.insert_state(AppState::InGame);
     }
 }
 
-fn add_background(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<MovingPatternMaterial>>,
-    window: Query<&Window>,
-) {
-    let window = window.single();
-    let resolution = Vec2::new(window.width(), window.height());
-    commands.spawn(MaterialMesh2dBundle {
-        mesh: meshes
-            .add(Mesh::from(Rectangle::from_size(resolution)))
-            .into(),
-        material: materials.add(MovingPatternMaterial {
-            color: LinearRgba::WHITE,
-            time: 0.0,
-        }),
-        ..default()
-    });
+/// Plays a local match without a window as fast as the simulation allows.
+fn run_headless(options: &LaunchOptions) {
+    let mut app = App::new();
+    app.add_plugins((
+        windowless_plugins(),
+        ScheduleRunnerPlugin::run_loop(Duration::ZERO),
+        SimulationPlugin,
+        HeadlessPlugin,
+    ))
+    .init_resource::<LocalInput>()
+    .add_systems(
+        FixedUpdate,
+        (
+            apply_local_input,
+            apply_ai_input.run_if(resource_exists::<AiOpponent>),
+            step_simulation,
+        )
+            .chain()
+            .run_if(in_state(AppState::InGame)),
+    );
+    configure_match(&mut app, options, false);
+    app.run();
 }
 
-fn setup(mut commands: Commands, mut effects: ResMut<Assets<EffectAsset>>) {
+fn setup(
+    mut commands: Commands,
+    mut effects: ResMut<Assets<EffectAsset>>,
+    bounds: Res<ArenaBounds>,
+) {
+    // Always show the whole arena; a window with another aspect ratio gets black bars.
+    let mut camera = Camera2dBundle::default();
+    camera.projection.scaling_mode = ScalingMode::AutoMin {
+        min_width: bounds.size().x,
+        min_height: bounds.size().y,
+    };
     commands.spawn((
-        Camera2dBundle {
-            camera: Camera { ..default() },
-            ..default()
-        },
-        post_process::PostProcessSettings {
-            intensity: 0.02,
-            ..default()
-        },
+        camera,
+        post_process::PostProcessBundle::default(),
     ));
 
     commands.spawn(DirectionalLightBundle {
@@ -204,139 +280,61 @@ fn setup(mut commands: Commands, mut effects: ResMut<Assets<EffectAsset>>) {
         .insert(Name::new("effect:meteor_explosion"));
 }
 
-#[derive(Component)]
-struct Sun {}
-
-fn add_sun(
+/// Gives every gravity well and wormhole of the arena something to look at.
+fn add_bodies(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
+    wells: Query<(Entity, &GravityWell), Added<GravityWell>>,
+    wormholes: Query<(Entity, &Wormhole), Added<Wormhole>>,
 ) {
-    commands.spawn((
-        MaterialMesh2dBundle {
-            mesh: meshes.add(Mesh::from(Circle::new(50.0))).into(),
-            material: materials.add(ColorMaterial::from(Color::srgb(7.0, 7.0, 0.0))),
-            transform: Transform::from_xyz(0.0, 0.0, 3.0),
-            ..default()
-        },
-        Sun {},
-    ));
-}
-
-fn handle_rocket_movement(
-    time: &Res<Time>,
-    keys: &Res<ButtonInput<KeyCode>>,
-    rocket: &mut Rocket,
-    transform: &mut Transform,
-) {
-    if keys.pressed(rocket.controls.accelerate) {
-        if rocket.speed < rocket.max_speed {
-            rocket.speed += 50.0 * time.delta_seconds();
-        }
-    } else {
-        if rocket.speed > 0.0 {
-            rocket.speed -= 50.0 * time.delta_seconds();
-        }
-    }
-
-    let mut rotation_input = 0.0;
-
-    if keys.pressed(rocket.controls.rotate_left) {
-        rotation_input += 4.0;
-    }
-
-    if keys.pressed(rocket.controls.rotate_right) {
-        rotation_input -= 4.0;
-    }
-
-    let max_rotation_speed = f32::to_radians(70.0);
-    let rotation_acceleration = f32::to_radians(50.0 * time.delta_seconds());
-    rocket.rotation_speed += rotation_input * rotation_acceleration;
-    rocket.rotation_speed = rocket
-        .rotation_speed
-        .clamp(-max_rotation_speed, max_rotation_speed);
-
-    transform.rotation *= Quat::from_rotation_z(rocket.rotation_speed * time.delta_seconds());
-
-    let direction = transform.rotation * Vec3::Y;
-    rocket.velocity = Vec2::new(direction.x, direction.y) * rocket.speed;
-
-    transform.translation += rocket.velocity.extend(0.0) * time.delta_seconds();
-}
-
-fn update_rocket_status(
-    mut commands: Commands,
-    keys: Res<ButtonInput<KeyCode>>,
-    mut entities: Query<(Entity, &mut Rocket, &mut Transform), Without<EffectProperties>>,    
-    mut effect: Query<(
-        &mut EffectProperties,
-        &mut EffectInitializers,
-        &mut Transform,
-    )>,
-    time: Res<Time>,
-) {
-    let rockets: Vec<(Entity, Mut<'_, Rocket>, Mut<'_, Transform>)> = entities.iter_mut().collect();
-    let Ok((mut properties, mut initializers, mut effect_transform)) = effect.get_single_mut()
-    else {
-        return;
-    };
-
-    if rockets.len() > 1 {
-        let (entity, rocket1, transform1) = &rockets[0];
-        let (entity2, rocket2, transform2) = &rockets[1];
-
-        if check_sun_collision(transform1, rocket1.radius_collision + 30.) {
-            commands.entity(*entity).despawn();
-            // spawn_particles(commands, meshes, materials, transform1, &time);
-
-            effect_transform.translation = transform1.translation;
-
-            let r = 255.;
-            let g = 165.;
-            let b = 0.;
-            let color = 0xFF000000u32 | (b as u32) << 16 | (g as u32) << 8 | (r as u32);
-            properties.set("spawn_color", color.into());
-            initializers.reset();
-        }
-
-        if check_sun_collision(transform2, rocket2.radius_collision + 30.) {
-            commands.entity(*entity2).despawn();
-            effect_transform.translation = transform2.translation;
-
-            let r = 255.;
-            let g = 165.;
-            let b = 0.;
-            let color = 0xFF000000u32 | (b as u32) << 16 | (g as u32) << 8 | (r as u32);
-            properties.set("spawn_color", color.into());
-            initializers.reset();
-        }
-
-        if check_collision(transform1, transform2, rocket1.radius_collision) {
-            effect_transform.translation = transform1.translation;
-
-            let r = 255.;
-            let g = 165.;
-            let b = 0.;
-            let color = 0xFF000000u32 | (b as u32) << 16 | (g as u32) << 8 | (r as u32);
-            properties.set("spawn_color", color.into());
-            initializers.reset();
-
-            effect_transform.translation = transform2.translation;
-
-            let r = 255.;
-            let g = 165.;
-            let b = 0.;
-            let color = 0xFF000000u32 | (b as u32) << 16 | (g as u32) << 8 | (r as u32);
-            properties.set("spawn_color", color.into());
-            initializers.reset();
-
-            for (entity, _, _) in entities.iter() {
-                commands.entity(entity).despawn();
-            }
+    for (entity, well) in &wells {
+        // The classic sun, with a kill radius of 30, is 50 across.
+        let radius = well.kill_radius + 20.0;
+        let (color, shape) = match well.kind {
+            WellKind::Sun => (Color::srgb(7.0, 7.0, 0.0), VectorShape::Sun { radius }),
+            WellKind::Planet => (Color::srgb(0.6, 1.2, 3.0), VectorShape::Planet { radius }),
+            WellKind::BlackHole => (Color::BLACK, VectorShape::BlackHole { radius }),
+        };
+        let mut body = commands.entity(entity);
+        body.insert((
+            MaterialMesh2dBundle {
+                mesh: meshes.add(Mesh::from(Circle::new(radius))).into(),
+                material: materials.add(ColorMaterial::from(color)),
+                transform: Transform::from_translation(well.position.extend(3.0)),
+                ..default()
+            },
+            shape,
+        ));
+        if well.kind == WellKind::BlackHole {
+            // Glowing accretion disk around the dark horizon.
+            body.with_children(|body| {
+                body.spawn((
+                    MaterialMesh2dBundle {
+                        mesh: meshes.add(Annulus::new(radius, radius * 1.6)).into(),
+                        material: materials.add(ColorMaterial::from(Color::srgb(4.0, 1.2, 0.3))),
+                        transform: Transform::from_xyz(0.0, 0.0, -0.1),
+                        ..default()
+                    },
+                    // Hidden along with the rest of the sprites in the vector style.
+                    RenderLayers::layer(SPRITE_LAYER),
+                ));
+            });
         }
     }
-
-    for (_, mut rocket, mut transform) in entities.iter_mut() {
-        handle_rocket_movement(&time, &keys, &mut rocket, &mut transform);
+    for (entity, wormhole) in &wormholes {
+        commands.entity(entity).insert((
+            MaterialMesh2dBundle {
+                mesh: meshes
+                    .add(Annulus::new(wormhole.radius * 0.7, wormhole.radius))
+                    .into(),
+                material: materials.add(ColorMaterial::from(Color::srgb(1.5, 0.4, 3.0))),
+                transform: Transform::from_translation(wormhole.position.extend(3.0)),
+                ..default()
+            },
+            VectorShape::Wormhole {
+                radius: wormhole.radius,
+            },
+        ));
     }
 }
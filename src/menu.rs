@@ -0,0 +1,295 @@
+//! Keyboard and gamepad driven menus, and the title screen built from them.
+//!
+//! A menu is a column of text entries under a [`Menu`] node. Up/Down (W/S, or the
+//! d-pad) move the highlight, Enter/Space (or South) confirm and Escape (or East)
+//! goes back; each screen reacts to the [`MenuConfirmed`] and [`MenuBack`] events of
+//! its own menu entity.
+
+use bevy::app::AppExit;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+
+use crate::ai::AiOpponent;
+use crate::lobby::LobbyBrowser;
+use crate::rocket::Player;
+use crate::settings::{spawn_settings_menu, SettingsClosed};
+use crate::AppState;
+
+const ENTRY_COLOR: Color = Color::srgb(0.7, 0.7, 0.7);
+const SELECTED_COLOR: Color = Color::srgb(1.0, 0.9, 0.3);
+
+/// Root of a menu, with the index of the highlighted entry.
+#[derive(Component)]
+pub struct Menu {
+    pub selected: usize,
+    entries: usize,
+    /// Ignores navigation, e.g. while the screen waits for a key to bind.
+    pub locked: bool,
+}
+
+/// One selectable line of a [`Menu`].
+#[derive(Component)]
+pub struct MenuEntry(pub usize);
+
+#[derive(Event)]
+pub struct MenuConfirmed {
+    pub menu: Entity,
+    pub entry: usize,
+}
+
+#[derive(Event)]
+pub struct MenuBack {
+    pub menu: Entity,
+}
+
+/// The highlight of `menu` moved to another entry.
+#[derive(Event)]
+pub struct MenuMoved {
+    pub menu: Entity,
+}
+
+/// Menu navigation from the keyboard and every connected gamepad this frame.
+#[derive(Default)]
+pub struct MenuInput {
+    pub up: bool,
+    pub down: bool,
+    pub left: bool,
+    pub right: bool,
+    pub confirm: bool,
+    pub back: bool,
+}
+
+/// Everything menus are navigated with.
+#[derive(SystemParam)]
+pub struct MenuButtons<'w> {
+    keys: Res<'w, ButtonInput<KeyCode>>,
+    gamepads: Res<'w, Gamepads>,
+    buttons: Res<'w, ButtonInput<GamepadButton>>,
+}
+
+impl MenuButtons<'_> {
+    pub fn read(&self) -> MenuInput {
+        let keys = &self.keys;
+        let pad = |button| {
+            self.gamepads.iter().any(|gamepad| {
+                self.buttons
+                    .just_pressed(GamepadButton::new(gamepad, button))
+            })
+        };
+        MenuInput {
+            up: keys.any_just_pressed([KeyCode::ArrowUp, KeyCode::KeyW])
+                || pad(GamepadButtonType::DPadUp),
+            down: keys.any_just_pressed([KeyCode::ArrowDown, KeyCode::KeyS])
+                || pad(GamepadButtonType::DPadDown),
+            left: keys.any_just_pressed([KeyCode::ArrowLeft, KeyCode::KeyA])
+                || pad(GamepadButtonType::DPadLeft),
+            right: keys.any_just_pressed([KeyCode::ArrowRight, KeyCode::KeyD])
+                || pad(GamepadButtonType::DPadRight),
+            confirm: keys.any_just_pressed([KeyCode::Enter, KeyCode::Space])
+                || pad(GamepadButtonType::South),
+            back: keys.any_just_pressed([KeyCode::Escape, KeyCode::Backspace])
+                || pad(GamepadButtonType::East),
+        }
+    }
+}
+
+/// Spawns a centered menu titled `title` that lives as long as `scope`, and returns
+/// its root so the caller can tag it.
+pub fn spawn_menu<S: States>(
+    commands: &mut Commands,
+    scope: S,
+    title: &str,
+    entries: &[&str],
+) -> Entity {
+    // Long menus get smaller entries so they still fit the smallest window.
+    let (entry_size, gap) = if entries.len() > 8 {
+        (20.0, 4.0)
+    } else {
+        (28.0, 12.0)
+    };
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(gap),
+                    ..default()
+                },
+                ..default()
+            },
+            Menu {
+                selected: 0,
+                entries: entries.len(),
+                locked: false,
+            },
+            StateScoped(scope),
+        ))
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(
+                    title,
+                    TextStyle {
+                        font_size: 64.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::bottom(Val::Px(32.0)),
+                    ..default()
+                }),
+            );
+            for (index, label) in entries.iter().enumerate() {
+                parent.spawn((
+                    TextBundle::from_section(
+                        *label,
+                        TextStyle {
+                            font_size: entry_size,
+                            color: ENTRY_COLOR,
+                            ..default()
+                        },
+                    ),
+                    MenuEntry(index),
+                ));
+            }
+        })
+        .id()
+}
+
+fn navigate_menus(
+    buttons: MenuButtons,
+    mut menus: Query<(Entity, &mut Menu)>,
+    mut confirmed: EventWriter<MenuConfirmed>,
+    mut back: EventWriter<MenuBack>,
+    mut moved: EventWriter<MenuMoved>,
+) {
+    let input = buttons.read();
+    for (entity, mut menu) in menus.iter_mut() {
+        if menu.entries == 0 || menu.locked {
+            continue;
+        }
+        if input.down {
+            menu.selected = (menu.selected + 1) % menu.entries;
+        }
+        if input.up {
+            menu.selected = (menu.selected + menu.entries - 1) % menu.entries;
+        }
+        if input.up != input.down {
+            moved.send(MenuMoved { menu: entity });
+        }
+        if input.confirm {
+            confirmed.send(MenuConfirmed {
+                menu: entity,
+                entry: menu.selected,
+            });
+        }
+        if input.back {
+            back.send(MenuBack { menu: entity });
+        }
+    }
+}
+
+fn highlight_entries(menus: Query<&Menu>, mut entries: Query<(&MenuEntry, &Parent, &mut Text)>) {
+    for (entry, parent, mut text) in entries.iter_mut() {
+        let Ok(menu) = menus.get(parent.get()) else {
+            continue;
+        };
+        let color = if menu.selected == entry.0 {
+            SELECTED_COLOR
+        } else {
+            ENTRY_COLOR
+        };
+        for section in text.sections.iter_mut() {
+            section.style.color = color;
+        }
+    }
+}
+
+#[derive(Component)]
+struct TitleMenu;
+
+const TITLE_LOCAL: usize = 0;
+const TITLE_VS_AI: usize = 1;
+const TITLE_ONLINE: usize = 2;
+const TITLE_SETTINGS: usize = 3;
+const TITLE_QUIT: usize = 4;
+
+fn spawn_title(mut commands: Commands) {
+    let menu = spawn_menu(
+        &mut commands,
+        AppState::Title,
+        "SPACEWARS",
+        &["Local match", "Versus AI", "Online", "Settings", "Quit"],
+    );
+    commands.entity(menu).insert(TitleMenu);
+}
+
+fn title_menu(
+    mut commands: Commands,
+    mut confirmed: EventReader<MenuConfirmed>,
+    title: Query<(), With<TitleMenu>>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for event in confirmed.read() {
+        if !title.contains(event.menu) {
+            continue;
+        }
+        match event.entry {
+            TITLE_LOCAL => {
+                commands.remove_resource::<AiOpponent>();
+                next_state.set(AppState::InGame);
+            }
+            TITLE_VS_AI => {
+                commands.insert_resource(AiOpponent::new(&[Player(1)]));
+                next_state.set(AppState::InGame);
+            }
+            TITLE_ONLINE => {
+                commands.insert_resource(LobbyBrowser::new());
+                next_state.set(AppState::Lobby);
+            }
+            TITLE_SETTINGS => next_state.set(AppState::Settings),
+            TITLE_QUIT => {
+                exit.send(AppExit::Success);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn spawn_title_settings(mut commands: Commands) {
+    spawn_settings_menu(&mut commands, AppState::Settings);
+}
+
+fn close_title_settings(
+    mut closed: EventReader<SettingsClosed>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if closed.read().count() > 0 {
+        next_state.set(AppState::Title);
+    }
+}
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<MenuConfirmed>()
+            .add_event::<MenuBack>()
+            .add_event::<MenuMoved>()
+            .add_systems(OnEnter(AppState::Title), spawn_title)
+            .add_systems(OnEnter(AppState::Settings), spawn_title_settings)
+            .add_systems(
+                Update,
+                (
+                    (navigate_menus, highlight_entries).chain(),
+                    title_menu.run_if(in_state(AppState::Title)),
+                    close_title_settings.run_if(in_state(AppState::Settings)),
+                ),
+            );
+    }
+}
@@ -0,0 +1,707 @@
+//! Peer-to-peer rollback netcode for online two-player matches.
+//!
+//! Both peers run the same deterministic [`Simulation`] at a fixed tick rate and only
+//! exchange inputs over UDP. Local input is scheduled a few ticks in the future
+//! (input delay) to hide latency; when the peer's input for a tick hasn't arrived yet
+//! it is predicted by repeating the last known one. Once the real input turns up and
+//! differs from the prediction, the state saved before that tick is restored and the
+//! ticks since then are simulated again.
+//!
+//! Every tick is hashed once the inputs of both sides are confirmed for it, and the
+//! hashes are exchanged so a desync is reported as soon as it happens.
+//!
+//! Kills and warps only reach the rest of the game once the inputs of their tick are
+//! confirmed, so a tick simulated again doesn't explode twice and a kill that was only
+//! predicted never shows.
+//!
+//! Start two processes to play over loopback:
+//!
+//! ```text
+//! spacewars --p2p 0 7000 127.0.0.1:7001
+//! spacewars --p2p 1 7001 127.0.0.1:7000
+//! ```
+//!
+//! `--input-delay <ticks>` and `--max-prediction <ticks>` tune the session.
+
+use bevy::ecs::event::Events;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use std::collections::{BTreeMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::io::ErrorKind;
+use std::net::{SocketAddr, UdpSocket};
+
+use crate::arena::Warped;
+use crate::asteroid::Asteroid;
+use crate::bullet::Bullet;
+use crate::input::{LocalInput, ShipInput};
+use crate::powerup::PowerUp;
+use crate::respawn::Respawns;
+use crate::rocket::{Player, Rocket, RocketDestroyed};
+use crate::{AppState, Scores, SimClock, SimRng, Simulation};
+
+const MSG_HELLO: u8 = 0;
+const MSG_INPUT: u8 = 1;
+/// Most inputs resent in a single packet while the peer hasn't acknowledged them.
+const MAX_INPUTS_PER_PACKET: usize = 64;
+/// How many ticks of checksums are kept around waiting for the peer's.
+const CHECKSUM_HISTORY: u32 = 600;
+
+#[derive(Clone, Copy, Debug)]
+pub struct RollbackSettings {
+    /// Ticks between sampling a local input and simulating it.
+    pub input_delay: u32,
+    /// How far the simulation may run ahead of the peer's confirmed input
+    /// before it waits for it.
+    pub max_prediction: u32,
+}
+
+impl Default for RollbackSettings {
+    fn default() -> Self {
+        Self {
+            input_delay: 2,
+            max_prediction: 8,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct SavedRocket {
+    player: Player,
+    rocket: Rocket,
+    transform: Transform,
+    input: ShipInput,
+    texture: Handle<Image>,
+}
+
+#[derive(Clone)]
+struct SavedBullet {
+    bullet: Bullet,
+    transform: Transform,
+    texture: Handle<Image>,
+}
+
+/// Everything the simulation reads or writes, captured before a tick.
+#[derive(Clone)]
+struct SavedState {
+    clock: SimClock,
+    scores: Scores,
+    respawns: Respawns,
+    rng: SimRng,
+    rockets: Vec<SavedRocket>,
+    bullets: Vec<SavedBullet>,
+    asteroids: Vec<(Asteroid, Transform)>,
+    power_ups: Vec<(PowerUp, Transform)>,
+}
+
+fn save_state(world: &mut World) -> SavedState {
+    let rockets = world
+        .query::<(&Player, &Rocket, &Transform, &ShipInput, &Handle<Image>)>()
+        .iter(world)
+        .map(|(player, rocket, transform, input, texture)| SavedRocket {
+            player: *player,
+            rocket: rocket.clone(),
+            transform: *transform,
+            input: *input,
+            texture: texture.clone(),
+        })
+        .collect();
+    let bullets = world
+        .query::<(&Bullet, &Transform, &Handle<Image>)>()
+        .iter(world)
+        .map(|(bullet, transform, texture)| SavedBullet {
+            bullet: bullet.clone(),
+            transform: *transform,
+            texture: texture.clone(),
+        })
+        .collect();
+    let asteroids = world
+        .query::<(&Asteroid, &Transform)>()
+        .iter(world)
+        .map(|(asteroid, transform)| (*asteroid, *transform))
+        .collect();
+    let power_ups = world
+        .query::<(&PowerUp, &Transform)>()
+        .iter(world)
+        .map(|(kind, transform)| (*kind, *transform))
+        .collect();
+
+    SavedState {
+        clock: *world.resource::<SimClock>(),
+        scores: *world.resource::<Scores>(),
+        respawns: *world.resource::<Respawns>(),
+        rng: *world.resource::<SimRng>(),
+        rockets,
+        bullets,
+        asteroids,
+        power_ups,
+    }
+}
+
+fn load_state(world: &mut World, state: &SavedState) {
+    *world.resource_mut::<SimClock>() = state.clock;
+    *world.resource_mut::<Scores>() = state.scores;
+    *world.resource_mut::<Respawns>() = state.respawns;
+    *world.resource_mut::<SimRng>() = state.rng;
+
+    let alive: Vec<(Entity, Player)> = world
+        .query_filtered::<(Entity, &Player), With<Rocket>>()
+        .iter(world)
+        .map(|(entity, player)| (entity, *player))
+        .collect();
+
+    for (entity, player) in &alive {
+        if !state.rockets.iter().any(|saved| saved.player == *player) {
+            world.despawn(*entity);
+        }
+    }
+
+    for saved in &state.rockets {
+        let components = (
+            saved.rocket.clone(),
+            saved.transform,
+            saved.input,
+            saved.player,
+        );
+        match alive.iter().find(|(_, player)| *player == saved.player) {
+            Some((entity, _)) => {
+                world.entity_mut(*entity).insert(components);
+            }
+            None => {
+                world.spawn((
+                    SpriteBundle {
+                        texture: saved.texture.clone(),
+                        transform: saved.transform,
+                        ..default()
+                    },
+                    components,
+                    StateScoped(AppState::InGame),
+                ));
+            }
+        }
+    }
+
+    // Bullets are recreated in their saved order so queries visit them the same way
+    // they did the first time around.
+    let bullets: Vec<Entity> = world
+        .query_filtered::<Entity, With<Bullet>>()
+        .iter(world)
+        .collect();
+    for entity in bullets {
+        world.despawn(entity);
+    }
+    for saved in &state.bullets {
+        world.spawn((
+            SpriteBundle {
+                texture: saved.texture.clone(),
+                transform: saved.transform,
+                ..default()
+            },
+            saved.bullet.clone(),
+            StateScoped(AppState::InGame),
+        ));
+    }
+
+    // Same for asteroids, which get their looks back once they are respawned.
+    let asteroids: Vec<Entity> = world
+        .query_filtered::<Entity, With<Asteroid>>()
+        .iter(world)
+        .collect();
+    for entity in asteroids {
+        world.despawn(entity);
+    }
+    for (asteroid, transform) in &state.asteroids {
+        world.spawn((
+            *asteroid,
+            SpatialBundle::from_transform(*transform),
+            StateScoped(AppState::InGame),
+        ));
+    }
+
+    // And power-ups.
+    let power_ups: Vec<Entity> = world
+        .query_filtered::<Entity, With<PowerUp>>()
+        .iter(world)
+        .collect();
+    for entity in power_ups {
+        world.despawn(entity);
+    }
+    for (kind, transform) in &state.power_ups {
+        world.spawn(kind.bundle(transform.translation.truncate()));
+    }
+}
+
+/// Events a simulated tick sent, held back until its inputs are confirmed.
+#[derive(Default)]
+struct TickEvents {
+    destroyed: Vec<RocketDestroyed>,
+    warped: Vec<Warped>,
+}
+
+/// Event queues the simulation reads and writes while the session runs, kept apart
+/// from the ones the rest of the game reads.
+#[derive(Default)]
+struct SimEvents {
+    destroyed: Events<RocketDestroyed>,
+    warped: Events<Warped>,
+}
+
+impl SimEvents {
+    /// Trades places with the queues in `world`.
+    fn swap(&mut self, world: &mut World) {
+        std::mem::swap(
+            &mut *world.resource_mut::<Events<RocketDestroyed>>(),
+            &mut self.destroyed,
+        );
+        std::mem::swap(&mut *world.resource_mut::<Events<Warped>>(), &mut self.warped);
+    }
+
+    /// Everything sent since the last call. The simulation reads its events within the
+    /// same tick, so nothing it still needs is lost.
+    fn take(&mut self) -> TickEvents {
+        TickEvents {
+            destroyed: self.destroyed.drain().collect(),
+            warped: self.warped.drain().collect(),
+        }
+    }
+}
+
+fn hash_vec3(hasher: &mut impl Hasher, v: Vec3) {
+    v.to_array().map(f32::to_bits).hash(hasher);
+}
+
+/// Hash of a saved simulation state; equal on both peers as long as they are in sync.
+fn checksum(state: &SavedState) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    state.clock.tick.hash(&mut hasher);
+    state.scores.0.hash(&mut hasher);
+    state.respawns.hash(&mut hasher);
+    state.rng.hash(&mut hasher);
+
+    let mut rockets: Vec<&SavedRocket> = state.rockets.iter().collect();
+    rockets.sort_by_key(|saved| saved.player.0);
+    for SavedRocket {
+        player,
+        rocket,
+        transform,
+        ..
+    } in rockets
+    {
+        player.hash(&mut hasher);
+        hash_vec3(&mut hasher, transform.translation);
+        transform
+            .rotation
+            .to_array()
+            .map(f32::to_bits)
+            .hash(&mut hasher);
+        rocket
+            .velocity
+            .to_array()
+            .map(f32::to_bits)
+            .hash(&mut hasher);
+        rocket.speed.to_bits().hash(&mut hasher);
+        rocket.ammo.hash(&mut hasher);
+        rocket.fuel.to_bits().hash(&mut hasher);
+        rocket.hyperspace.hash(&mut hasher);
+        rocket.boosts.hash(&mut hasher);
+        rocket.hull.to_bits().hash(&mut hasher);
+        rocket.shield.to_bits().hash(&mut hasher);
+        rocket.last_hit.hash(&mut hasher);
+        rocket.protected_until.hash(&mut hasher);
+    }
+
+    // Bullets are combined order-independently.
+    let bullets = state.bullets.iter().fold(0u64, |acc, saved| {
+        let mut bullet_hasher = std::collections::hash_map::DefaultHasher::new();
+        hash_vec3(&mut bullet_hasher, saved.transform.translation);
+        saved
+            .bullet
+            .velocity
+            .to_array()
+            .map(f32::to_bits)
+            .hash(&mut bullet_hasher);
+        acc.wrapping_add(bullet_hasher.finish())
+    });
+    bullets.hash(&mut hasher);
+
+    let asteroids = state
+        .asteroids
+        .iter()
+        .fold(0u64, |acc, (asteroid, transform)| {
+            let mut asteroid_hasher = std::collections::hash_map::DefaultHasher::new();
+            hash_vec3(&mut asteroid_hasher, transform.translation);
+            asteroid
+                .velocity
+                .to_array()
+                .map(f32::to_bits)
+                .hash(&mut asteroid_hasher);
+            asteroid.radius.to_bits().hash(&mut asteroid_hasher);
+            acc.wrapping_add(asteroid_hasher.finish())
+        });
+    asteroids.hash(&mut hasher);
+
+    let power_ups = state.power_ups.iter().fold(0u64, |acc, (kind, transform)| {
+        let mut power_up_hasher = std::collections::hash_map::DefaultHasher::new();
+        hash_vec3(&mut power_up_hasher, transform.translation);
+        kind.hash(&mut power_up_hasher);
+        acc.wrapping_add(power_up_hasher.finish())
+    });
+    power_ups.hash(&mut hasher);
+
+    hasher.finish()
+}
+
+#[derive(Resource)]
+pub struct RollbackSession {
+    socket: UdpSocket,
+    remote: SocketAddr,
+    pub local_player: Player,
+    pub remote_player: Player,
+    pub settings: RollbackSettings,
+    connected: bool,
+    /// Next tick to be simulated.
+    tick: u32,
+    /// Local inputs by tick, including the ones scheduled ahead by the input delay.
+    local_inputs: Vec<ShipInput>,
+    /// Peer inputs by tick, `None` where they haven't arrived yet.
+    remote_inputs: Vec<Option<ShipInput>>,
+    /// Number of leading ticks for which the peer's input is known.
+    confirmed: u32,
+    /// Peer inputs that were guessed for ticks that have already been simulated.
+    predicted: HashMap<u32, ShipInput>,
+    /// Number of our inputs the peer has received.
+    remote_ack: u32,
+    snapshots: VecDeque<(u32, SavedState)>,
+    checksums: HashMap<u32, u64>,
+    remote_checksums: HashMap<u32, u64>,
+    latest_checksum: Option<(u32, u64)>,
+    /// Number of leading ticks whose checksum has been taken.
+    hashed: u32,
+    sim_events: SimEvents,
+    /// Events of simulated ticks whose inputs aren't all confirmed yet.
+    unconfirmed_events: BTreeMap<u32, TickEvents>,
+    /// First tick at which the peers were found to disagree.
+    pub desync: Option<u32>,
+}
+
+impl RollbackSession {
+    pub fn new(
+        local_player: Player,
+        local_port: u16,
+        remote: SocketAddr,
+        settings: RollbackSettings,
+    ) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", local_port))?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Self {
+            socket,
+            remote,
+            local_player,
+            remote_player: Player(1 - local_player.0),
+            settings,
+            connected: false,
+            tick: 0,
+            local_inputs: vec![ShipInput::default(); settings.input_delay as usize],
+            remote_inputs: Vec::new(),
+            confirmed: 0,
+            predicted: HashMap::new(),
+            remote_ack: 0,
+            snapshots: VecDeque::new(),
+            checksums: HashMap::new(),
+            remote_checksums: HashMap::new(),
+            latest_checksum: None,
+            hashed: 0,
+            sim_events: SimEvents::default(),
+            unconfirmed_events: BTreeMap::new(),
+            desync: None,
+        })
+    }
+
+    fn receive(&mut self) {
+        let mut buf = [0u8; 512];
+        loop {
+            match self.socket.recv_from(&mut buf) {
+                Ok((len, from)) if from == self.remote => self.handle_packet(&buf[..len]),
+                Ok(_) => {}
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                // The peer's port being closed shows up here on some platforms.
+                Err(err) if err.kind() == ErrorKind::ConnectionReset => {}
+                Err(err) => {
+                    warn!("rollback socket error: {err}");
+                    break;
+                }
+            }
+        }
+    }
+
+    fn handle_packet(&mut self, packet: &[u8]) {
+        match packet.first() {
+            Some(&MSG_HELLO) => {
+                self.connected = true;
+            }
+            Some(&MSG_INPUT) if packet.len() >= 6 => {
+                self.connected = true;
+                let start = read_u32(&packet[1..]);
+                let count = packet[5] as usize;
+                let Some(rest) = packet.get(6 + count..6 + count + 16) else {
+                    return;
+                };
+                // The peer can't be further ahead than it may predict, plus its input
+                // delay. Anything claiming otherwise is forged or garbled.
+                let furthest = self
+                    .tick
+                    .saturating_add(self.settings.max_prediction)
+                    .saturating_add(self.settings.input_delay);
+                if start > furthest {
+                    return;
+                }
+
+                for (i, bits) in packet[6..6 + count].iter().enumerate() {
+                    let Some(tick) = start.checked_add(i as u32) else {
+                        break;
+                    };
+                    if tick < self.confirmed {
+                        continue;
+                    }
+                    let tick = tick as usize;
+                    if self.remote_inputs.len() <= tick {
+                        self.remote_inputs.resize(tick + 1, None);
+                    }
+                    self.remote_inputs[tick] = Some(ShipInput(*bits));
+                }
+                while let Some(Some(_)) = self.remote_inputs.get(self.confirmed as usize) {
+                    self.confirmed += 1;
+                }
+
+                self.remote_ack = self.remote_ack.max(read_u32(rest));
+                let checksum_tick = read_u32(&rest[4..]);
+                let checksum = u64::from_le_bytes(rest[8..16].try_into().unwrap());
+                if checksum_tick != u32::MAX {
+                    self.compare_checksum(checksum_tick, checksum);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn compare_checksum(&mut self, tick: u32, remote: u64) {
+        match self.checksums.get(&tick) {
+            Some(local) if *local != remote => self.report_desync(tick),
+            Some(_) => {}
+            None => {
+                self.remote_checksums.insert(tick, remote);
+            }
+        }
+    }
+
+    fn report_desync(&mut self, tick: u32) {
+        if self.desync.is_none() {
+            error!("desync detected at tick {tick}");
+            self.desync = Some(tick);
+        }
+    }
+
+    fn send(&self) {
+        let mut packet = Vec::with_capacity(32);
+        if !self.connected {
+            packet.push(MSG_HELLO);
+        } else {
+            let start = self.remote_ack as usize;
+            let end = self.local_inputs.len().min(start + MAX_INPUTS_PER_PACKET);
+            packet.push(MSG_INPUT);
+            packet.extend_from_slice(&self.remote_ack.to_le_bytes());
+            packet.push((end.saturating_sub(start)) as u8);
+            packet.extend(self.local_inputs[start.min(end)..end].iter().map(|i| i.0));
+            packet.extend_from_slice(&self.confirmed.to_le_bytes());
+            let (tick, checksum) = self.latest_checksum.unwrap_or((u32::MAX, 0));
+            packet.extend_from_slice(&tick.to_le_bytes());
+            packet.extend_from_slice(&checksum.to_le_bytes());
+        }
+        if let Err(err) = self.socket.send_to(&packet, self.remote) {
+            if err.kind() != ErrorKind::WouldBlock {
+                warn!("rollback socket error: {err}");
+            }
+        }
+    }
+
+    /// Earliest simulated tick whose predicted peer input turned out wrong.
+    fn first_misprediction(&mut self) -> Option<u32> {
+        let mut first = None;
+        let confirmed = self.confirmed;
+        let remote_inputs = &self.remote_inputs;
+        self.predicted.retain(|tick, guess| {
+            if *tick >= confirmed {
+                return true;
+            }
+            if remote_inputs[*tick as usize] != Some(*guess) {
+                first = Some(first.map_or(*tick, |t: u32| t.min(*tick)));
+            }
+            false
+        });
+        first
+    }
+
+    fn predict_remote(&self) -> ShipInput {
+        let last = self
+            .confirmed
+            .checked_sub(1)
+            .and_then(|tick| self.remote_inputs[tick as usize])
+            .unwrap_or_default();
+        // Holding thrust and rotation is likely to continue, a fresh shot or jump is not.
+        ShipInput(last.0 & !ShipInput::EDGE_TRIGGERED)
+    }
+
+    /// Saves the state, then simulates `tick` with the best inputs known so far.
+    fn advance(&mut self, world: &mut World, tick: u32) {
+        self.snapshots.retain(|(saved, _)| *saved < tick);
+        self.snapshots.push_back((tick, save_state(world)));
+        while self.snapshots.len() > self.settings.max_prediction as usize + 2 {
+            self.snapshots.pop_front();
+        }
+
+        let local = self.local_inputs[tick as usize];
+        let remote = match self.remote_inputs.get(tick as usize) {
+            Some(Some(input)) => *input,
+            _ => {
+                let guess = self.predict_remote();
+                self.predicted.insert(tick, guess);
+                guess
+            }
+        };
+
+        let (local_player, remote_player) = (self.local_player, self.remote_player);
+        for (player, mut input) in world.query::<(&Player, &mut ShipInput)>().iter_mut(world) {
+            if *player == local_player {
+                *input = local;
+            } else if *player == remote_player {
+                *input = remote;
+            }
+        }
+
+        self.sim_events.swap(world);
+        world.run_schedule(Simulation);
+        self.sim_events.swap(world);
+        self.unconfirmed_events.insert(tick, self.sim_events.take());
+    }
+
+    /// Hashes the state after every tick confirmed by now, which is the one saved
+    /// before the next tick. Both peers hash the same ticks that way, whether or not
+    /// they had to simulate them again.
+    fn hash_confirmed(&mut self) {
+        while self.hashed < self.confirmed && self.hashed + 1 < self.tick {
+            let tick = self.hashed;
+            self.hashed += 1;
+            let Some((_, state)) = self.snapshots.iter().find(|(saved, _)| *saved == tick + 1)
+            else {
+                // Only ever the case for states too old to be kept around.
+                continue;
+            };
+            let checksum = checksum(state);
+            if let Some(remote) = self.remote_checksums.remove(&tick) {
+                if remote != checksum {
+                    self.report_desync(tick);
+                }
+            }
+            self.checksums.insert(tick, checksum);
+            self.latest_checksum = Some((tick, checksum));
+            let oldest = tick.saturating_sub(CHECKSUM_HISTORY);
+            self.checksums.retain(|t, _| *t >= oldest);
+            self.remote_checksums.retain(|t, _| *t >= oldest);
+        }
+    }
+
+    /// Restores the state saved before `tick` and simulates again up to the current
+    /// tick. Returns `false` if that state is no longer around.
+    fn rollback_to(&mut self, world: &mut World, tick: u32) -> bool {
+        let Some((_, state)) = self.snapshots.iter().find(|(saved, _)| *saved == tick) else {
+            return false;
+        };
+        let state = state.clone();
+        load_state(world, &state);
+        // Whatever these ticks sent is about to be sent again, maybe differently.
+        self.unconfirmed_events.split_off(&tick);
+        for resim in tick..self.tick {
+            self.advance(world, resim);
+        }
+        true
+    }
+
+    /// Hands the events of the ticks confirmed by now to the rest of the game.
+    fn send_confirmed_events(&mut self, world: &mut World) {
+        let unconfirmed = self.unconfirmed_events.split_off(&self.confirmed);
+        let confirmed = std::mem::replace(&mut self.unconfirmed_events, unconfirmed);
+        for events in confirmed.into_values() {
+            world.send_event_batch(events.destroyed);
+            world.send_event_batch(events.warped);
+        }
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes[..4].try_into().unwrap())
+}
+
+/// Replaces the local [`Simulation`] step while an online match is running. Ends the
+/// match if the state can no longer be put right.
+pub fn rollback_step(world: &mut World) {
+    let lost = world.resource_scope(|world, mut session: Mut<RollbackSession>| {
+        session.receive();
+        if !session.connected {
+            session.send();
+            return false;
+        }
+
+        if let Some(tick) = session.first_misprediction() {
+            if tick < session.tick && !session.rollback_to(world, tick) {
+                error!("no saved state for tick {tick}, cannot roll back");
+                session.desync.get_or_insert(tick);
+                return true;
+            }
+        }
+        session.send_confirmed_events(world);
+        session.hash_confirmed();
+
+        // Too far ahead of the peer: wait for its inputs instead of guessing more.
+        if session.tick >= session.confirmed + session.settings.max_prediction {
+            session.send();
+            return false;
+        }
+
+        let local_player = session.local_player;
+        let input = world.resource_mut::<LocalInput>().take(local_player);
+        session.local_inputs.push(input);
+
+        let tick = session.tick;
+        session.advance(world, tick);
+        session.tick += 1;
+        session.send();
+        false
+    });
+
+    if lost {
+        world.remove_resource::<RollbackSession>();
+        world
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::Title);
+    }
+}
+
+/// Where the two peers of a session are, as given by `--p2p`.
+#[derive(Clone, Copy, Debug)]
+pub struct PeerLink {
+    pub local_player: Player,
+    pub local_port: u16,
+    pub remote: SocketAddr,
+}
+
+/// Opens a session over `link`, or logs why it couldn't.
+pub fn open_session(link: PeerLink, settings: RollbackSettings) -> Option<RollbackSession> {
+    match RollbackSession::new(link.local_player, link.local_port, link.remote, settings) {
+        Ok(session) => Some(session),
+        Err(err) => {
+            error!("could not open port {}: {err}", link.local_port);
+            None
+        }
+    }
+}
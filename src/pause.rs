@@ -0,0 +1,215 @@
+//! Pausing a match.
+//!
+//! Escape, P or the gamepad's Start button freezes virtual time, which stops the
+//! fixed-tick simulation, and opens a menu to resume, restart the round, change
+//! settings or go back to the title screen. Online matches keep running, since the
+//! other side can't be paused; their menu only offers to resume or leave.
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+
+use crate::client::ServerConnection;
+use crate::input::LocalInput;
+use crate::menu::{spawn_menu, MenuBack, MenuConfirmed};
+use crate::netcode::RollbackSession;
+use crate::replay::ReplayState;
+use crate::settings::{spawn_settings_menu, SettingsClosed};
+use crate::{restart_round, AppState};
+
+#[derive(SubStates, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[source(AppState = AppState::InGame)]
+pub enum Pause {
+    #[default]
+    Running,
+    Paused,
+    /// The settings screen, opened from the pause menu.
+    Settings,
+}
+
+#[derive(Component)]
+struct PauseMenu;
+
+const PAUSE_RESUME: usize = 0;
+const PAUSE_RESTART: usize = 1;
+const PAUSE_SETTINGS: usize = 2;
+const PAUSE_QUIT: usize = 3;
+/// Entries of the menu of an online match.
+const ONLINE_RESUME: usize = 0;
+const ONLINE_LEAVE: usize = 1;
+
+/// The P key and every gamepad's Start button, which both pause and resume.
+#[derive(SystemParam)]
+struct PauseButton<'w> {
+    keys: Res<'w, ButtonInput<KeyCode>>,
+    gamepads: Res<'w, Gamepads>,
+    buttons: Res<'w, ButtonInput<GamepadButton>>,
+}
+
+impl PauseButton<'_> {
+    fn just_pressed(&self) -> bool {
+        self.keys.just_pressed(KeyCode::KeyP)
+            || self.gamepads.iter().any(|gamepad| {
+                let start = GamepadButton::new(gamepad, GamepadButtonType::Start);
+                self.buttons.just_pressed(start)
+            })
+    }
+}
+
+/// The network match being played, if any.
+#[derive(SystemParam)]
+struct Network<'w> {
+    session: Option<Res<'w, RollbackSession>>,
+    connection: Option<Res<'w, ServerConnection>>,
+}
+
+impl Network<'_> {
+    fn online(&self) -> bool {
+        self.session.is_some() || self.connection.is_some()
+    }
+}
+
+/// Whether the match is played over the network.
+fn online(network: Network) -> bool {
+    network.online()
+}
+
+fn toggle_pause(pause: PauseButton, mut next_state: ResMut<NextState<Pause>>) {
+    if pause.just_pressed() || pause.keys.just_pressed(KeyCode::Escape) {
+        next_state.set(Pause::Paused);
+    }
+}
+
+fn freeze(mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+}
+
+/// Restarts time and drops whatever was pressed while the game stood still, so a
+/// shot fired in the menu doesn't go off on resume.
+fn unfreeze(mut time: ResMut<Time<Virtual>>, mut local: ResMut<LocalInput>) {
+    time.unpause();
+    *local = LocalInput::default();
+}
+
+fn spawn_pause_menu(mut commands: Commands, network: Network) {
+    let menu = if network.online() {
+        spawn_menu(
+            &mut commands,
+            Pause::Paused,
+            "MENU",
+            &["Resume", "Leave match"],
+        )
+    } else {
+        spawn_menu(
+            &mut commands,
+            Pause::Paused,
+            "PAUSED",
+            &["Resume", "Restart round", "Settings", "Quit to title"],
+        )
+    };
+    commands
+        .entity(menu)
+        .insert((PauseMenu, BackgroundColor(Color::BLACK.with_alpha(0.6))));
+}
+
+fn pause_menu(
+    mut commands: Commands,
+    pause: PauseButton,
+    mut confirmed: EventReader<MenuConfirmed>,
+    mut back: EventReader<MenuBack>,
+    menu: Query<(), With<PauseMenu>>,
+    mut next_pause: ResMut<NextState<Pause>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let back = back.read().any(|event| menu.contains(event.menu));
+    // Escape arrives as a menu back.
+    if back || pause.just_pressed() {
+        next_pause.set(Pause::Running);
+        return;
+    }
+
+    for event in confirmed.read().filter(|event| menu.contains(event.menu)) {
+        match event.entry {
+            PAUSE_RESUME => next_pause.set(Pause::Running),
+            PAUSE_RESTART => {
+                commands.add(restart_round);
+                next_pause.set(Pause::Running);
+            }
+            PAUSE_SETTINGS => next_pause.set(Pause::Settings),
+            PAUSE_QUIT => next_state.set(AppState::Title),
+            _ => {}
+        }
+    }
+}
+
+/// The menu of an online match, which can only be resumed or left.
+fn online_menu(
+    mut commands: Commands,
+    pause: PauseButton,
+    mut confirmed: EventReader<MenuConfirmed>,
+    mut back: EventReader<MenuBack>,
+    menu: Query<(), With<PauseMenu>>,
+    mut next_pause: ResMut<NextState<Pause>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let back = back.read().any(|event| menu.contains(event.menu));
+    if back || pause.just_pressed() {
+        next_pause.set(Pause::Running);
+        return;
+    }
+
+    for event in confirmed.read().filter(|event| menu.contains(event.menu)) {
+        match event.entry {
+            ONLINE_RESUME => next_pause.set(Pause::Running),
+            ONLINE_LEAVE => {
+                // Dropping the connection tells the server we are gone.
+                commands.remove_resource::<RollbackSession>();
+                commands.remove_resource::<ServerConnection>();
+                next_state.set(AppState::Title);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn spawn_pause_settings(mut commands: Commands) {
+    let menu = spawn_settings_menu(&mut commands, Pause::Settings);
+    commands
+        .entity(menu)
+        .insert(BackgroundColor(Color::BLACK.with_alpha(0.6)));
+}
+
+fn close_pause_settings(
+    mut closed: EventReader<SettingsClosed>,
+    mut next_state: ResMut<NextState<Pause>>,
+) {
+    if closed.read().count() > 0 {
+        next_state.set(Pause::Paused);
+    }
+}
+
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_sub_state::<Pause>()
+            .enable_state_scoped_entities::<Pause>()
+            .add_systems(OnExit(Pause::Running), freeze.run_if(not(online)))
+            .add_systems(OnEnter(Pause::Running), unfreeze)
+            .add_systems(OnExit(AppState::InGame), unfreeze)
+            .add_systems(OnEnter(Pause::Paused), spawn_pause_menu)
+            .add_systems(OnEnter(Pause::Settings), spawn_pause_settings)
+            .add_systems(
+                Update,
+                (
+                    toggle_pause
+                        .run_if(in_state(Pause::Running))
+                        .run_if(in_state(ReplayState::Live)),
+                    pause_menu
+                        .run_if(in_state(Pause::Paused))
+                        .run_if(not(online)),
+                    online_menu.run_if(in_state(Pause::Paused)).run_if(online),
+                    close_pause_settings.run_if(in_state(Pause::Settings)),
+                ),
+            );
+    }
+}
@@ -1,10 +1,15 @@
-//! This example shows how to create a custom render pass that runs after the main pass
-//! and reads the texture generated by the main pass.
+//! Screen effects that run after the main 2D pass, one full-screen pass each.
 //!
-//! The example shader is a very simple implementation of chromatic aberration.
-//! To adapt this example for 2D, replace all instances of 3D structures (such as `Core3D`, etc.) with their corresponding 2D counterparts.
+//! A camera lists the effects it wants, in order, in its [`PostProcessStack`], and
+//! carries one settings component per effect. Each effect has its own shader in
+//! `assets/shaders/post/`; the settings component is extracted to the render world
+//! and uploaded as that shader's uniform. Passes read the previous pass's output, so
+//! both the stack and the settings can be changed per camera while the game runs.
 //!
-//! This is a fairly low level example and assumes some familiarity with rendering concepts and wgpu.
+//! [`PostEffect::Phosphor`] is the one pass that also reads earlier frames: it keeps
+//! the screen of every camera in a history texture that fades a little each frame.
+//!
+//! This is a fairly low level module and assumes some familiarity with rendering concepts and wgpu.
 
 use bevy::{
     core_pipeline::{
@@ -23,13 +28,15 @@ use bevy::{
         },
         render_resource::{
             binding_types::{sampler, texture_2d, uniform_buffer},
+            encase::internal::WriteInto,
             *,
         },
         renderer::{RenderContext, RenderDevice},
         texture::BevyDefault,
         view::ViewTarget,
-        RenderApp,
+        Render, RenderApp, RenderSet,
     },
+    utils::HashMap,
 };
 
 /// It is generally encouraged to set up post processing effects as a plugin
@@ -37,19 +44,17 @@ pub struct PostProcessPlugin;
 
 impl Plugin for PostProcessPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins((
-            // The settings will be a component that lives in the main world but will
-            // be extracted to the render world every frame.
-            // This makes it possible to control the effect from the main world.
-            // This plugin will take care of extracting it automatically.
-            // It's important to derive [`ExtractComponent`] on [`PostProcessingSettings`]
-            // for this plugin to work correctly.
-            ExtractComponentPlugin::<PostProcessSettings>::default(),
-            // The settings will also be the data used in the shader.
-            // This plugin will prepare the component for the GPU by creating a uniform buffer
-            // and writing the data to that buffer every frame.
-            UniformComponentPlugin::<PostProcessSettings>::default(),
-        ));
+        // The stack and the settings are components that live in the main world but
+        // are extracted to the render world every frame, and the settings are also
+        // written to a uniform buffer for their shader.
+        app.add_plugins(ExtractComponentPlugin::<PostProcessStack>::default());
+        add_effect::<PhosphorSettings>(app);
+        add_effect::<CurvatureSettings>(app);
+        add_effect::<ScanlineSettings>(app);
+        add_effect::<ChromaticAberrationSettings>(app);
+        add_effect::<VignetteSettings>(app);
+        app.init_resource::<CrtStrength>()
+            .init_resource::<CrtPreset>();
 
         // We need to get the render app from the main app
         let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
@@ -57,25 +62,14 @@ impl Plugin for PostProcessPlugin {
         };
 
         render_app
-            // Bevy's renderer uses a render graph which is a collection of nodes in a directed acyclic graph.
-            // It currently runs on each view/camera and executes each node in the specified order.
-            // It will make sure that any node that needs a dependency from another node
-            // only runs when that dependency is done.
-            //
-            // Each node can execute arbitrary work, but it generally runs at least one render pass.
-            // A node only has access to the render world, so if you need data from the main world
-            // you need to extract it manually or with the plugin like above.
-            // Add a [`Node`] to the [`RenderGraph`]
-            // The Node needs to impl FromWorld
-            //
+            .init_resource::<PhosphorHistories>()
+            .add_systems(
+                Render,
+                prepare_phosphor_histories.in_set(RenderSet::PrepareResources),
+            )
             // The [`ViewNodeRunner`] is a special [`Node`] that will automatically run the node for each view
             // matching the [`ViewQuery`]
-            .add_render_graph_node::<ViewNodeRunner<PostProcessNode>>(
-                // Specify the label of the graph, in this case we want the graph for 3d
-                Core2d,
-                // It also needs the label of the node
-                PostProcessLabel,
-            )
+            .add_render_graph_node::<ViewNodeRunner<PostProcessNode>>(Core2d, PostProcessLabel)
             .add_render_graph_edges(
                 Core2d,
                 // Specify the node ordering.
@@ -95,8 +89,68 @@ impl Plugin for PostProcessPlugin {
         };
 
         render_app
-            // Initialize the pipeline
-            .init_resource::<PostProcessPipeline>();
+            // Initialize the pipelines
+            .init_resource::<PostProcessPipelines>();
+    }
+}
+
+fn add_effect<T: Component + ExtractComponent + ShaderType + WriteInto + Clone>(app: &mut App) {
+    app.add_plugins((
+        ExtractComponentPlugin::<T>::default(),
+        UniformComponentPlugin::<T>::default(),
+    ));
+}
+
+/// The passes [`PostProcessPlugin`] can run.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum PostEffect {
+    /// Leaves fading trails behind everything that moves, like the slow phosphor of
+    /// an old vector display.
+    Phosphor,
+    /// Bulges the picture like a curved tube, fades its edges and pixelates it.
+    Curvature,
+    /// Darkens every other line and adds grain and flicker.
+    Scanlines,
+    /// Splits the red and blue channels apart towards the edges.
+    ChromaticAberration,
+    /// Darkens the corners.
+    Vignette,
+}
+
+impl PostEffect {
+    pub const ALL: [PostEffect; 5] = [
+        PostEffect::Phosphor,
+        PostEffect::Curvature,
+        PostEffect::Scanlines,
+        PostEffect::ChromaticAberration,
+        PostEffect::Vignette,
+    ];
+
+    fn shader(&self) -> &'static str {
+        match self {
+            PostEffect::Phosphor => "shaders/post/phosphor.wgsl",
+            PostEffect::Curvature => "shaders/post/curvature.wgsl",
+            PostEffect::Scanlines => "shaders/post/scanlines.wgsl",
+            PostEffect::ChromaticAberration => "shaders/post/chromatic_aberration.wgsl",
+            PostEffect::Vignette => "shaders/post/vignette.wgsl",
+        }
+    }
+}
+
+/// The effects a camera runs, first to last. An effect whose settings component is
+/// missing from the camera is skipped.
+#[derive(Component, Clone, ExtractComponent)]
+pub struct PostProcessStack(pub Vec<PostEffect>);
+
+impl Default for PostProcessStack {
+    fn default() -> Self {
+        Self(vec![
+            PostEffect::Phosphor,
+            PostEffect::ChromaticAberration,
+            PostEffect::Curvature,
+            PostEffect::Scanlines,
+            PostEffect::Vignette,
+        ])
     }
 }
 
@@ -109,146 +163,181 @@ struct PostProcessNode;
 
 // The ViewNode trait is required by the ViewNodeRunner
 impl ViewNode for PostProcessNode {
-    // The node needs a query to gather data from the ECS in order to do its rendering,
-    // but it's not a normal system so we need to define it manually.
-    //
-    // This query will only run on the view entity
+    // This query will only run on the view entity. As there could be multiple
+    // settings sent to the GPU (one per camera), we need the index of the ones that
+    // are associated with the current view.
     type ViewQuery = (
         &'static ViewTarget,
-        // This makes sure the node only runs on cameras with the PostProcessSettings component
-        &'static PostProcessSettings,
-        // As there could be multiple post processing components sent to the GPU (one per camera),
-        // we need to get the index of the one that is associated with the current view.
-        &'static DynamicUniformIndex<PostProcessSettings>,
+        &'static PostProcessStack,
+        Option<&'static DynamicUniformIndex<PhosphorSettings>>,
+        Option<&'static DynamicUniformIndex<CurvatureSettings>>,
+        Option<&'static DynamicUniformIndex<ScanlineSettings>>,
+        Option<&'static DynamicUniformIndex<ChromaticAberrationSettings>>,
+        Option<&'static DynamicUniformIndex<VignetteSettings>>,
     );
 
-    // Runs the node logic
-    // This is where you encode draw commands.
-    //
-    // This will run on every view on which the graph is running.
-    // If you don't want your effect to run on every camera,
-    // you'll need to make sure you have a marker component as part of [`ViewQuery`]
-    // to identify which camera(s) should run the effect.
     fn run(
         &self,
-        _graph: &mut RenderGraphContext,
+        graph: &mut RenderGraphContext,
         render_context: &mut RenderContext,
-        (view_target, _post_process_settings, settings_index): QueryItem<Self::ViewQuery>,
+        (view_target, stack, phosphor, curvature, scanlines, aberration, vignette): QueryItem<
+            Self::ViewQuery,
+        >,
         world: &World,
     ) -> Result<(), NodeRunError> {
-        // Get the pipeline resource that contains the global data we need
-        // to create the render pipeline
-        let post_process_pipeline = world.resource::<PostProcessPipeline>();
-
-        // The pipeline cache is a cache of all previously created pipelines.
-        // It is required to avoid creating a new pipeline each frame,
-        // which is expensive due to shader compilation.
+        let pipelines = world.resource::<PostProcessPipelines>();
         let pipeline_cache = world.resource::<PipelineCache>();
+        let history = world
+            .resource::<PhosphorHistories>()
+            .0
+            .get(&graph.view_entity());
 
-        // Get the pipeline from the cache
-        let Some(pipeline) = pipeline_cache.get_render_pipeline(post_process_pipeline.pipeline_id)
-        else {
-            return Ok(());
-        };
+        for effect in &stack.0 {
+            let uniform = match effect {
+                PostEffect::Phosphor => uniform_binding(world, phosphor),
+                PostEffect::Curvature => uniform_binding(world, curvature),
+                PostEffect::Scanlines => uniform_binding(world, scanlines),
+                PostEffect::ChromaticAberration => uniform_binding(world, aberration),
+                PostEffect::Vignette => uniform_binding(world, vignette),
+            };
+            let Some((settings_binding, settings_index)) = uniform else {
+                continue;
+            };
+            let pass = pipelines.pass(*effect);
+            // Still compiling.
+            let Some(pipeline) = pipeline_cache.get_render_pipeline(pass.pipeline_id) else {
+                continue;
+            };
+            // The phosphor pass reads last frame's history and writes this frame's; a
+            // camera has none until the frame after it starts using the pass.
+            let history = match effect {
+                PostEffect::Phosphor => match history {
+                    Some(history) => Some(history.views()),
+                    None => continue,
+                },
+                _ => None,
+            };
 
-        // Get the settings uniform binding
-        let settings_uniforms = world.resource::<ComponentUniforms<PostProcessSettings>>();
-        let Some(settings_binding) = settings_uniforms.uniforms().binding() else {
-            return Ok(());
-        };
+            // This will start a new "post process write", obtaining two texture
+            // views from the view target - a `source` and a `destination`.
+            // `source` is the "current" main texture and you _must_ write into
+            // `destination` because calling `post_process_write()` on the
+            // [`ViewTarget`] will internally flip the [`ViewTarget`]'s main
+            // texture to the `destination` texture. That flip is what lets every pass
+            // read the output of the one before it.
+            let post_process = view_target.post_process_write();
 
-        // This will start a new "post process write", obtaining two texture
-        // views from the view target - a `source` and a `destination`.
-        // `source` is the "current" main texture and you _must_ write into
-        // `destination` because calling `post_process_write()` on the
-        // [`ViewTarget`] will internally flip the [`ViewTarget`]'s main
-        // texture to the `destination` texture. Failing to do so will cause
-        // the current main texture information to be lost.
-        let post_process = view_target.post_process_write();
-
-        // The bind_group gets created each frame.
-        //
-        // Normally, you would create a bind_group in the Queue set,
-        // but this doesn't work with the post_process_write().
-        // The reason it doesn't work is because each post_process_write will alternate the source/destination.
-        // The only way to have the correct source/destination for the bind_group
-        // is to make sure you get it during the node execution.
-        let bind_group = render_context.render_device().create_bind_group(
-            "post_process_bind_group",
-            &post_process_pipeline.layout,
-            // It's important for this to match the BindGroupLayout defined in the PostProcessPipeline
-            &BindGroupEntries::sequential((
-                // Make sure to use the source view
-                post_process.source,
-                // Use the sampler created for the pipeline
-                &post_process_pipeline.sampler,
-                // Set the settings binding
-                settings_binding.clone(),
-            )),
-        );
+            // The bind group has to be created during the node execution, since each
+            // post_process_write alternates the source and destination.
+            let bind_group = match history {
+                Some((previous, _)) => render_context.render_device().create_bind_group(
+                    "post_process_bind_group",
+                    &pass.layout,
+                    &BindGroupEntries::sequential((
+                        post_process.source,
+                        &pipelines.sampler,
+                        settings_binding,
+                        previous,
+                    )),
+                ),
+                None => render_context.render_device().create_bind_group(
+                    "post_process_bind_group",
+                    &pass.layout,
+                    &BindGroupEntries::sequential((
+                        post_process.source,
+                        &pipelines.sampler,
+                        settings_binding,
+                    )),
+                ),
+            };
 
-        // Begin the render pass
-        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
-            label: Some("post_process_pass"),
-            color_attachments: &[Some(RenderPassColorAttachment {
-                // We need to specify the post process destination view here
-                // to make sure we write to the appropriate texture.
-                view: post_process.destination,
-                resolve_target: None,
-                ops: Operations::default(),
-            })],
-            depth_stencil_attachment: None,
-            timestamp_writes: None,
-            occlusion_query_set: None,
-        });
-
-        // This is mostly just wgpu boilerplate for drawing a fullscreen triangle,
-        // using the pipeline/bind_group created above
-        render_pass.set_render_pipeline(pipeline);
-        // By passing in the index of the post process settings on this view, we ensure
-        // that in the event that multiple settings were sent to the GPU (as would be the
-        // case with multiple cameras), we use the correct one.
-        render_pass.set_bind_group(0, &bind_group, &[settings_index.index()]);
-        render_pass.draw(0..3, 0..1);
+            let color_attachment = |view| {
+                Some(RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: Operations::default(),
+                })
+            };
+            let mut color_attachments = vec![color_attachment(post_process.destination)];
+            if let Some((_, next)) = history {
+                color_attachments.push(color_attachment(next));
+            }
+
+            let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+                label: Some("post_process_pass"),
+                color_attachments: &color_attachments,
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            // This is mostly just wgpu boilerplate for drawing a fullscreen triangle
+            render_pass.set_render_pipeline(pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[settings_index]);
+            render_pass.draw(0..3, 0..1);
+        }
 
         Ok(())
     }
 }
 
-// This contains global data used by the render pipeline. This will be created once on startup.
-#[derive(Resource)]
-struct PostProcessPipeline {
+/// Uniform buffer of the `T` settings and the offset of this view's entry in it.
+fn uniform_binding<'w, T: Component + ShaderType + WriteInto>(
+    world: &'w World,
+    index: Option<&DynamicUniformIndex<T>>,
+) -> Option<(BindingResource<'w>, u32)> {
+    let index = index?.index();
+    let binding = world
+        .resource::<ComponentUniforms<T>>()
+        .uniforms()
+        .binding()?;
+    Some((binding, index))
+}
+
+/// Pipeline of one [`PostEffect`].
+struct EffectPass {
     layout: BindGroupLayout,
-    sampler: Sampler,
     pipeline_id: CachedRenderPipelineId,
 }
 
-impl FromWorld for PostProcessPipeline {
-    fn from_world(world: &mut World) -> Self {
+impl EffectPass {
+    fn new<T: ShaderType>(world: &mut World, effect: PostEffect) -> Self {
         let render_device = world.resource::<RenderDevice>();
 
-        // We need to define the bind group layout used for our pipeline
-        let layout = render_device.create_bind_group_layout(
-            "post_process_bind_group_layout",
-            &BindGroupLayoutEntries::sequential(
-                // The layout entries will only be visible in the fragment stage
-                ShaderStages::FRAGMENT,
-                (
-                    // The screen texture
-                    texture_2d(TextureSampleType::Float { filterable: true }),
-                    // The sampler that will be used to sample the screen texture
-                    sampler(SamplerBindingType::Filtering),
-                    // The settings uniform that will control the effect
-                    uniform_buffer::<PostProcessSettings>(true),
-                ),
-            ),
+        // The layout entries will only be visible in the fragment stage
+        let entries = (
+            // The screen texture
+            texture_2d(TextureSampleType::Float { filterable: true }),
+            // The sampler that will be used to sample the screen texture
+            sampler(SamplerBindingType::Filtering),
+            // The settings uniform that will control the effect
+            uniform_buffer::<T>(true),
         );
+        let layout = if effect == PostEffect::Phosphor {
+            let (screen, sampler, settings) = entries;
+            render_device.create_bind_group_layout(
+                "post_process_bind_group_layout",
+                &BindGroupLayoutEntries::sequential(
+                    ShaderStages::FRAGMENT,
+                    (
+                        screen,
+                        sampler,
+                        settings,
+                        // Last frame's history
+                        texture_2d(TextureSampleType::Float { filterable: true }),
+                    ),
+                ),
+            )
+        } else {
+            render_device.create_bind_group_layout(
+                "post_process_bind_group_layout",
+                &BindGroupLayoutEntries::sequential(ShaderStages::FRAGMENT, entries),
+            )
+        };
+        // The phosphor pass also writes the new history.
+        let targets = if effect == PostEffect::Phosphor { 2 } else { 1 };
 
-        // We can create the sampler here since it won't change at runtime and doesn't depend on the view
-        let sampler = render_device.create_sampler(&SamplerDescriptor::default());
-
-        // Get the shader handle
-        let shader = world.load_asset("shaders/retro_view.wgsl");
+        let shader = world.load_asset(effect.shader());
 
         let pipeline_id = world
             .resource_mut::<PipelineCache>()
@@ -261,17 +350,16 @@ impl FromWorld for PostProcessPipeline {
                 fragment: Some(FragmentState {
                     shader,
                     shader_defs: vec![],
-                    // Make sure this matches the entry point of your shader.
-                    // It can be anything as long as it matches here and in the shader.
                     entry_point: "fragment".into(),
-                    targets: vec![Some(ColorTargetState {
-                        format: TextureFormat::bevy_default(),
-                        blend: None,
-                        write_mask: ColorWrites::ALL,
-                    })],
+                    targets: vec![
+                        Some(ColorTargetState {
+                            format: TextureFormat::bevy_default(),
+                            blend: None,
+                            write_mask: ColorWrites::ALL,
+                        });
+                        targets
+                    ],
                 }),
-                // All of the following properties are not important for this effect so just use the default values.
-                // This struct doesn't have the Default trait implemented because not all field can have a default value.
                 primitive: PrimitiveState::default(),
                 depth_stencil: None,
                 multisample: MultisampleState::default(),
@@ -280,16 +368,283 @@ impl FromWorld for PostProcessPipeline {
 
         Self {
             layout,
-            sampler,
             pipeline_id,
         }
     }
 }
 
-// This is the component that will get passed to the shader
-#[derive(Component, Default, Clone, Copy, ExtractComponent, ShaderType)]
-pub struct PostProcessSettings {
-    pub intensity: f32,
+// This contains global data used by the render pipelines. This will be created once on startup.
+#[derive(Resource)]
+struct PostProcessPipelines {
+    sampler: Sampler,
+    /// In [`PostEffect::ALL`] order.
+    passes: Vec<EffectPass>,
+}
+
+impl PostProcessPipelines {
+    fn pass(&self, effect: PostEffect) -> &EffectPass {
+        let index = PostEffect::ALL.iter().position(|e| *e == effect).unwrap();
+        &self.passes[index]
+    }
+}
+
+impl FromWorld for PostProcessPipelines {
+    fn from_world(world: &mut World) -> Self {
+        // We can create the sampler here since it won't change at runtime and doesn't depend on the view
+        let sampler = world
+            .resource::<RenderDevice>()
+            .create_sampler(&SamplerDescriptor::default());
+
+        let passes = PostEffect::ALL
+            .into_iter()
+            .map(|effect| match effect {
+                PostEffect::Phosphor => EffectPass::new::<PhosphorSettings>(world, effect),
+                PostEffect::Curvature => EffectPass::new::<CurvatureSettings>(world, effect),
+                PostEffect::Scanlines => EffectPass::new::<ScanlineSettings>(world, effect),
+                PostEffect::ChromaticAberration => {
+                    EffectPass::new::<ChromaticAberrationSettings>(world, effect)
+                }
+                PostEffect::Vignette => EffectPass::new::<VignetteSettings>(world, effect),
+            })
+            .collect();
+
+        Self { sampler, passes }
+    }
+}
+
+/// Two screen-sized textures per camera with a phosphor pass: one holds what the
+/// screen showed last frame and the other receives this frame, and they swap roles
+/// every frame.
+struct PhosphorHistory {
+    size: Extent3d,
+    views: [TextureView; 2],
+    /// Index of the texture holding last frame.
+    previous: usize,
+}
+
+impl PhosphorHistory {
+    fn new(render_device: &RenderDevice, size: Extent3d) -> Self {
+        let view = |_| {
+            render_device
+                .create_texture(&TextureDescriptor {
+                    label: Some("phosphor_history_texture"),
+                    size,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: TextureFormat::bevy_default(),
+                    usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&TextureViewDescriptor::default())
+        };
+        Self {
+            size,
+            views: [0, 1].map(view),
+            previous: 0,
+        }
+    }
+
+    /// Last frame's history to read, and the texture to write this frame into.
+    fn views(&self) -> (&TextureView, &TextureView) {
+        (&self.views[self.previous], &self.views[1 - self.previous])
+    }
+}
+
+/// Render world entities are cleared every frame, so the histories are kept here,
+/// by view entity, instead of on the views.
+#[derive(Resource, Default)]
+struct PhosphorHistories(HashMap<Entity, PhosphorHistory>);
+
+/// Gives every view with [`PhosphorSettings`] a history the size of its screen,
+/// starting over black when that size changes, and swaps the textures of the ones
+/// that already had one.
+fn prepare_phosphor_histories(
+    mut histories: ResMut<PhosphorHistories>,
+    views: Query<(Entity, &ViewTarget), With<PhosphorSettings>>,
+    render_device: Res<RenderDevice>,
+) {
+    let mut live = HashMap::new();
+    for (entity, view_target) in &views {
+        let size = view_target.main_texture().size();
+        let history = match histories.0.remove(&entity) {
+            Some(mut history) if history.size == size => {
+                history.previous = 1 - history.previous;
+                history
+            }
+            _ => PhosphorHistory::new(&render_device, size),
+        };
+        live.insert(entity, history);
+    }
+    // Cameras that went away or dropped the pass free their textures.
+    histories.0 = live;
+}
+
+// Settings of each effect; these are the components that get passed to the shaders.
+// Every one has a `strength` that [`update_settings`] keeps at [`CrtStrength`].
+
+#[derive(Component, Clone, Copy, ExtractComponent, ShaderType)]
+pub struct PhosphorSettings {
+    /// Seconds for a trail to fade to half its brightness; 0 leaves no trail.
+    pub half_life: f32,
+    /// Share of last frame's brightness that carries over, worked out from
+    /// `half_life` and the frame time by [`update_settings`].
+    pub persistence: f32,
+    pub strength: f32,
+}
+
+impl PhosphorSettings {
+    fn persistence(&self, frame_seconds: f32) -> f32 {
+        if self.half_life <= 0.0 {
+            return 0.0;
+        }
+        0.5_f32.powf(frame_seconds / self.half_life)
+    }
+}
+
+#[derive(Component, Clone, Copy, ExtractComponent, ShaderType)]
+pub struct CurvatureSettings {
+    /// How much the screen bulges; 0 is flat.
+    pub curvature: f32,
+    /// Width of the fade to black at the screen edges, as a fraction of the screen.
+    pub edge_blur: f32,
+    pub strength: f32,
+    /// Size of the pixelation blocks, in physical pixels; 1 turns it off.
+    pub pixel_size: Vec2,
+    /// Size of the camera's viewport in physical pixels, kept up to date by
+    /// [`update_settings`].
+    pub resolution: Vec2,
+}
+
+#[derive(Component, Clone, Copy, ExtractComponent, ShaderType)]
+pub struct ScanlineSettings {
+    /// How much every other line is darkened, from 0 to 1.
+    pub scanlines: f32,
+    /// Brightness of the static grain.
+    pub noise: f32,
+    /// Depth of the brightness flicker, from 0 to 1.
+    pub flicker: f32,
+    /// Seconds, kept up to date by [`update_settings`].
+    pub time: f32,
+    pub strength: f32,
+}
+
+#[derive(Component, Clone, Copy, ExtractComponent, ShaderType)]
+pub struct ChromaticAberrationSettings {
+    /// How far the red and blue channels drift apart; 0 keeps them aligned.
+    pub amount: f32,
+    pub strength: f32,
+}
+
+#[derive(Component, Clone, Copy, ExtractComponent, ShaderType)]
+pub struct VignetteSettings {
+    /// Distance from the center where the darkening starts; 1 is a corner.
+    pub radius: f32,
+    /// Distance over which it fades in.
+    pub softness: f32,
+    /// How dark the corners get, from 0 to 1.
+    pub darkness: f32,
+    pub strength: f32,
+}
+
+/// A camera's stack and the settings of every effect in it.
+#[derive(Bundle, Clone)]
+pub struct PostProcessBundle {
+    pub stack: PostProcessStack,
+    pub phosphor: PhosphorSettings,
+    pub curvature: CurvatureSettings,
+    pub scanlines: ScanlineSettings,
+    pub chromatic_aberration: ChromaticAberrationSettings,
+    pub vignette: VignetteSettings,
+}
+
+impl Default for PostProcessBundle {
+    fn default() -> Self {
+        CrtPreset::default().bundle()
+    }
+}
+
+/// Named looks for the CRT effect.
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum CrtPreset {
+    Off,
+    Subtle,
+    #[default]
+    Arcade,
+    BrokenTv,
+}
+
+impl CrtPreset {
+    pub const ALL: [CrtPreset; 4] = [
+        CrtPreset::Off,
+        CrtPreset::Subtle,
+        CrtPreset::Arcade,
+        CrtPreset::BrokenTv,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CrtPreset::Off => "Off",
+            CrtPreset::Subtle => "Subtle",
+            CrtPreset::Arcade => "Arcade",
+            CrtPreset::BrokenTv => "Broken TV",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|preset| preset.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Settings of every effect for this look, in the default stack order.
+    pub fn bundle(&self) -> PostProcessBundle {
+        let half_life = match self {
+            CrtPreset::Off => 0.0,
+            CrtPreset::Subtle => 0.03,
+            CrtPreset::Arcade => 0.06,
+            CrtPreset::BrokenTv => 0.2,
+        };
+        let (curvature, edge_blur, pixel, scanlines, noise, flicker, aberration, vignette) =
+            match self {
+                CrtPreset::Off => (0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0),
+                CrtPreset::Subtle => (0.12, 0.01, 2.0, 0.1, 0.01, 0.0, 0.002, 0.3),
+                CrtPreset::Arcade => (0.24, 0.021, 4.0, 0.2, 0.02, 0.0, 0.004, 0.5),
+                CrtPreset::BrokenTv => (0.33, 0.06, 6.0, 0.45, 0.12, 0.15, 0.025, 1.0),
+            };
+        PostProcessBundle {
+            stack: PostProcessStack::default(),
+            phosphor: PhosphorSettings {
+                half_life,
+                persistence: 0.0,
+                strength: 1.0,
+            },
+            curvature: CurvatureSettings {
+                curvature,
+                edge_blur,
+                strength: 1.0,
+                pixel_size: Vec2::splat(pixel),
+                resolution: Vec2::ONE,
+            },
+            scanlines: ScanlineSettings {
+                scanlines,
+                noise,
+                flicker,
+                time: 0.0,
+                strength: 1.0,
+            },
+            chromatic_aberration: ChromaticAberrationSettings {
+                amount: aberration,
+                strength: 1.0,
+            },
+            vignette: VignetteSettings {
+                radius: 0.7,
+                softness: 0.6,
+                darkness: vignette,
+                strength: 1.0,
+            },
+        }
+    }
 }
 
 #[derive(Component)]
@@ -303,19 +658,73 @@ pub fn rotate(time: Res<Time>, mut query: Query<&mut Transform, With<Rotates>>)
     }
 }
 
-// Change the intensity over time to show that the effect is controlled from the main world
-pub fn update_settings(mut settings: Query<&mut PostProcessSettings>, time: Res<Time>) {
-    for mut setting in &mut settings {
-        let mut intensity = time.elapsed_seconds().sin();
-        // Make it loop periodically
-        intensity = intensity.sin();
-        // Remap it to 0..1 because the intensity can't be negative
-        intensity = intensity * 0.5 + 0.5;
-        // Scale it to a more reasonable level
-        intensity *= 0.015;
-
-        // Set the intensity.
-        // This will then be extracted to the render world and uploaded to the gpu automatically by the [`UniformComponentPlugin`]
-        setting.intensity = intensity;
+/// Scales the strength of the CRT effect; 0 turns it off.
+#[derive(Resource, Clone, Copy)]
+pub struct CrtStrength(pub f32);
+
+impl Default for CrtStrength {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+type EffectSettings<'a> = (
+    &'a Camera,
+    Option<&'a mut PhosphorSettings>,
+    Option<&'a mut CurvatureSettings>,
+    Option<&'a mut ScanlineSettings>,
+    Option<&'a mut ChromaticAberrationSettings>,
+    Option<&'a mut VignetteSettings>,
+);
+
+/// Switches every camera to a newly picked [`CrtPreset`], leaving its stack alone, and
+/// keeps the viewport size, time, trail persistence and strength current. Everything here is extracted to
+/// the render world and uploaded by the [`UniformComponentPlugin`].
+pub fn update_settings(
+    mut cameras: Query<EffectSettings>,
+    time: Res<Time>,
+    real_time: Res<Time<Real>>,
+    strength: Res<CrtStrength>,
+    preset: Res<CrtPreset>,
+) {
+    let look = preset.bundle();
+    let strength = strength.0;
+    for (camera, phosphor, curvature, scanlines, aberration, vignette) in &mut cameras {
+        if let Some(mut phosphor) = phosphor {
+            if preset.is_changed() {
+                *phosphor = look.phosphor;
+            }
+            // Trails keep fading while the game is paused.
+            phosphor.persistence = phosphor.persistence(real_time.delta_seconds());
+            phosphor.strength = strength;
+        }
+        if let Some(mut curvature) = curvature {
+            if preset.is_changed() {
+                *curvature = look.curvature;
+            }
+            if let Some(size) = camera.physical_viewport_size() {
+                curvature.resolution = size.as_vec2();
+            }
+            curvature.strength = strength;
+        }
+        if let Some(mut scanlines) = scanlines {
+            if preset.is_changed() {
+                *scanlines = look.scanlines;
+            }
+            scanlines.time = time.elapsed_seconds_wrapped();
+            scanlines.strength = strength;
+        }
+        if let Some(mut aberration) = aberration {
+            if preset.is_changed() {
+                *aberration = look.chromatic_aberration;
+            }
+            aberration.strength = strength;
+        }
+        if let Some(mut vignette) = vignette {
+            if preset.is_changed() {
+                *vignette = look.vignette;
+            }
+            vignette.strength = strength;
+        }
     }
 }
@@ -0,0 +1,369 @@
+//! Power-ups floating in the arena for rockets to pick up.
+//!
+//! Every few seconds one of the kinds the [`Rules`] allow turns up at a random spot
+//! well clear of the gravity wells, the rockets and the other power-ups, as long as
+//! there aren't too many around already. Flying through one picks it up: fuel and
+//! hyperspace charges are handed over right away, the others last a while.
+
+use bevy::{prelude::*, sprite::Mesh2dHandle};
+use std::time::Duration;
+
+use crate::arena::{GravityWell, Warped};
+use crate::hull::ticks_after;
+use crate::input::ShipInput;
+use crate::rocket::{Player, Rocket};
+use crate::vector::VectorShape;
+use crate::{AppState, ArenaBounds, Rules, SimClock, SimRng, TICK_RATE};
+
+/// Ticks between two power-ups turning up.
+const SPAWN_INTERVAL: u32 = 8 * TICK_RATE as u32;
+/// No new power-ups while this many are waiting to be picked up.
+const MAX_WAITING: usize = 3;
+/// Power-ups don't turn up closer than this to a rocket, another power-up or the
+/// kill radius of a well. Hyperspace jumps keep the same distance from the wells.
+const CLEARANCE: f32 = 150.0;
+/// Tries at finding a clear spot before giving up until the next interval.
+const PLACEMENT_TRIES: usize = 20;
+/// Kept free at the edges, so nothing turns up half off the screen.
+const EDGE_MARGIN: f32 = 40.0;
+/// How close the middle of a rocket gets to a power-up to pick it up.
+const PICKUP_RADIUS: f32 = 30.0;
+const POWER_UP_RADIUS: f32 = 12.0;
+const POWER_UP_Z: f32 = 2.0;
+/// Rapid fire divides the reload time by this.
+const RAPID_FIRE_FACTOR: u32 = 3;
+/// Angle between the torpedoes of a spread shot, in radians.
+pub const SPREAD_ANGLE: f32 = 0.2;
+
+/// Something a rocket can pick up. Also the component of a power-up waiting in the
+/// arena.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum PowerUp {
+    /// Reloads the torpedo tube much faster for a while.
+    RapidFire,
+    /// Torpedoes, asteroids and ramming can't hurt the rocket for a while. The wells
+    /// still can.
+    Shield,
+    /// One more jump to a random spot of the arena.
+    Hyperspace,
+    /// Tops the fuel up to what the rocket started with.
+    Fuel,
+    /// Three torpedoes per shot, fanned out, for a while.
+    SpreadShot,
+}
+
+impl PowerUp {
+    pub const ALL: [PowerUp; 5] = [
+        PowerUp::RapidFire,
+        PowerUp::Shield,
+        PowerUp::Hyperspace,
+        PowerUp::Fuel,
+        PowerUp::SpreadShot,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PowerUp::RapidFire => "rapid-fire",
+            PowerUp::Shield => "shield",
+            PowerUp::Hyperspace => "hyperspace",
+            PowerUp::Fuel => "fuel",
+            PowerUp::SpreadShot => "spread-shot",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(name))
+    }
+
+    /// How long it lasts once picked up; zero for the ones used up right away.
+    pub fn duration(&self) -> Duration {
+        match self {
+            PowerUp::RapidFire | PowerUp::SpreadShot => Duration::from_secs(10),
+            PowerUp::Shield => Duration::from_secs(8),
+            PowerUp::Hyperspace | PowerUp::Fuel => Duration::ZERO,
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            PowerUp::RapidFire => Color::srgb(1.0, 0.45, 0.2),
+            PowerUp::Shield => Color::srgb(0.3, 0.8, 1.0),
+            PowerUp::Hyperspace => Color::srgb(0.8, 0.4, 1.0),
+            PowerUp::Fuel => Color::srgb(0.4, 1.0, 0.4),
+            PowerUp::SpreadShot => Color::srgb(1.0, 0.9, 0.3),
+        }
+    }
+
+    fn bit(&self) -> u8 {
+        1 << *self as u8
+    }
+
+    /// Everything the simulation needs of a power-up waiting at `position`.
+    pub fn bundle(self, position: Vec2) -> (PowerUp, SpatialBundle, StateScoped<AppState>) {
+        (
+            self,
+            SpatialBundle::from_transform(Transform::from_translation(position.extend(POWER_UP_Z))),
+            StateScoped(AppState::InGame),
+        )
+    }
+
+    /// Gives `rocket` what this power-up holds, at match tick `tick`.
+    pub fn apply(self, rocket: &mut Rocket, tick: u32, rules: &Rules) {
+        let until = ticks_after(tick, self.duration());
+        match self {
+            PowerUp::RapidFire => rocket.boosts.rapid_fire = until,
+            PowerUp::Shield => rocket.boosts.shield = until,
+            PowerUp::SpreadShot => rocket.boosts.spread_shot = until,
+            PowerUp::Hyperspace => rocket.hyperspace += 1,
+            PowerUp::Fuel => rocket.fuel = rocket.fuel.max(rules.fuel),
+        }
+    }
+}
+
+/// Which power-ups turn up in a match.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct PowerUps(u8);
+
+impl PowerUps {
+    pub const NONE: PowerUps = PowerUps(0);
+    pub const ALL: PowerUps = PowerUps((1 << PowerUp::ALL.len()) - 1);
+
+    pub fn contains(&self, kind: PowerUp) -> bool {
+        self.0 & kind.bit() != 0
+    }
+
+    pub fn with(self, kind: PowerUp) -> Self {
+        PowerUps(self.0 | kind.bit())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = PowerUp> + '_ {
+        PowerUp::ALL.into_iter().filter(|kind| self.contains(*kind))
+    }
+
+    /// Reads `all`, `none` or a comma-separated list of power-up names.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text.trim() {
+            "all" => Ok(Self::ALL),
+            "none" => Ok(Self::NONE),
+            list => list.split(',').try_fold(Self::NONE, |set, name| {
+                PowerUp::from_name(name.trim())
+                    .map(|kind| set.with(kind))
+                    .ok_or_else(|| format!("unknown power-up {}", name.trim()))
+            }),
+        }
+    }
+}
+
+/// Ticks of the match clock the timed power-ups of a rocket run out at. Zero, or any
+/// tick in the past, means the rocket doesn't have it.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Boosts {
+    pub rapid_fire: u32,
+    pub shield: u32,
+    pub spread_shot: u32,
+}
+
+impl Boosts {
+    /// Whether the timed power-up `kind` still lasts at `tick`.
+    pub fn active(&self, kind: PowerUp, tick: u32) -> bool {
+        self.remaining(kind, tick).is_some()
+    }
+
+    /// How long the timed power-up `kind` still lasts at `tick`, if at all.
+    pub fn remaining(&self, kind: PowerUp, tick: u32) -> Option<Duration> {
+        let until = match kind {
+            PowerUp::RapidFire => self.rapid_fire,
+            PowerUp::Shield => self.shield,
+            PowerUp::SpreadShot => self.spread_shot,
+            PowerUp::Hyperspace | PowerUp::Fuel => return None,
+        };
+        (until > tick).then(|| Duration::from_secs_f64((until - tick) as f64 / TICK_RATE))
+    }
+
+    /// Time between two shots for a tube that normally takes `cooldown` to reload.
+    pub fn reload_time(&self, cooldown: Duration, tick: u32) -> Duration {
+        if self.active(PowerUp::RapidFire, tick) {
+            cooldown / RAPID_FIRE_FACTOR
+        } else {
+            cooldown
+        }
+    }
+}
+
+/// A random spot of the arena at least [`CLEARANCE`] away from the kill radius of
+/// every well and from every point of `others`, if one turns up within a few tries.
+fn clear_spot(
+    rng: &mut SimRng,
+    bounds: &ArenaBounds,
+    wells: &[GravityWell],
+    others: &[Vec2],
+) -> Option<Vec2> {
+    let half = bounds.half_size - Vec2::splat(EDGE_MARGIN);
+    (0..PLACEMENT_TRIES)
+        .map(|_| Vec2::new(rng.range(-half.x, half.x), rng.range(-half.y, half.y)))
+        .find(|spot| {
+            wells
+                .iter()
+                .all(|well| spot.distance(well.position) > CLEARANCE + well.kill_radius)
+                && others.iter().all(|other| spot.distance(*other) > CLEARANCE)
+        })
+}
+
+/// What new power-ups keep their distance from.
+type Occupants = Or<(With<Rocket>, With<PowerUp>)>;
+
+/// Lets a new power-up of a kind the rules allow turn up every [`SPAWN_INTERVAL`].
+pub fn spawn_power_ups(
+    mut commands: Commands,
+    clock: Res<SimClock>,
+    rules: Res<Rules>,
+    bounds: Res<ArenaBounds>,
+    mut rng: ResMut<SimRng>,
+    wells: Query<&GravityWell>,
+    occupied: Query<(&Transform, Has<PowerUp>), Occupants>,
+) {
+    let kinds: Vec<PowerUp> = rules.power_ups.iter().collect();
+    if kinds.is_empty() || clock.tick == 0 || !clock.tick.is_multiple_of(SPAWN_INTERVAL) {
+        return;
+    }
+    let waiting = occupied.iter().filter(|(_, power_up)| *power_up).count();
+    if waiting >= MAX_WAITING {
+        return;
+    }
+    let wells: Vec<GravityWell> = wells.iter().copied().collect();
+    let others: Vec<Vec2> = occupied
+        .iter()
+        .map(|(transform, _)| transform.translation.truncate())
+        .collect();
+    let kind = kinds[(rng.next_u64() % kinds.len() as u64) as usize];
+    if let Some(position) = clear_spot(&mut rng, &bounds, &wells, &others) {
+        commands.spawn(kind.bundle(position));
+    }
+}
+
+/// Hands power-ups to the rockets flying through them. When both get there in the
+/// same tick, the first player has it.
+pub fn collect_power_ups(
+    mut commands: Commands,
+    clock: Res<SimClock>,
+    rules: Res<Rules>,
+    power_ups: Query<(Entity, &PowerUp, &Transform), Without<Rocket>>,
+    mut rockets: Query<(&Player, &mut Rocket, &Transform)>,
+) {
+    let mut rockets: Vec<_> = rockets.iter_mut().collect();
+    rockets.sort_by_key(|(player, ..)| player.0);
+    for (entity, kind, transform) in &power_ups {
+        let position = transform.translation.truncate();
+        let Some((_, rocket, _)) = rockets.iter_mut().find(|(_, _, rocket_transform)| {
+            rocket_transform.translation.truncate().distance(position) < PICKUP_RADIUS
+        }) else {
+            continue;
+        };
+        kind.apply(rocket, clock.tick, &rules);
+        commands.entity(entity).despawn();
+    }
+}
+
+/// Jumps rockets whose pilot pressed hyperspace to a random spot clear of the wells,
+/// using up one charge. The rocket keeps its heading and speed.
+pub fn hyperspace_jump(
+    bounds: Res<ArenaBounds>,
+    mut rng: ResMut<SimRng>,
+    mut warped: EventWriter<Warped>,
+    wells: Query<&GravityWell>,
+    mut rockets: Query<(&Player, &ShipInput, &mut Rocket, &mut Transform)>,
+) {
+    let wells: Vec<GravityWell> = wells.iter().copied().collect();
+    let mut rockets: Vec<_> = rockets.iter_mut().collect();
+    // Both rockets draw from the same random numbers, so keep the order fixed.
+    rockets.sort_by_key(|(player, ..)| player.0);
+    for (_, input, rocket, transform) in rockets.iter_mut() {
+        if !input.pressed(ShipInput::HYPERSPACE) || rocket.hyperspace == 0 {
+            continue;
+        }
+        let Some(spot) = clear_spot(&mut rng, &bounds, &wells, &[]) else {
+            continue;
+        };
+        rocket.hyperspace -= 1;
+        transform.translation = spot.extend(transform.translation.z);
+        warped.send(Warped {
+            position: transform.translation,
+        });
+    }
+}
+
+/// Mesh every power-up shares and a material for each kind, in [`PowerUp::ALL`] order.
+#[derive(Resource)]
+pub struct PowerUpLook {
+    pub mesh: Handle<Mesh>,
+    pub materials: Vec<Handle<ColorMaterial>>,
+}
+
+impl FromWorld for PowerUpLook {
+    fn from_world(world: &mut World) -> Self {
+        let size = POWER_UP_RADIUS * 2.0;
+        let mesh = world
+            .resource_mut::<Assets<Mesh>>()
+            .add(Rhombus::new(size, size));
+        let mut materials = world.resource_mut::<Assets<ColorMaterial>>();
+        Self {
+            mesh,
+            materials: PowerUp::ALL
+                .iter()
+                .map(|kind| materials.add(ColorMaterial::from(kind.color())))
+                .collect(),
+        }
+    }
+}
+
+/// Draws power-ups, which the simulation spawns without any looks, and the shields
+/// of the rockets that picked one up.
+pub struct PowerUpPlugin;
+
+impl Plugin for PowerUpPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PowerUpLook>()
+            .add_systems(Update, (dress_power_ups, draw_shields));
+    }
+}
+
+fn dress_power_ups(
+    mut commands: Commands,
+    look: Res<PowerUpLook>,
+    power_ups: Query<(Entity, &PowerUp), Added<PowerUp>>,
+) {
+    for (entity, kind) in &power_ups {
+        commands.entity(entity).insert((
+            Mesh2dHandle(look.mesh.clone()),
+            look.materials[*kind as usize].clone(),
+            VectorShape::PowerUp {
+                kind: *kind,
+                radius: POWER_UP_RADIUS,
+            },
+        ));
+    }
+}
+
+fn draw_shields(
+    mut gizmos: Gizmos,
+    clock: Res<SimClock>,
+    time: Res<Time>,
+    rockets: Query<(&Rocket, &Transform)>,
+) {
+    for (rocket, transform) in &rockets {
+        let Some(remaining) = rocket.boosts.remaining(PowerUp::Shield, clock.tick) else {
+            continue;
+        };
+        // Flickers during the last two seconds.
+        if remaining < Duration::from_secs(2) && (time.elapsed_seconds() * 8.0).fract() < 0.5 {
+            continue;
+        }
+        gizmos.circle_2d(
+            transform.translation.truncate(),
+            rocket.radius_collision * 0.6,
+            PowerUp::Shield.color(),
+        );
+    }
+}
@@ -0,0 +1,489 @@
+//! Messages exchanged between the dedicated server and its clients over UDP.
+//!
+//! Every datagram holds exactly one message: a kind byte followed by the fields in
+//! little-endian order. Anything that fails to decode is dropped.
+
+use bevy::prelude::*;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+use crate::arena::ArenaLayout;
+use crate::input::ShipInput;
+use crate::powerup::{Boosts, PowerUp};
+use crate::respawn::Respawns;
+use crate::rocket::{Loadout, Player, ShipClass};
+use crate::Rules;
+
+pub const DEFAULT_PORT: u16 = 7777;
+/// Ports lobby browsers listen on for [`ServerMessage::Announce`]. Each browser takes
+/// the first free one, so several can run on the same machine.
+pub const DISCOVERY_PORTS: RangeInclusive<u16> = 7780..=7789;
+/// Largest datagram either side will send or accept.
+pub const MAX_PACKET: usize = 1400;
+/// Inputs repeated in every client packet so a few lost ones don't matter.
+pub const INPUT_REDUNDANCY: usize = 4;
+/// Bullets beyond this are left out of a snapshot to keep it within one datagram.
+const MAX_SNAPSHOT_BULLETS: usize = 60;
+/// Same for asteroids. They go first, and bullets only get the room left after them.
+const MAX_SNAPSHOT_ASTEROIDS: usize = 32;
+/// The simulation keeps only a few power-ups around; this is just a safeguard.
+const MAX_SNAPSHOT_POWER_UPS: usize = 8;
+/// Encoded size of one [`BulletState`].
+const BULLET_BYTES: usize = 28;
+
+/// Fingerprint of the arena and rules of a match. Clients predict with their own, so
+/// they only play on a server that has the same.
+pub fn match_setup(layout: &ArenaLayout, rules: &Rules) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    // The debug output spells out every field, down to the last bit of each float. The
+    // name is left out, so the built-in layout and its arena file count as the same.
+    let ArenaLayout {
+        wells,
+        wormholes,
+        asteroids,
+        ..
+    } = layout;
+    format!("{wells:?} {wormholes:?} {asteroids:?} {rules:?}").hash(&mut hasher);
+    hasher.finish()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RocketState {
+    pub player: Player,
+    pub translation: Vec2,
+    pub angle: f32,
+    pub velocity: Vec2,
+    pub speed: f32,
+    pub rotation_speed: f32,
+    pub ammo: u32,
+    pub fuel: f32,
+    pub last_shot_time: Duration,
+    pub hyperspace: u32,
+    pub boosts: Boosts,
+    pub hull: f32,
+    pub shield: f32,
+    pub protected_until: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BulletState {
+    /// Server-side entity bits, only used to match bullets between snapshots.
+    pub id: u64,
+    pub translation: Vec2,
+    pub angle: f32,
+    pub velocity: Vec2,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AsteroidState {
+    /// Server-side entity bits, only used to match asteroids between snapshots.
+    pub id: u64,
+    pub translation: Vec2,
+    /// Sent rounded to a whole number.
+    pub radius: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PowerUpState {
+    /// Server-side entity bits, only used to match power-ups between snapshots.
+    pub id: u64,
+    pub kind: PowerUp,
+    pub translation: Vec2,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Snapshot {
+    pub tick: u32,
+    /// Last input sequence number of the receiving client that went into this tick.
+    pub ack: u32,
+    pub scores: [u32; 2],
+    pub respawns: Respawns,
+    pub rockets: Vec<RocketState>,
+    pub power_ups: Vec<PowerUpState>,
+    pub asteroids: Vec<AsteroidState>,
+    pub bullets: Vec<BulletState>,
+}
+
+/// One seat in the lobby as the server sees it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LobbyPlayer {
+    pub player: Player,
+    pub loadout: Loadout,
+    pub ready: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ClientMessage {
+    Join,
+    /// Inputs for sequence numbers `seq + 1 - inputs.len() ..= seq`.
+    Input {
+        seq: u32,
+        inputs: Vec<ShipInput>,
+    },
+    Leave,
+    SetLoadout(Loadout),
+    Ready(bool),
+    /// Sent by the host (player 0) once everyone is ready.
+    Start,
+    /// Asks to watch without taking a seat. Repeated as a keep-alive.
+    Spectate,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ServerMessage {
+    Welcome {
+        player: Player,
+        /// The server's [`match_setup`], which the client has to share to play.
+        setup: u64,
+    },
+    /// Both player slots are taken.
+    Full,
+    Snapshot(Snapshot),
+    Lobby(Vec<LobbyPlayer>),
+    MatchStart([Loadout; 2]),
+    /// Broadcast on the local network so lobby browsers can list the server.
+    Announce {
+        /// Picked at random when the server starts, so a browser that hears it from
+        /// several addresses still lists it once.
+        id: u64,
+        name: String,
+        players: u8,
+    },
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, v: u8) -> &mut Self {
+        self.0.push(v);
+        self
+    }
+
+    fn u32(&mut self, v: u32) -> &mut Self {
+        self.0.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    fn u64(&mut self, v: u64) -> &mut Self {
+        self.0.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    fn f32(&mut self, v: f32) -> &mut Self {
+        self.0.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    fn vec2(&mut self, v: Vec2) -> &mut Self {
+        self.f32(v.x).f32(v.y)
+    }
+
+    fn loadout(&mut self, loadout: &Loadout) -> &mut Self {
+        let class = ShipClass::ALL
+            .iter()
+            .position(|class| *class == loadout.class)
+            .unwrap_or_default();
+        self.u8(class as u8).u8(loadout.color as u8)
+    }
+
+    fn str(&mut self, s: &str) -> &mut Self {
+        let bytes = &s.as_bytes()[..s.len().min(u8::MAX as usize)];
+        self.u8(bytes.len() as u8);
+        self.0.extend_from_slice(bytes);
+        self
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (head, tail) = self.0.split_first_chunk::<N>()?;
+        self.0 = tail;
+        Some(*head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take::<1>().map(|b| b[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.take().map(u64::from_le_bytes)
+    }
+
+    fn f32(&mut self) -> Option<f32> {
+        self.take().map(f32::from_le_bytes)
+    }
+
+    fn vec2(&mut self) -> Option<Vec2> {
+        Some(Vec2::new(self.f32()?, self.f32()?))
+    }
+
+    /// Only the two seats of a match decode as a player.
+    fn player(&mut self) -> Option<Player> {
+        Some(self.u8()? as usize)
+            .filter(|index| *index < 2)
+            .map(Player)
+    }
+
+    fn loadout(&mut self) -> Option<Loadout> {
+        Some(Loadout {
+            class: *ShipClass::ALL.get(self.u8()? as usize)?,
+            color: self.u8()? as usize,
+        })
+    }
+
+    fn str(&mut self) -> Option<String> {
+        let len = self.u8()? as usize;
+        let bytes = self.0.get(..len)?;
+        self.0 = &self.0[len..];
+        Some(String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+impl ClientMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut w = Writer(Vec::new());
+        match self {
+            ClientMessage::Join => {
+                w.u8(0);
+            }
+            ClientMessage::Input { seq, inputs } => {
+                w.u8(1).u32(*seq).u8(inputs.len() as u8);
+                for input in inputs {
+                    w.u8(input.0);
+                }
+            }
+            ClientMessage::Leave => {
+                w.u8(2);
+            }
+            ClientMessage::SetLoadout(loadout) => {
+                w.u8(3).loadout(loadout);
+            }
+            ClientMessage::Ready(ready) => {
+                w.u8(4).u8(*ready as u8);
+            }
+            ClientMessage::Start => {
+                w.u8(5);
+            }
+            ClientMessage::Spectate => {
+                w.u8(6);
+            }
+        }
+        w.0
+    }
+
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut r = Reader(bytes);
+        match r.u8()? {
+            0 => Some(ClientMessage::Join),
+            1 => {
+                let seq = r.u32()?;
+                let count = r.u8()?;
+                let inputs = (0..count)
+                    .map(|_| r.u8().map(ShipInput))
+                    .collect::<Option<_>>()?;
+                Some(ClientMessage::Input { seq, inputs })
+            }
+            2 => Some(ClientMessage::Leave),
+            3 => Some(ClientMessage::SetLoadout(r.loadout()?)),
+            4 => Some(ClientMessage::Ready(r.u8()? != 0)),
+            5 => Some(ClientMessage::Start),
+            6 => Some(ClientMessage::Spectate),
+            _ => None,
+        }
+    }
+}
+
+impl ServerMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut w = Writer(Vec::new());
+        match self {
+            ServerMessage::Welcome { player, setup } => {
+                w.u8(10).u8(player.0 as u8).u64(*setup);
+            }
+            ServerMessage::Full => {
+                w.u8(11);
+            }
+            ServerMessage::Snapshot(snapshot) => {
+                w.u8(12).u32(snapshot.tick).u32(snapshot.ack);
+                w.u32(snapshot.scores[0]).u32(snapshot.scores[1]);
+                // Unlimited lives go out as u8::MAX, and no respawn due as tick 0.
+                for player in 0..2 {
+                    let lives = snapshot.respawns.lives[player];
+                    w.u8(lives.map_or(u8::MAX, |lives| lives.min(u8::MAX as u32 - 1) as u8))
+                        .u32(snapshot.respawns.due[player].unwrap_or(0));
+                }
+                w.u8(snapshot.rockets.len() as u8);
+                for rocket in &snapshot.rockets {
+                    w.u8(rocket.player.0 as u8)
+                        .vec2(rocket.translation)
+                        .f32(rocket.angle)
+                        .vec2(rocket.velocity)
+                        .f32(rocket.speed)
+                        .f32(rocket.rotation_speed)
+                        .u8(rocket.ammo.min(u8::MAX as u32) as u8)
+                        .f32(rocket.fuel)
+                        .u32(rocket.last_shot_time.as_millis() as u32)
+                        .u8(rocket.hyperspace.min(u8::MAX as u32) as u8)
+                        .u32(rocket.boosts.rapid_fire)
+                        .u32(rocket.boosts.shield)
+                        .u32(rocket.boosts.spread_shot)
+                        .f32(rocket.hull)
+                        .f32(rocket.shield)
+                        .u32(rocket.protected_until);
+                }
+                let power_ups =
+                    &snapshot.power_ups[..snapshot.power_ups.len().min(MAX_SNAPSHOT_POWER_UPS)];
+                w.u8(power_ups.len() as u8);
+                for power_up in power_ups {
+                    w.u64(power_up.id)
+                        .u8(power_up.kind as u8)
+                        .vec2(power_up.translation);
+                }
+                let asteroids =
+                    &snapshot.asteroids[..snapshot.asteroids.len().min(MAX_SNAPSHOT_ASTEROIDS)];
+                w.u8(asteroids.len() as u8);
+                for asteroid in asteroids {
+                    w.u64(asteroid.id)
+                        .vec2(asteroid.translation)
+                        .u8(asteroid.radius.round().clamp(1.0, u8::MAX as f32) as u8);
+                }
+                let room = MAX_PACKET.saturating_sub(w.0.len() + 1) / BULLET_BYTES;
+                let count = snapshot.bullets.len().min(MAX_SNAPSHOT_BULLETS).min(room);
+                let bullets = &snapshot.bullets[..count];
+                w.u8(bullets.len() as u8);
+                for bullet in bullets {
+                    w.u64(bullet.id)
+                        .vec2(bullet.translation)
+                        .f32(bullet.angle)
+                        .vec2(bullet.velocity);
+                }
+            }
+            ServerMessage::Lobby(players) => {
+                w.u8(13).u8(players.len() as u8);
+                for seat in players {
+                    w.u8(seat.player.0 as u8)
+                        .loadout(&seat.loadout)
+                        .u8(seat.ready as u8);
+                }
+            }
+            ServerMessage::MatchStart(loadouts) => {
+                w.u8(14).loadout(&loadouts[0]).loadout(&loadouts[1]);
+            }
+            ServerMessage::Announce { id, name, players } => {
+                w.u8(15).u64(*id).str(name).u8(*players);
+            }
+        }
+        w.0
+    }
+
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut r = Reader(bytes);
+        match r.u8()? {
+            10 => Some(ServerMessage::Welcome {
+                player: r.player()?,
+                setup: r.u64()?,
+            }),
+            11 => Some(ServerMessage::Full),
+            12 => {
+                let tick = r.u32()?;
+                let ack = r.u32()?;
+                let scores = [r.u32()?, r.u32()?];
+                let mut respawns = Respawns::default();
+                for player in 0..2 {
+                    respawns.lives[player] =
+                        Some(r.u8()? as u32).filter(|lives| *lives != u8::MAX as u32);
+                    respawns.due[player] = Some(r.u32()?).filter(|due| *due != 0);
+                }
+                let rockets = (0..r.u8()?)
+                    .map(|_| {
+                        Some(RocketState {
+                            player: r.player()?,
+                            translation: r.vec2()?,
+                            angle: r.f32()?,
+                            velocity: r.vec2()?,
+                            speed: r.f32()?,
+                            rotation_speed: r.f32()?,
+                            ammo: r.u8()? as u32,
+                            fuel: r.f32()?,
+                            last_shot_time: Duration::from_millis(r.u32()? as u64),
+                            hyperspace: r.u8()? as u32,
+                            boosts: Boosts {
+                                rapid_fire: r.u32()?,
+                                shield: r.u32()?,
+                                spread_shot: r.u32()?,
+                            },
+                            hull: r.f32()?,
+                            shield: r.f32()?,
+                            protected_until: r.u32()?,
+                        })
+                    })
+                    .collect::<Option<_>>()?;
+                let power_ups = (0..r.u8()?)
+                    .map(|_| {
+                        Some(PowerUpState {
+                            id: r.u64()?,
+                            kind: *PowerUp::ALL.get(r.u8()? as usize)?,
+                            translation: r.vec2()?,
+                        })
+                    })
+                    .collect::<Option<_>>()?;
+                let asteroids = (0..r.u8()?)
+                    .map(|_| {
+                        Some(AsteroidState {
+                            id: r.u64()?,
+                            translation: r.vec2()?,
+                            radius: r.u8()? as f32,
+                        })
+                    })
+                    .collect::<Option<_>>()?;
+                let bullets = (0..r.u8()?)
+                    .map(|_| {
+                        Some(BulletState {
+                            id: r.u64()?,
+                            translation: r.vec2()?,
+                            angle: r.f32()?,
+                            velocity: r.vec2()?,
+                        })
+                    })
+                    .collect::<Option<_>>()?;
+                Some(ServerMessage::Snapshot(Snapshot {
+                    tick,
+                    ack,
+                    scores,
+                    respawns,
+                    rockets,
+                    power_ups,
+                    asteroids,
+                    bullets,
+                }))
+            }
+            13 => {
+                let players = (0..r.u8()?)
+                    .map(|_| {
+                        Some(LobbyPlayer {
+                            player: r.player()?,
+                            loadout: r.loadout()?,
+                            ready: r.u8()? != 0,
+                        })
+                    })
+                    .collect::<Option<_>>()?;
+                Some(ServerMessage::Lobby(players))
+            }
+            14 => Some(ServerMessage::MatchStart([r.loadout()?, r.loadout()?])),
+            15 => Some(ServerMessage::Announce {
+                id: r.u64()?,
+                name: r.str()?,
+                players: r.u8()?,
+            }),
+            _ => None,
+        }
+    }
+}
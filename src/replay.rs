@@ -0,0 +1,359 @@
+//! Instant replay of the last few seconds before a kill.
+//!
+//! Every frame the positions of rockets and torpedoes are pushed into a ring buffer.
+//! When a rocket is destroyed the match keeps running for a moment so the explosion
+//! can be seen live, then the simulation is frozen and the buffered frames are played
+//! back in slow motion with stand-in sprites, ending with the explosion again.
+//! Orbiting bodies are moved back along their orbits to match, and asteroids are
+//! redrawn where they were.
+
+use bevy::{prelude::*, sprite::Mesh2dHandle};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::arena::{ArenaLayout, WellIndex};
+use crate::asteroid::{Asteroid, RockLook};
+use crate::bullet::Bullet;
+use crate::client::ServerConnection;
+use crate::netcode::RollbackSession;
+use crate::rocket::{Player, Rocket, RocketDestroyed};
+use crate::vector::VectorShape;
+use crate::{Explosion, SimClock};
+
+/// How much history is kept, and therefore how long a replay lasts.
+const REPLAY_WINDOW: Duration = Duration::from_secs(4);
+/// Time the live explosion gets before the replay kicks in.
+const REPLAY_DELAY: f32 = 1.2;
+/// Playback speed of the replay, also applied to the particle effects.
+const REPLAY_SPEED: f32 = 0.3;
+/// How long the replay keeps showing the explosion after the kill frame.
+const REPLAY_TAIL: Duration = Duration::from_millis(1500);
+
+#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ReplayState {
+    #[default]
+    Live,
+    Replaying,
+}
+
+#[derive(Clone)]
+struct RocketFrame {
+    player: Player,
+    transform: Transform,
+    texture: Handle<Image>,
+    shape: Option<VectorShape>,
+}
+
+/// Everything needed to redraw one frame of the match.
+#[derive(Clone, Default)]
+struct Snapshot {
+    /// Match clock, which says where the orbiting bodies were.
+    tick: u32,
+    rockets: Vec<RocketFrame>,
+    bullets: Vec<(Transform, Handle<Image>, Option<VectorShape>)>,
+    asteroids: Vec<Transform>,
+}
+
+#[derive(Resource, Default)]
+pub struct ReplayBuffer {
+    frames: VecDeque<(Duration, Snapshot)>,
+}
+
+impl ReplayBuffer {
+    fn push(&mut self, time: Duration, snapshot: Snapshot) {
+        self.frames.push_back((time, snapshot));
+        while let Some((oldest, _)) = self.frames.front() {
+            if time.saturating_sub(*oldest) <= REPLAY_WINDOW + REPLAY_TAIL {
+                break;
+            }
+            self.frames.pop_front();
+        }
+    }
+
+    /// Latest recorded frame at or before `time`.
+    fn frame_at(&self, time: Duration) -> Option<&Snapshot> {
+        let index = self.frames.partition_point(|(t, _)| *t <= time);
+        self.frames
+            .get(index.saturating_sub(1))
+            .map(|(_, snapshot)| snapshot)
+    }
+}
+
+/// Drops the recorded frames and any replay still waiting to start, so nothing from
+/// before a restart is played back over the new round.
+pub fn forget_replays(world: &mut World) {
+    world.remove_resource::<PendingReplay>();
+    if let Some(mut buffer) = world.get_resource_mut::<ReplayBuffer>() {
+        buffer.frames.clear();
+    }
+}
+
+/// A kill that is waiting for its replay to start.
+#[derive(Resource)]
+struct PendingReplay {
+    kill_time: Duration,
+    victim: Player,
+    position: Vec3,
+    delay: Timer,
+}
+
+/// The replay currently being shown.
+#[derive(Resource)]
+struct ActiveReplay {
+    cursor: Duration,
+    kill_time: Duration,
+    victim: Player,
+    position: Vec3,
+    exploded: bool,
+}
+
+/// Sprites of the running match, hidden while the replay is shown.
+type LiveSprites = Or<(With<Rocket>, With<Bullet>, With<Asteroid>)>;
+
+type RocketSprite<'a> = (
+    &'a Player,
+    &'a Transform,
+    &'a Handle<Image>,
+    Option<&'a VectorShape>,
+);
+type BulletSprite<'a> = (&'a Transform, &'a Handle<Image>, Option<&'a VectorShape>);
+
+/// Stand-in sprites drawn during a replay, respawned every frame.
+#[derive(Component)]
+struct ReplayGhost;
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<ReplayState>()
+            .init_resource::<ReplayBuffer>()
+            .add_systems(
+                Update,
+                (record_snapshot, queue_replay)
+                    .chain()
+                    // Pausing for a replay would stall the other side of an online match.
+                    .run_if(in_state(ReplayState::Live))
+                    .run_if(not(resource_exists::<RollbackSession>))
+                    .run_if(not(resource_exists::<ServerConnection>)),
+            )
+            .add_systems(OnEnter(ReplayState::Replaying), start_replay)
+            .add_systems(
+                Update,
+                (
+                    (play_replay, replay_orbits, replay_asteroids).chain(),
+                    skip_replay,
+                )
+                    .run_if(in_state(ReplayState::Replaying)),
+            )
+            .add_systems(OnExit(ReplayState::Replaying), end_replay);
+    }
+}
+
+fn record_snapshot(
+    time: Res<Time>,
+    clock: Res<SimClock>,
+    mut buffer: ResMut<ReplayBuffer>,
+    rockets: Query<RocketSprite, With<Rocket>>,
+    bullets: Query<BulletSprite, With<Bullet>>,
+    asteroids: Query<&Transform, With<Asteroid>>,
+) {
+    // Nothing moves while the game is paused.
+    if time.delta().is_zero() {
+        return;
+    }
+    let snapshot = Snapshot {
+        tick: clock.tick,
+        rockets: rockets
+            .iter()
+            .map(|(player, transform, texture, shape)| RocketFrame {
+                player: *player,
+                transform: *transform,
+                texture: texture.clone(),
+                shape: shape.copied(),
+            })
+            .collect(),
+        bullets: bullets
+            .iter()
+            .map(|(transform, texture, shape)| (*transform, texture.clone(), shape.copied()))
+            .collect(),
+        asteroids: asteroids.iter().copied().collect(),
+    };
+    buffer.push(time.elapsed(), snapshot);
+}
+
+fn queue_replay(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut destroyed: EventReader<RocketDestroyed>,
+    pending: Option<ResMut<PendingReplay>>,
+    mut next_state: ResMut<NextState<ReplayState>>,
+) {
+    match pending {
+        Some(mut pending) => {
+            destroyed.clear();
+            if pending.delay.tick(time.delta()).finished() {
+                commands.remove_resource::<PendingReplay>();
+                commands.insert_resource(ActiveReplay {
+                    cursor: pending.kill_time.saturating_sub(REPLAY_WINDOW),
+                    kill_time: pending.kill_time,
+                    victim: pending.victim,
+                    position: pending.position,
+                    exploded: false,
+                });
+                next_state.set(ReplayState::Replaying);
+            }
+        }
+        None => {
+            if let Some(kill) = destroyed.read().last() {
+                commands.insert_resource(PendingReplay {
+                    kill_time: time.elapsed(),
+                    victim: kill.player,
+                    position: kill.position,
+                    delay: Timer::from_seconds(REPLAY_DELAY, TimerMode::Once),
+                });
+            }
+        }
+    }
+}
+
+fn start_replay(mut time: ResMut<Time<Virtual>>, mut live: Query<&mut Visibility, LiveSprites>) {
+    time.set_relative_speed(REPLAY_SPEED);
+    for mut visibility in &mut live {
+        *visibility = Visibility::Hidden;
+    }
+}
+
+fn play_replay(
+    mut commands: Commands,
+    time: Res<Time>,
+    buffer: Res<ReplayBuffer>,
+    mut replay: ResMut<ActiveReplay>,
+    ghosts: Query<Entity, With<ReplayGhost>>,
+    mut explosions: EventWriter<Explosion>,
+    mut next_state: ResMut<NextState<ReplayState>>,
+) {
+    replay.cursor += time.delta();
+
+    if replay.cursor >= replay.kill_time + REPLAY_TAIL {
+        next_state.set(ReplayState::Live);
+        return;
+    }
+
+    if !replay.exploded && replay.cursor >= replay.kill_time {
+        explosions.send(Explosion {
+            position: replay.position,
+        });
+        replay.exploded = true;
+    }
+
+    for ghost in &ghosts {
+        commands.entity(ghost).despawn();
+    }
+
+    let Some(snapshot) = buffer.frame_at(replay.cursor.min(replay.kill_time)) else {
+        return;
+    };
+
+    for rocket in &snapshot.rockets {
+        // The killed rocket disappears once the explosion starts.
+        if replay.exploded && rocket.player == replay.victim {
+            continue;
+        }
+        let mut ghost = commands.spawn((
+            SpriteBundle {
+                texture: rocket.texture.clone(),
+                transform: rocket.transform,
+                ..default()
+            },
+            ReplayGhost,
+        ));
+        if let Some(shape) = rocket.shape {
+            ghost.insert(shape);
+        }
+    }
+
+    for (transform, texture, shape) in &snapshot.bullets {
+        let mut ghost = commands.spawn((
+            SpriteBundle {
+                texture: texture.clone(),
+                transform: *transform,
+                ..default()
+            },
+            ReplayGhost,
+        ));
+        if let Some(shape) = shape {
+            ghost.insert(*shape);
+        }
+    }
+}
+
+/// Puts orbiting bodies where they were in the frame being replayed. The simulation
+/// moves them back once the match resumes.
+fn replay_orbits(
+    buffer: Res<ReplayBuffer>,
+    replay: Res<ActiveReplay>,
+    layout: Res<ArenaLayout>,
+    mut wells: Query<(&WellIndex, &mut Transform)>,
+) {
+    let Some(snapshot) = buffer.frame_at(replay.cursor.min(replay.kill_time)) else {
+        return;
+    };
+    let clock = SimClock {
+        tick: snapshot.tick,
+    };
+    let positions = layout.positions(clock.elapsed().as_secs_f32());
+    for (index, mut transform) in &mut wells {
+        if let Some(position) = positions.get(index.0) {
+            transform.translation = position.extend(transform.translation.z);
+        }
+    }
+}
+
+/// Stand-ins for the asteroids of the frame being replayed, drawn like the real ones.
+fn replay_asteroids(
+    mut commands: Commands,
+    buffer: Res<ReplayBuffer>,
+    replay: Res<ActiveReplay>,
+    look: Res<RockLook>,
+) {
+    let Some(snapshot) = buffer.frame_at(replay.cursor.min(replay.kill_time)) else {
+        return;
+    };
+    for transform in &snapshot.asteroids {
+        commands.spawn((
+            SpatialBundle::from_transform(*transform),
+            Mesh2dHandle(look.mesh.clone()),
+            look.material.clone(),
+            VectorShape::Asteroid,
+            ReplayGhost,
+        ));
+    }
+}
+
+/// Any player can cut the replay short with any key or gamepad button.
+fn skip_replay(
+    keys: Res<ButtonInput<KeyCode>>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    mut next_state: ResMut<NextState<ReplayState>>,
+) {
+    if keys.get_just_pressed().len() > 0 || buttons.get_just_pressed().len() > 0 {
+        next_state.set(ReplayState::Live);
+    }
+}
+
+fn end_replay(
+    mut commands: Commands,
+    mut time: ResMut<Time<Virtual>>,
+    ghosts: Query<Entity, With<ReplayGhost>>,
+    mut live: Query<&mut Visibility, LiveSprites>,
+) {
+    time.set_relative_speed(1.0);
+    commands.remove_resource::<ActiveReplay>();
+    for ghost in &ghosts {
+        commands.entity(ghost).despawn();
+    }
+    for mut visibility in &mut live {
+        *visibility = Visibility::Inherited;
+    }
+}
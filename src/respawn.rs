@@ -0,0 +1,196 @@
+//! Rockets coming back after they are destroyed, for deathmatch [`Rules`].
+//!
+//! A destroyed rocket returns after the delay of the rules, at the spot that keeps
+//! it furthest from the enemy rocket, the gravity wells and the torpedoes flying
+//! around. It can't be hurt for a few seconds, and blinks while that lasts. With
+//! limited lives a player who has lost them all stays out for the rest of the round.
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use std::time::Duration;
+
+use crate::arena::GravityWell;
+use crate::bullet::Bullet;
+use crate::hull::ticks_after;
+use crate::replay::ReplayState;
+use crate::rocket::{Hangar, Player, Rocket, RocketDestroyed, SPAWNS};
+use crate::{ArenaBounds, Rules, SimClock, SimRng, TICK_RATE};
+
+/// Random spots weighed against the player's starting position when respawning.
+const CANDIDATES: usize = 16;
+/// Kept free at the edges, so a rocket doesn't come back half off the screen.
+const EDGE_MARGIN: f32 = 60.0;
+/// How far ahead the paths of torpedoes are followed when looking for a safe spot.
+const TORPEDO_LOOKAHEAD: f32 = 1.5;
+/// Ticks a protected rocket stays shown, then hidden, while it blinks.
+const BLINK_TICKS: u32 = 4;
+
+/// Whether destroyed rockets come back, and how often.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Respawn {
+    /// Seconds between a rocket being destroyed and it coming back.
+    pub delay: f32,
+    /// Rockets each player has in a round, counting the first; `None` for no limit.
+    pub lives: Option<u32>,
+}
+
+impl Default for Respawn {
+    fn default() -> Self {
+        Self {
+            delay: 3.0,
+            lives: Some(5),
+        }
+    }
+}
+
+/// Lives left of each player and when their destroyed rockets come back, indexed by
+/// [`Player`].
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Respawns {
+    /// `None` when the rules don't limit them.
+    pub lives: [Option<u32>; 2],
+    /// Match tick at which the rocket comes back, while it is destroyed.
+    pub due: [Option<u32>; 2],
+}
+
+impl Respawns {
+    /// Full lives for a new round under `rules`.
+    pub fn new(rules: &Rules) -> Self {
+        Self {
+            lives: [rules.respawn.and_then(|respawn| respawn.lives); 2],
+            due: [None; 2],
+        }
+    }
+
+    /// Whether a destroyed rocket is still to come back.
+    pub fn pending(&self) -> bool {
+        self.due.iter().any(Option::is_some)
+    }
+
+    /// Time until the rocket of `player` comes back at `tick`, if it is coming back.
+    pub fn remaining(&self, player: Player, tick: u32) -> Option<Duration> {
+        let due = self.due[player.0]?;
+        Some(Duration::from_secs_f64(
+            due.saturating_sub(tick) as f64 / TICK_RATE,
+        ))
+    }
+}
+
+/// Takes a life from every player whose rocket was destroyed this tick, and sets a
+/// time for it to come back if any are left.
+pub fn schedule_respawns(
+    clock: Res<SimClock>,
+    rules: Res<Rules>,
+    mut destroyed: EventReader<RocketDestroyed>,
+    mut respawns: ResMut<Respawns>,
+) {
+    let Some(respawn) = rules.respawn else {
+        return;
+    };
+    for event in destroyed.read() {
+        let player = event.player.0.min(1);
+        // A rocket can be hit by several things in the same tick but only dies once.
+        if respawns.due[player].is_some() {
+            continue;
+        }
+        if let Some(lives) = &mut respawns.lives[player] {
+            *lives = lives.saturating_sub(1);
+            if *lives == 0 {
+                continue;
+            }
+        }
+        let delay = Duration::from_secs_f32(respawn.delay);
+        respawns.due[player] = Some(ticks_after(clock.tick, delay));
+    }
+}
+
+/// What a rocket coming back keeps its distance from.
+#[derive(SystemParam)]
+pub struct Threats<'w, 's> {
+    bounds: Res<'w, ArenaBounds>,
+    wells: Query<'w, 's, &'static GravityWell>,
+    rockets: Query<'w, 's, (&'static Player, &'static Transform), With<Rocket>>,
+    torpedoes: Query<'w, 's, (&'static Bullet, &'static Transform)>,
+}
+
+impl Threats<'_, '_> {
+    /// Distance from `spot` to the nearest danger for `player`: an enemy rocket, the
+    /// kill radius of a well or the path of a torpedo.
+    fn clearance(&self, spot: Vec2, player: Player) -> f32 {
+        let enemies = self
+            .rockets
+            .iter()
+            .filter(|(owner, _)| **owner != player)
+            .map(|(_, transform)| spot.distance(transform.translation.truncate()));
+        let wells = self
+            .wells
+            .iter()
+            .map(|well| spot.distance(well.position) - well.kill_radius);
+        let torpedoes = self.torpedoes.iter().map(|(bullet, transform)| {
+            let start = transform.translation.truncate();
+            let path = bullet.velocity * TORPEDO_LOOKAHEAD;
+            let along = ((spot - start).dot(path) / path.length_squared()).clamp(0.0, 1.0);
+            spot.distance(start + path * along)
+        });
+        enemies
+            .chain(wells)
+            .chain(torpedoes)
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    /// The starting position of `player` or one of a few random spots, whichever is
+    /// furthest from any danger.
+    pub fn safest_spot(&self, rng: &mut SimRng, player: Player) -> Vec2 {
+        let half = self.bounds.half_size - Vec2::splat(EDGE_MARGIN);
+        let home = SPAWNS[player.0];
+        let mut best = (home, self.clearance(home, player));
+        for _ in 0..CANDIDATES {
+            let spot = Vec2::new(rng.range(-half.x, half.x), rng.range(-half.y, half.y));
+            let clearance = self.clearance(spot, player);
+            if clearance > best.1 {
+                best = (spot, clearance);
+            }
+        }
+        best.0
+    }
+}
+
+/// Brings back the destroyed rockets whose time has come.
+pub fn respawn_rockets(
+    mut commands: Commands,
+    clock: Res<SimClock>,
+    hangar: Hangar,
+    mut respawns: ResMut<Respawns>,
+    mut rng: ResMut<SimRng>,
+    threats: Threats,
+) {
+    for player in [Player(0), Player(1)] {
+        if respawns.due[player.0].is_some_and(|due| clock.tick >= due) {
+            respawns.due[player.0] = None;
+            let position = threats.safest_spot(&mut rng, player);
+            hangar.relaunch(&mut commands, player, position);
+        }
+    }
+}
+
+/// Makes rockets blink for as long as they can't be hurt, mostly right after they
+/// come back.
+pub struct RespawnPlugin;
+
+impl Plugin for RespawnPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, blink_protected.run_if(in_state(ReplayState::Live)));
+    }
+}
+
+fn blink_protected(clock: Res<SimClock>, mut rockets: Query<(&Rocket, &mut Visibility)>) {
+    for (rocket, mut visibility) in &mut rockets {
+        let left = rocket.protected_until.saturating_sub(clock.tick);
+        let shown = left == 0 || (left / BLINK_TICKS).is_multiple_of(2);
+        visibility.set_if_neq(if shown {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+    }
+}
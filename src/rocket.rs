@@ -1,7 +1,13 @@
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
 use std::time::Duration;
 
-#[derive(Component)]
+use crate::hull::{ticks_after, SPAWN_PROTECTION};
+use crate::input::{KeyBindings, ShipInput};
+use crate::powerup::Boosts;
+use crate::{AppState, ArenaBounds, Rules, SimClock};
+
+#[derive(Component, Clone)]
 pub struct Rocket {
     pub speed: f32,
     pub max_speed: f32,
@@ -12,106 +18,222 @@ pub struct Rocket {
     pub spawn_key: KeyCode,
     pub cooldown: Duration,
     pub last_shot_time: Duration,
+    /// Torpedoes left.
+    pub ammo: u32,
+    /// Seconds of thrust left.
+    pub fuel: f32,
+    /// Hyperspace jumps left.
+    pub hyperspace: u32,
+    /// Timed power-ups picked up.
+    pub boosts: Boosts,
+    /// Hull points left, when the rules give rockets armor.
+    pub hull: f32,
+    /// Shield points left, when the rules give rockets armor.
+    pub shield: f32,
+    /// Match tick of the last hit that did damage.
+    pub last_hit: u32,
+    /// Match tick until which nothing can hurt the rocket.
+    pub protected_until: u32,
+}
+
+/// Identifies which player controls a rocket, stable across despawns.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Player(pub usize);
+
+/// Sent whenever a rocket is destroyed, by a torpedo, the sun or a collision.
+#[derive(Event)]
+pub struct RocketDestroyed {
+    pub player: Player,
+    pub position: Vec3,
 }
 
+#[derive(Clone, Copy)]
 pub struct RocketControls {
     pub rotate_left: KeyCode,
     pub rotate_right: KeyCode,
     pub accelerate: KeyCode,
+    pub hyperspace: KeyCode,
 }
 
-const W_CLIP: f32 = 510.;
-const H_CLIP: f32 = 380.;
-
 const SPAWN_X: f32 = 400.;
 const SPAWN_Y: f32 = 200.;
+/// Where each player's rocket starts a round.
+pub const SPAWNS: [Vec2; 2] = [Vec2::new(-SPAWN_X, SPAWN_Y), Vec2::new(SPAWN_X, -SPAWN_Y)];
 
-pub fn add_rockets(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let rocket_image = asset_server.load("Rocket1-v2.png");
-    let rocket_image_b = asset_server.load("Rocket1-v3.png");
-
-    let pos = Vec2::new(-SPAWN_X, SPAWN_Y);
-    let pos_b = Vec2::new(SPAWN_X, -SPAWN_Y);
-    let direction = Vec2::ZERO - pos;
-    let direction_b = Vec2::ZERO - pos_b;
-    let angle = direction.y.atan2(direction.x) - std::f32::consts::FRAC_PI_2;
-    let angle_b = direction_b.y.atan2(direction_b.x) - std::f32::consts::FRAC_PI_2;
-
-    let rocket_a_pos = Transform {
-        translation: Vec3::new(-SPAWN_X, SPAWN_Y, 1.),
-        scale: Vec3::splat(0.12),
-        rotation: Quat::from_rotation_z(angle),
-        ..Default::default()
-    };
-    let rocket_b_pos = Transform {
-        translation: Vec3::new(SPAWN_X, -SPAWN_Y, 1.),
-        scale: Vec3::splat(0.144),
-        rotation: Quat::from_rotation_z(angle_b),
-        ..Default::default()
-    };
-    commands.spawn((
-        SpriteBundle {
-            texture: rocket_image.clone(),
-            transform: rocket_a_pos,
-            ..default()
-        },
-        Rocket {
-            speed: 0.0,
-            max_speed: 150.0,
-            velocity: Vec2::ZERO,
-            radius_collision: 50.,
-            rotation_speed: f32::to_radians(0.0),
-            controls: RocketControls {
-                rotate_left: KeyCode::KeyA,
-                rotate_right: KeyCode::KeyD,
-                accelerate: KeyCode::KeyS,
-            },
-            spawn_key: KeyCode::KeyW,
-            cooldown: Duration::from_millis(500),
-            last_shot_time: Duration::ZERO,
-        },
-    ));
-    commands.spawn((
-        SpriteBundle {
-            texture: rocket_image_b,
-            transform: rocket_b_pos,
-            ..default()
-        },
-        Rocket {
-            speed: 0.0,
-            max_speed: 150.0,
-            radius_collision: 50.,
-            velocity: Vec2::ZERO,
-            rotation_speed: f32::to_radians(0.0),
-            controls: RocketControls {
-                rotate_left: KeyCode::KeyJ,
-                rotate_right: KeyCode::KeyL,
-                accelerate: KeyCode::KeyK,
-            },
-            spawn_key: KeyCode::KeyI,
-            cooldown: Duration::from_millis(300),
-            last_shot_time: Duration::ZERO
-        },
-    ));
+/// Hull types players can pick in the lobby.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ShipClass {
+    /// Slim and steady, with a slower torpedo tube.
+    Needle,
+    /// Bulkier, but reloads much faster.
+    Wedge,
 }
 
-pub fn clip_rockets(mut query: Query<&mut Transform, With<Rocket>>) {
-    let mut pos: Vec<Mut<Transform>> = query.iter_mut().collect();
-    for i in 0..pos.len() {
-        let transform = &mut pos[i];
-        if transform.translation[0] < -W_CLIP || transform.translation[0] > W_CLIP {
-            transform.translation = Vec3::new(
-                -transform.translation[0],
-                transform.translation[1],
-                transform.translation[2],
-            );
+impl ShipClass {
+    pub const ALL: [ShipClass; 2] = [ShipClass::Needle, ShipClass::Wedge];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShipClass::Needle => "Needle",
+            ShipClass::Wedge => "Wedge",
         }
-        if transform.translation[1] < -H_CLIP || transform.translation[1] > H_CLIP {
-            transform.translation = Vec3::new(
-                transform.translation[0],
-                -transform.translation[1],
-                transform.translation[2],
-            );
+    }
+
+    fn sprite(&self) -> &'static str {
+        match self {
+            ShipClass::Needle => "Rocket1-v2.png",
+            ShipClass::Wedge => "Rocket1-v3.png",
         }
     }
+
+    fn scale(&self) -> f32 {
+        match self {
+            ShipClass::Needle => 0.12,
+            ShipClass::Wedge => 0.144,
+        }
+    }
+
+    fn cooldown(&self) -> Duration {
+        match self {
+            ShipClass::Needle => Duration::from_millis(500),
+            ShipClass::Wedge => Duration::from_millis(300),
+        }
+    }
+}
+
+/// Hull tints players can pick in the lobby. White leaves the sprite as drawn.
+pub const SHIP_COLORS: [(&str, Color); 6] = [
+    ("White", Color::WHITE),
+    ("Red", Color::srgb(1.0, 0.35, 0.3)),
+    ("Green", Color::srgb(0.4, 1.0, 0.4)),
+    ("Blue", Color::srgb(0.4, 0.6, 1.0)),
+    ("Yellow", Color::srgb(1.0, 0.9, 0.3)),
+    ("Purple", Color::srgb(0.8, 0.45, 1.0)),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Loadout {
+    pub class: ShipClass,
+    /// Index into [`SHIP_COLORS`].
+    pub color: usize,
+}
+
+impl Loadout {
+    pub fn color(&self) -> Color {
+        SHIP_COLORS[self.color % SHIP_COLORS.len()].1
+    }
+}
+
+/// What each player flies in the next match, indexed by [`Player`].
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct Loadouts(pub [Loadout; 2]);
+
+impl Default for Loadouts {
+    fn default() -> Self {
+        Self([
+            Loadout {
+                class: ShipClass::Needle,
+                color: 0,
+            },
+            Loadout {
+                class: ShipClass::Wedge,
+                color: 0,
+            },
+        ])
+    }
+}
+
+/// What it takes to put a rocket in the arena.
+#[derive(SystemParam)]
+pub struct Hangar<'w> {
+    asset_server: Res<'w, AssetServer>,
+    loadouts: Res<'w, Loadouts>,
+    bindings: Res<'w, KeyBindings>,
+    rules: Res<'w, Rules>,
+    clock: Res<'w, SimClock>,
+}
+
+impl Hangar<'_> {
+    /// Spawns a fresh rocket for `player` at `position`, facing the middle of the
+    /// arena.
+    pub fn launch(&self, commands: &mut Commands, player: Player, position: Vec2) -> Entity {
+        self.spawn_rocket(commands, player, position, Duration::ZERO)
+    }
+
+    /// Same as [`Self::launch`] for a rocket coming back after it was destroyed, which
+    /// can't be hurt for a moment.
+    pub fn relaunch(&self, commands: &mut Commands, player: Player, position: Vec2) -> Entity {
+        self.spawn_rocket(commands, player, position, SPAWN_PROTECTION)
+    }
+
+    fn spawn_rocket(
+        &self,
+        commands: &mut Commands,
+        player: Player,
+        position: Vec2,
+        protection: Duration,
+    ) -> Entity {
+        let loadout = self.loadouts.0[player.0];
+        let keys = self.bindings.0[player.0];
+        let armor = self.rules.armor.unwrap_or_default();
+        let direction = Vec2::ZERO - position;
+        let angle = direction.y.atan2(direction.x) - std::f32::consts::FRAC_PI_2;
+
+        commands
+            .spawn((
+                SpriteBundle {
+                    texture: self.asset_server.load(loadout.class.sprite()),
+                    sprite: Sprite {
+                        color: loadout.color(),
+                        ..default()
+                    },
+                    transform: Transform {
+                        translation: position.extend(1.),
+                        scale: Vec3::splat(loadout.class.scale()),
+                        rotation: Quat::from_rotation_z(angle),
+                    },
+                    ..default()
+                },
+                Rocket {
+                    speed: 0.0,
+                    max_speed: 150.0,
+                    velocity: Vec2::ZERO,
+                    radius_collision: 50.,
+                    rotation_speed: f32::to_radians(0.0),
+                    controls: keys.controls(),
+                    spawn_key: keys.fire,
+                    cooldown: loadout.class.cooldown(),
+                    last_shot_time: Duration::ZERO,
+                    ammo: self.rules.ammo,
+                    fuel: self.rules.fuel,
+                    hyperspace: 0,
+                    boosts: Boosts::default(),
+                    hull: armor.hull,
+                    shield: armor.shield,
+                    last_hit: self.clock.tick,
+                    protected_until: ticks_after(self.clock.tick, protection),
+                },
+                player,
+                ShipInput::default(),
+                StateScoped(AppState::InGame),
+            ))
+            .id()
+    }
+}
+
+pub fn add_rockets(mut commands: Commands, hangar: Hangar) {
+    for (player, position) in SPAWNS.into_iter().enumerate() {
+        hangar.launch(&mut commands, Player(player), position);
+    }
+}
+
+pub fn clip_rockets(bounds: Res<ArenaBounds>, mut query: Query<&mut Transform, With<Rocket>>) {
+    for mut transform in query.iter_mut() {
+        wrap_position(&bounds, &mut transform);
+    }
+}
+
+/// Moves a rocket that left the arena to the opposite edge.
+pub fn wrap_position(bounds: &ArenaBounds, transform: &mut Transform) {
+    transform.translation = bounds.wrap(transform.translation);
 }
@@ -0,0 +1,630 @@
+//! Authoritative match server.
+//!
+//! The server owns the whole [`Simulation`]: it applies the inputs its clients send,
+//! makes every collision decision and broadcasts a [`Snapshot`] of the result after
+//! each tick. Inputs are queued per client and consumed one per tick, so a client
+//! that predicts its own rocket sees the server apply its inputs in the same order.
+//!
+//! Clients reach the server over UDP, or over TCP (see [`TcpLink`]) where datagrams
+//! don't get through; it listens for both on the same port.
+//!
+//! Before the match the server sits in [`AppState::Lobby`], announcing itself on the
+//! local network. Players pick their ship there and ready up, and the host (player 0)
+//! starts the match once everyone is ready.
+//!
+//! Spectators can join at any time. They get the same snapshots as the players, held
+//! back by the configured delay so nobody can watch the stream to see what their
+//! opponent is doing; the same delayed stream can be copied to a [`MatchStream`].
+
+use bevy::prelude::*;
+use std::collections::hash_map::RandomState;
+use std::collections::VecDeque;
+use std::fmt;
+use std::hash::BuildHasher;
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, UdpSocket};
+use std::time::Duration;
+
+use crate::arena::ArenaLayout;
+use crate::asteroid::Asteroid;
+use crate::bullet::Bullet;
+use crate::input::ShipInput;
+use crate::powerup::PowerUp;
+use crate::protocol::{
+    match_setup, AsteroidState, BulletState, ClientMessage, LobbyPlayer, PowerUpState,
+    RocketState, ServerMessage, Snapshot, DISCOVERY_PORTS, MAX_PACKET,
+};
+use crate::respawn::Respawns;
+use crate::rocket::{Loadouts, Player, Rocket};
+use crate::stream::MatchStream;
+use crate::tcp::TcpLink;
+use crate::{AppState, Rules, Scores, SimClock, Simulation, TICK_RATE};
+
+/// Clients that stay silent this long lose their slot.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Inputs buffered per client before the oldest are dropped.
+const MAX_QUEUED_INPUTS: usize = 8;
+/// How far past the ticks the server has played a client's input sequence numbers
+/// may run. Anything beyond is dropped, so a forged number can't lock a client out.
+const INPUT_WINDOW: u32 = TICK_RATE as u32;
+/// Ticks between two lobby status updates.
+const LOBBY_UPDATE_TICKS: u32 = 6;
+const MAX_SPECTATORS: usize = 16;
+/// Open TCP connections, whether or not they have joined yet.
+const MAX_TCP_LINKS: usize = 2 + MAX_SPECTATORS;
+
+/// Where a client or spectator is reached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Peer {
+    Udp(SocketAddr),
+    Tcp(SocketAddr),
+}
+
+impl fmt::Display for Peer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Peer::Udp(addr) => write!(f, "{addr}"),
+            Peer::Tcp(addr) => write!(f, "{addr} (TCP)"),
+        }
+    }
+}
+
+struct ConnectedClient {
+    peer: Peer,
+    player: Player,
+    seat: LobbyPlayer,
+    last_heard: Duration,
+    /// Highest input sequence number received so far.
+    received: u32,
+    /// Sequence number of the input applied on the last tick.
+    ack: u32,
+    /// Ticks played since `ack` last moved on.
+    stalled: u32,
+    queue: VecDeque<(u32, ShipInput)>,
+    last_input: ShipInput,
+}
+
+impl ConnectedClient {
+    fn next_input(&mut self) -> ShipInput {
+        match self.queue.pop_front() {
+            Some((seq, input)) => {
+                self.ack = seq;
+                self.stalled = 0;
+                self.last_input = input;
+                input
+            }
+            // Nothing arrived in time: keep steering the same way, but don't fire or
+            // jump again.
+            None => {
+                self.stalled = self.stalled.saturating_add(1);
+                ShipInput(self.last_input.0 & !ShipInput::EDGE_TRIGGERED)
+            }
+        }
+    }
+}
+
+struct Spectator {
+    peer: Peer,
+    last_heard: Duration,
+}
+
+/// A TCP connection, from the moment it is accepted.
+struct TcpConnection {
+    link: TcpLink,
+    last_heard: Duration,
+}
+
+#[derive(Resource)]
+pub struct GameServer {
+    socket: UdpSocket,
+    listener: TcpListener,
+    connections: Vec<TcpConnection>,
+    /// Tells this server apart from others with the same name.
+    id: u64,
+    /// Shown to players browsing for games.
+    name: String,
+    /// Arena and rules played, see [`match_setup`].
+    setup: u64,
+    clients: Vec<ConnectedClient>,
+    spectators: Vec<Spectator>,
+    /// Ticks spectators lag behind the players.
+    spectator_delay: u32,
+    /// Snapshots waiting out the spectator delay.
+    delayed: VecDeque<Snapshot>,
+    stream: Option<MatchStream>,
+    start_requested: bool,
+    ticks: u32,
+}
+
+impl GameServer {
+    pub fn bind(port: u16, name: String) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", port))?;
+        socket.set_nonblocking(true)?;
+        socket.set_broadcast(true)?;
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        info!(
+            "server \"{name}\" listening on {} (UDP and TCP)",
+            socket.local_addr()?
+        );
+        Ok(Self {
+            socket,
+            listener,
+            connections: Vec::new(),
+            id: RandomState::new().hash_one(std::process::id()),
+            name,
+            setup: 0,
+            clients: Vec::new(),
+            spectators: Vec::new(),
+            spectator_delay: 0,
+            delayed: VecDeque::new(),
+            stream: None,
+            start_requested: false,
+            ticks: 0,
+        })
+    }
+
+    /// Holds spectators `ticks` behind the players.
+    pub fn with_spectator_delay(mut self, ticks: u32) -> Self {
+        self.spectator_delay = ticks;
+        self
+    }
+
+    /// Copies the spectator view of the match to `stream`.
+    pub fn with_stream(mut self, stream: MatchStream) -> Self {
+        self.stream = Some(stream);
+        self
+    }
+
+    /// Tells lobby browsers on this machine and the local network about the server.
+    fn announce(&self) {
+        let message = ServerMessage::Announce {
+            id: self.id,
+            name: self.name.clone(),
+            players: self.clients.len() as u8,
+        }
+        .encode();
+        for port in DISCOVERY_PORTS {
+            for ip in [Ipv4Addr::BROADCAST, Ipv4Addr::LOCALHOST] {
+                // Broadcasts fail on machines without a network; loopback still works.
+                let _ = self.socket.send_to(&message, (ip, port));
+            }
+        }
+    }
+
+    fn lobby(&self) -> Vec<LobbyPlayer> {
+        let mut seats: Vec<LobbyPlayer> = self.clients.iter().map(|client| client.seat).collect();
+        seats.sort_by_key(|seat| seat.player.0);
+        seats
+    }
+
+    fn loadouts(&self) -> Loadouts {
+        let mut loadouts = Loadouts::default();
+        for client in &self.clients {
+            loadouts.0[client.player.0] = client.seat.loadout;
+        }
+        loadouts
+    }
+
+    /// Sends to every player and spectator.
+    fn send_all(&mut self, message: &ServerMessage) {
+        let players = self.clients.iter().map(|client| client.peer);
+        let peers: Vec<Peer> = players
+            .chain(self.spectators.iter().map(|spectator| spectator.peer))
+            .collect();
+        for peer in peers {
+            self.send(peer, message);
+        }
+    }
+
+    /// Sends to the players only.
+    fn send_players(&mut self, message: &ServerMessage) {
+        let peers: Vec<Peer> = self.clients.iter().map(|client| client.peer).collect();
+        for peer in peers {
+            self.send(peer, message);
+        }
+    }
+
+    /// Sends to spectators and the match stream, which see the same view.
+    fn send_spectators(&mut self, message: &ServerMessage) {
+        let peers: Vec<Peer> = self.spectators.iter().map(|spectator| spectator.peer).collect();
+        for peer in peers {
+            self.send(peer, message);
+        }
+        if let Some(stream) = &mut self.stream {
+            if let Err(err) = stream.write(message) {
+                warn!("stopped streaming to {}: {err}", stream.target());
+                self.stream = None;
+            }
+        }
+    }
+
+    fn send(&mut self, peer: Peer, message: &ServerMessage) {
+        match peer {
+            Peer::Udp(addr) => {
+                if let Err(err) = self.socket.send_to(&message.encode(), addr) {
+                    if err.kind() != ErrorKind::WouldBlock {
+                        warn!("server socket error: {err}");
+                    }
+                }
+            }
+            Peer::Tcp(addr) => {
+                let Some(index) = self.connections.iter().position(|c| c.link.peer() == addr)
+                else {
+                    return;
+                };
+                if let Err(err) = self.connections[index].link.send(&message.encode()) {
+                    info!("lost {peer}: {err}");
+                    self.connections.swap_remove(index);
+                }
+            }
+        }
+    }
+
+    fn receive(&mut self, now: Duration) {
+        let mut buf = [0u8; MAX_PACKET];
+        loop {
+            match self.socket.recv_from(&mut buf) {
+                Ok((len, from)) => {
+                    if let Some(message) = ClientMessage::decode(&buf[..len]) {
+                        self.handle_message(Peer::Udp(from), message, now);
+                    }
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::ConnectionReset => {}
+                Err(err) => {
+                    warn!("server socket error: {err}");
+                    break;
+                }
+            }
+        }
+
+        self.accept(now);
+        let mut received = Vec::new();
+        self.connections.retain_mut(|connection| {
+            let mut messages = Vec::new();
+            let status = connection.link.receive(&mut messages);
+            if !messages.is_empty() {
+                connection.last_heard = now;
+            }
+            let peer = Peer::Tcp(connection.link.peer());
+            received.extend(
+                messages
+                    .iter()
+                    .filter_map(|message| ClientMessage::decode(message))
+                    .map(|message| (peer, message)),
+            );
+            if let Err(err) = status {
+                info!("lost {peer}: {err}");
+                return false;
+            }
+            now.saturating_sub(connection.last_heard) < CLIENT_TIMEOUT
+        });
+        for (peer, message) in received {
+            self.handle_message(peer, message, now);
+        }
+
+        self.clients.retain(|client| {
+            let alive = now.saturating_sub(client.last_heard) < CLIENT_TIMEOUT;
+            if !alive {
+                info!("{} timed out", client.peer);
+            }
+            alive
+        });
+        self.spectators
+            .retain(|spectator| now.saturating_sub(spectator.last_heard) < CLIENT_TIMEOUT);
+    }
+
+    /// Takes the TCP connections waiting to be accepted, as many as there is room for.
+    fn accept(&mut self, now: Duration) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, addr)) => {
+                    if self.connections.len() >= MAX_TCP_LINKS {
+                        info!("turned away {addr}: too many TCP connections");
+                        continue;
+                    }
+                    match TcpLink::new(stream) {
+                        Ok(link) => self.connections.push(TcpConnection {
+                            link,
+                            last_heard: now,
+                        }),
+                        Err(err) => warn!("could not set up the connection from {addr}: {err}"),
+                    }
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) => {
+                    warn!("server listener error: {err}");
+                    break;
+                }
+            }
+        }
+    }
+
+    fn handle_message(&mut self, from: Peer, message: ClientMessage, now: Duration) {
+        let known = self.clients.iter().position(|client| client.peer == from);
+
+        if let Some(index) = self.spectators.iter().position(|s| s.peer == from) {
+            match message {
+                ClientMessage::Leave => {
+                    info!("spectator {from} left");
+                    self.spectators.remove(index);
+                }
+                _ => self.spectators[index].last_heard = now,
+            }
+            return;
+        }
+
+        match (message, known) {
+            (ClientMessage::Join, Some(index)) => {
+                self.clients[index].last_heard = now;
+                let player = self.clients[index].player;
+                let setup = self.setup;
+                self.send(from, &ServerMessage::Welcome { player, setup });
+            }
+            (ClientMessage::Join, None) => {
+                let free = (0..2)
+                    .map(Player)
+                    .find(|player| self.clients.iter().all(|c| c.player != *player));
+                match free {
+                    Some(player) => {
+                        info!("{from} joined as player {}", player.0);
+                        self.clients.push(ConnectedClient {
+                            peer: from,
+                            player,
+                            seat: LobbyPlayer {
+                                player,
+                                loadout: Loadouts::default().0[player.0],
+                                ready: false,
+                            },
+                            last_heard: now,
+                            received: 0,
+                            ack: 0,
+                            stalled: 0,
+                            queue: VecDeque::new(),
+                            last_input: ShipInput::default(),
+                        });
+                        let setup = self.setup;
+                        self.send(from, &ServerMessage::Welcome { player, setup });
+                    }
+                    None => self.send(from, &ServerMessage::Full),
+                }
+            }
+            (ClientMessage::Input { seq, inputs }, Some(index)) => {
+                let client = &mut self.clients[index];
+                client.last_heard = now;
+                // The client can't be further ahead than the ticks played since its
+                // last applied input, plus some slack for its clock running fast.
+                let newest = client
+                    .ack
+                    .saturating_add(client.stalled)
+                    .saturating_add(INPUT_WINDOW);
+                let Some(next) = seq.checked_add(1) else {
+                    return;
+                };
+                let first = next.saturating_sub(inputs.len() as u32);
+                for (offset, input) in inputs.into_iter().enumerate() {
+                    let Some(input_seq) = first.checked_add(offset as u32) else {
+                        break;
+                    };
+                    if input_seq > client.received && input_seq <= newest {
+                        client.queue.push_back((input_seq, input));
+                        client.received = input_seq;
+                    }
+                }
+                while client.queue.len() > MAX_QUEUED_INPUTS {
+                    // Don't lose a shot or a jump just because the client got ahead.
+                    let (_, dropped) = client.queue.pop_front().unwrap();
+                    if let Some((_, next)) = client.queue.front_mut() {
+                        next.0 |= dropped.0 & ShipInput::EDGE_TRIGGERED;
+                    }
+                }
+            }
+            (ClientMessage::Spectate, None) if self.spectators.len() < MAX_SPECTATORS => {
+                info!("{from} is spectating");
+                self.spectators.push(Spectator {
+                    peer: from,
+                    last_heard: now,
+                });
+            }
+            (ClientMessage::Leave, Some(index)) => {
+                info!("{from} left");
+                self.clients.remove(index);
+            }
+            (ClientMessage::SetLoadout(loadout), Some(index)) => {
+                self.clients[index].last_heard = now;
+                self.clients[index].seat.loadout = loadout;
+            }
+            (ClientMessage::Ready(ready), Some(index)) => {
+                self.clients[index].last_heard = now;
+                self.clients[index].seat.ready = ready;
+            }
+            (ClientMessage::Start, Some(index)) => {
+                self.clients[index].last_heard = now;
+                let everyone_ready = self.clients.iter().all(|client| client.seat.ready);
+                if self.clients[index].player == Player(0) && self.is_full() && everyone_ready {
+                    self.start_requested = true;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether both player slots are taken, so the match can run.
+    pub fn is_full(&self) -> bool {
+        self.clients.len() >= 2
+    }
+}
+
+/// Captures what clients need to draw the current tick.
+pub fn snapshot(world: &mut World) -> Snapshot {
+    let rockets = world
+        .query::<(&Player, &Rocket, &Transform)>()
+        .iter(world)
+        .map(|(player, rocket, transform)| RocketState {
+            player: *player,
+            translation: transform.translation.truncate(),
+            angle: transform.rotation.to_euler(EulerRot::ZYX).0,
+            velocity: rocket.velocity,
+            speed: rocket.speed,
+            rotation_speed: rocket.rotation_speed,
+            ammo: rocket.ammo,
+            fuel: rocket.fuel,
+            last_shot_time: rocket.last_shot_time,
+            hyperspace: rocket.hyperspace,
+            boosts: rocket.boosts,
+            hull: rocket.hull,
+            shield: rocket.shield,
+            protected_until: rocket.protected_until,
+        })
+        .collect();
+    let power_ups = world
+        .query::<(Entity, &PowerUp, &Transform)>()
+        .iter(world)
+        .map(|(entity, kind, transform)| PowerUpState {
+            id: entity.to_bits(),
+            kind: *kind,
+            translation: transform.translation.truncate(),
+        })
+        .collect();
+    let bullets = world
+        .query::<(Entity, &Bullet, &Transform)>()
+        .iter(world)
+        .map(|(entity, bullet, transform)| BulletState {
+            id: entity.to_bits(),
+            translation: transform.translation.truncate(),
+            angle: transform.rotation.to_euler(EulerRot::ZYX).0,
+            velocity: bullet.velocity,
+        })
+        .collect();
+    let asteroids = world
+        .query::<(Entity, &Asteroid, &Transform)>()
+        .iter(world)
+        .map(|(entity, asteroid, transform)| AsteroidState {
+            id: entity.to_bits(),
+            translation: transform.translation.truncate(),
+            radius: asteroid.radius,
+        })
+        .collect();
+
+    Snapshot {
+        tick: world.resource::<SimClock>().tick,
+        ack: 0,
+        scores: world.resource::<Scores>().0,
+        respawns: *world.resource::<Respawns>(),
+        rockets,
+        power_ups,
+        asteroids,
+        bullets,
+    }
+}
+
+/// Runs one server tick: read client inputs, simulate, send out the result.
+pub fn server_tick(world: &mut World) {
+    let now = world.resource::<Time<Real>>().elapsed();
+
+    let state = *world.resource::<State<AppState>>().get();
+
+    world.resource_scope(|world, mut server: Mut<GameServer>| {
+        server.receive(now);
+        server.ticks = server.ticks.wrapping_add(1);
+
+        if state == AppState::Lobby {
+            lobby_tick(world, &mut server);
+            return;
+        }
+
+        // Keep repeating the start in case a client missed it.
+        if server.ticks.is_multiple_of(TICK_RATE as u32) {
+            server.send_all(&ServerMessage::MatchStart(world.resource::<Loadouts>().0));
+        }
+
+        if server.is_full() {
+            for client in server.clients.iter_mut() {
+                let input = client.next_input();
+                let player = client.player;
+                for (rocket_player, mut rocket_input) in
+                    world.query::<(&Player, &mut ShipInput)>().iter_mut(world)
+                {
+                    if *rocket_player == player {
+                        *rocket_input = input;
+                    }
+                }
+            }
+            world.run_schedule(Simulation);
+        }
+
+        let mut snapshot = snapshot(world);
+        let clients: Vec<(Peer, u32)> = server
+            .clients
+            .iter()
+            .map(|client| (client.peer, client.ack))
+            .collect();
+        for (peer, ack) in clients {
+            snapshot.ack = ack;
+            server.send(peer, &ServerMessage::Snapshot(snapshot.clone()));
+        }
+
+        snapshot.ack = 0;
+        server.delayed.push_back(snapshot);
+        while server.delayed.len() > server.spectator_delay as usize {
+            let snapshot = server.delayed.pop_front().unwrap();
+            server.send_spectators(&ServerMessage::Snapshot(snapshot));
+        }
+    });
+}
+
+/// Takes note of the arena and rules once they are all set up.
+fn describe_match(mut server: ResMut<GameServer>, layout: Res<ArenaLayout>, rules: Res<Rules>) {
+    server.setup = match_setup(&layout, &rules);
+}
+
+fn lobby_tick(world: &mut World, server: &mut GameServer) {
+    if server.ticks.is_multiple_of(TICK_RATE as u32) {
+        server.announce();
+    }
+
+    if server.start_requested {
+        server.start_requested = false;
+        let loadouts = server.loadouts();
+        info!("starting the match");
+        world.insert_resource(loadouts);
+        world
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::InGame);
+        let start = ServerMessage::MatchStart(loadouts.0);
+        server.send_players(&start);
+        server.send_spectators(&start);
+    } else if server.ticks.is_multiple_of(LOBBY_UPDATE_TICKS) {
+        let lobby = server.lobby();
+        server.send_all(&ServerMessage::Lobby(lobby));
+    }
+}
+
+pub struct ServerPlugin {
+    pub port: u16,
+    pub name: String,
+    /// How long spectators lag behind the players, in seconds.
+    pub spectator_delay: f64,
+    /// Where to copy the spectator view, see [`MatchStream::open`].
+    pub stream: Option<String>,
+}
+
+impl Plugin for ServerPlugin {
+    fn build(&self, app: &mut App) {
+        let mut server = match GameServer::bind(self.port, self.name.clone()) {
+            Ok(server) => server,
+            Err(err) => panic!("could not bind port {}: {err}", self.port),
+        };
+        server = server.with_spectator_delay((self.spectator_delay * TICK_RATE).round() as u32);
+        if let Some(target) = &self.stream {
+            match MatchStream::open(target) {
+                Ok(stream) => server = server.with_stream(stream),
+                Err(err) => error!("could not stream to {target}: {err}"),
+            }
+        }
+        app.insert_resource(server)
+            .add_systems(Startup, describe_match)
+            .add_systems(FixedUpdate, server_tick);
+    }
+}
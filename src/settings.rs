@@ -0,0 +1,575 @@
+//! Player settings and the screen to change them.
+//!
+//! Settings are kept in `settings.cfg` in the platform's config directory, one
+//! `key = value` per line, and read before the window opens. Entries that are
+//! missing or don't parse keep their defaults, so an old or hand-edited file never
+//! stops the game from starting. Changes apply right away and are saved when the
+//! settings screen is closed.
+
+use bevy::prelude::*;
+use bevy::window::{PrimaryWindow, WindowMode};
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+use crate::background::{BackgroundMode, BackgroundSettings};
+use crate::input::{KeyBindings, PlayerKeys};
+use crate::menu::{spawn_menu, Menu, MenuBack, MenuButtons, MenuConfirmed, MenuEntry};
+use crate::post_process::{CrtPreset, CrtStrength};
+use crate::rocket::{Player, Rocket};
+use crate::vector::RenderStyle;
+
+const FILE_NAME: &str = "settings.cfg";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DisplayMode {
+    Windowed,
+    Borderless,
+    Fullscreen,
+}
+
+impl DisplayMode {
+    pub const ALL: [DisplayMode; 3] = [
+        DisplayMode::Windowed,
+        DisplayMode::Borderless,
+        DisplayMode::Fullscreen,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DisplayMode::Windowed => "Windowed",
+            DisplayMode::Borderless => "Borderless",
+            DisplayMode::Fullscreen => "Fullscreen",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn window_mode(&self) -> WindowMode {
+        match self {
+            DisplayMode::Windowed => WindowMode::Windowed,
+            DisplayMode::Borderless => WindowMode::BorderlessFullscreen,
+            DisplayMode::Fullscreen => WindowMode::Fullscreen,
+        }
+    }
+}
+
+/// Window sizes offered in the settings screen.
+pub const RESOLUTIONS: [(u32, u32); 5] = [
+    (980, 735),
+    (1280, 720),
+    (1280, 960),
+    (1600, 900),
+    (1920, 1080),
+];
+
+/// Reads a window size written as `<width>x<height>`.
+pub fn parse_resolution(text: &str) -> Option<(u32, u32)> {
+    let (width, height) = text.split_once('x')?;
+    Some((width.trim().parse().ok()?, height.trim().parse().ok()?))
+}
+
+/// Keys that can be bound to ship controls. Escape is left out, it cancels rebinding.
+const BINDABLE_KEYS: [KeyCode; 52] = [
+    KeyCode::KeyA,
+    KeyCode::KeyB,
+    KeyCode::KeyC,
+    KeyCode::KeyD,
+    KeyCode::KeyE,
+    KeyCode::KeyF,
+    KeyCode::KeyG,
+    KeyCode::KeyH,
+    KeyCode::KeyI,
+    KeyCode::KeyJ,
+    KeyCode::KeyK,
+    KeyCode::KeyL,
+    KeyCode::KeyM,
+    KeyCode::KeyN,
+    KeyCode::KeyO,
+    KeyCode::KeyP,
+    KeyCode::KeyQ,
+    KeyCode::KeyR,
+    KeyCode::KeyS,
+    KeyCode::KeyT,
+    KeyCode::KeyU,
+    KeyCode::KeyV,
+    KeyCode::KeyW,
+    KeyCode::KeyX,
+    KeyCode::KeyY,
+    KeyCode::KeyZ,
+    KeyCode::Digit0,
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+    KeyCode::ArrowUp,
+    KeyCode::ArrowDown,
+    KeyCode::ArrowLeft,
+    KeyCode::ArrowRight,
+    KeyCode::Space,
+    KeyCode::Enter,
+    KeyCode::Tab,
+    KeyCode::ShiftLeft,
+    KeyCode::ShiftRight,
+    KeyCode::ControlLeft,
+    KeyCode::ControlRight,
+    KeyCode::AltLeft,
+    KeyCode::AltRight,
+    KeyCode::Comma,
+    KeyCode::Period,
+    KeyCode::Slash,
+];
+
+/// Name of `key` as written to the settings file, e.g. `KeyA` or `ArrowLeft`.
+fn key_id(key: KeyCode) -> String {
+    format!("{key:?}")
+}
+
+/// Short name of `key` for the settings screen, e.g. `A` or `Left`.
+fn key_label(key: KeyCode) -> String {
+    let id = key_id(key);
+    ["Key", "Digit", "Arrow"]
+        .iter()
+        .find_map(|prefix| id.strip_prefix(prefix))
+        .unwrap_or(&id)
+        .to_string()
+}
+
+fn parse_key(value: &str) -> Option<KeyCode> {
+    BINDABLE_KEYS.into_iter().find(|key| key_id(*key) == value)
+}
+
+#[derive(Resource, Clone, PartialEq, Debug)]
+pub struct Settings {
+    pub display: DisplayMode,
+    pub resolution: (u32, u32),
+    pub background: BackgroundMode,
+    pub graphics: RenderStyle,
+    pub crt_preset: CrtPreset,
+    /// Multiplier of the CRT effect, from 0 (off) to 2.
+    pub crt_strength: f32,
+    /// From 0 to 1.
+    pub music_volume: f32,
+    /// From 0 to 1.
+    pub sfx_volume: f32,
+    pub bindings: KeyBindings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            display: DisplayMode::Windowed,
+            resolution: RESOLUTIONS[0],
+            background: BackgroundMode::default(),
+            graphics: RenderStyle::default(),
+            crt_preset: CrtPreset::default(),
+            crt_strength: 1.0,
+            music_volume: 0.8,
+            sfx_volume: 1.0,
+            bindings: KeyBindings::default(),
+        }
+    }
+}
+
+/// `spacewars` in the platform's per-user config directory.
+fn config_dir() -> Option<PathBuf> {
+    let var = |name: &str| {
+        std::env::var_os(name)
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+    };
+    let base = if cfg!(target_os = "windows") {
+        var("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        var("HOME").map(|home| home.join("Library/Application Support"))
+    } else {
+        var("XDG_CONFIG_HOME").or_else(|| var("HOME").map(|home| home.join(".config")))
+    };
+    base.map(|dir| dir.join("spacewars"))
+}
+
+const BINDING_KEYS: [&str; 5] = [
+    "rotate_left",
+    "rotate_right",
+    "thrust",
+    "fire",
+    "hyperspace",
+];
+
+fn binding_mut(keys: &mut PlayerKeys, action: usize) -> &mut KeyCode {
+    match action {
+        0 => &mut keys.rotate_left,
+        1 => &mut keys.rotate_right,
+        2 => &mut keys.accelerate,
+        3 => &mut keys.fire,
+        _ => &mut keys.hyperspace,
+    }
+}
+
+impl Settings {
+    pub fn path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join(FILE_NAME))
+    }
+
+    /// Reads the settings file, falling back to defaults for anything missing.
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(text) => Self::parse(&text),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, self.to_text())
+    }
+
+    fn parse(text: &str) -> Self {
+        let mut settings = Self::default();
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let (key, value) = (key.trim(), value.trim());
+            let fraction = |value: &str| value.parse::<f32>().ok().map(|v| v.clamp(0.0, 1.0));
+            match key {
+                "display" => {
+                    settings.display = DisplayMode::from_name(value).unwrap_or(settings.display);
+                }
+                "resolution" => {
+                    settings.resolution = parse_resolution(value).unwrap_or(settings.resolution);
+                }
+                "background" => {
+                    settings.background =
+                        BackgroundMode::from_name(value).unwrap_or(settings.background);
+                }
+                "graphics" => {
+                    settings.graphics = RenderStyle::from_name(value).unwrap_or(settings.graphics);
+                }
+                "crt_style" => {
+                    settings.crt_preset =
+                        CrtPreset::from_name(value).unwrap_or(settings.crt_preset);
+                }
+                "crt_strength" => {
+                    if let Ok(strength) = value.parse::<f32>() {
+                        settings.crt_strength = strength.clamp(0.0, 2.0);
+                    }
+                }
+                "music_volume" => {
+                    settings.music_volume = fraction(value).unwrap_or(settings.music_volume);
+                }
+                "sfx_volume" => {
+                    settings.sfx_volume = fraction(value).unwrap_or(settings.sfx_volume);
+                }
+                _ => {
+                    // p1_fire = KeyW, p2_thrust = KeyK, ...
+                    let binding = key
+                        .strip_prefix('p')
+                        .and_then(|rest| rest.split_once('_'))
+                        .and_then(|(player, action)| {
+                            let player = player.parse::<usize>().ok()?.checked_sub(1)?;
+                            let action = BINDING_KEYS.iter().position(|a| *a == action)?;
+                            Some((settings.bindings.0.get_mut(player)?, action))
+                        });
+                    if let (Some((keys, action)), Some(code)) = (binding, parse_key(value)) {
+                        *binding_mut(keys, action) = code;
+                    }
+                }
+            }
+        }
+        settings
+    }
+
+    fn to_text(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "display = {}", self.display.name().to_lowercase());
+        let _ = writeln!(
+            out,
+            "resolution = {}x{}",
+            self.resolution.0, self.resolution.1
+        );
+        let _ = writeln!(
+            out,
+            "background = {}",
+            self.background.name().to_lowercase()
+        );
+        let _ = writeln!(out, "graphics = {}", self.graphics.name().to_lowercase());
+        let _ = writeln!(out, "crt_style = {}", self.crt_preset.name());
+        let _ = writeln!(out, "crt_strength = {}", self.crt_strength);
+        let _ = writeln!(out, "music_volume = {}", self.music_volume);
+        let _ = writeln!(out, "sfx_volume = {}", self.sfx_volume);
+        for (player, keys) in self.bindings.0.iter().enumerate() {
+            let mut keys = *keys;
+            for (action, name) in BINDING_KEYS.iter().enumerate() {
+                let code = *binding_mut(&mut keys, action);
+                let _ = writeln!(out, "p{}_{name} = {}", player + 1, key_id(code));
+            }
+        }
+        out
+    }
+}
+
+/// Pushes changed settings to the window and the key bindings.
+fn apply_settings(
+    settings: Res<Settings>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut bindings: ResMut<KeyBindings>,
+    mut rockets: Query<(&Player, &mut Rocket)>,
+) {
+    if let Ok(mut window) = windows.get_single_mut() {
+        let mode = settings.display.window_mode();
+        if window.mode != mode {
+            window.mode = mode;
+        }
+        let (width, height) = (settings.resolution.0 as f32, settings.resolution.1 as f32);
+        if window.resolution.width() != width || window.resolution.height() != height {
+            window.resolution.set(width, height);
+        }
+    }
+    if *bindings != settings.bindings {
+        *bindings = settings.bindings;
+        for (player, mut rocket) in rockets.iter_mut() {
+            let keys = bindings.0[player.0];
+            rocket.controls = keys.controls();
+            rocket.spawn_key = keys.fire;
+        }
+    }
+}
+
+/// Pushes changed settings to the background, the graphics style and the CRT effect.
+fn apply_look(
+    settings: Res<Settings>,
+    mut background: ResMut<BackgroundSettings>,
+    mut style: ResMut<RenderStyle>,
+    mut crt: ResMut<CrtStrength>,
+    mut crt_preset: ResMut<CrtPreset>,
+) {
+    if background.mode != settings.background {
+        background.mode = settings.background;
+    }
+    style.set_if_neq(settings.graphics);
+    crt.0 = settings.crt_strength;
+    crt_preset.set_if_neq(settings.crt_preset);
+}
+
+#[derive(Component)]
+struct SettingsMenu;
+
+/// Sent when the player leaves the settings screen, wherever it was opened from.
+#[derive(Event)]
+pub struct SettingsClosed;
+
+/// Waiting for the key to bind to `action` of `player`.
+#[derive(Resource)]
+struct Rebinding {
+    player: usize,
+    action: usize,
+}
+
+const ENTRY_DISPLAY: usize = 0;
+const ENTRY_RESOLUTION: usize = 1;
+const ENTRY_BACKGROUND: usize = 2;
+const ENTRY_GRAPHICS: usize = 3;
+const ENTRY_CRT_STYLE: usize = 4;
+const ENTRY_CRT: usize = 5;
+const ENTRY_MUSIC: usize = 6;
+const ENTRY_SFX: usize = 7;
+/// One per action and player, in [`BINDING_KEYS`] order.
+const ENTRY_FIRST_BINDING: usize = 8;
+const ENTRY_BACK: usize = ENTRY_FIRST_BINDING + 2 * ACTIONS;
+
+const ACTION_NAMES: [&str; 5] = [
+    "rotate left",
+    "rotate right",
+    "thrust",
+    "fire",
+    "hyperspace",
+];
+const ACTIONS: usize = ACTION_NAMES.len();
+
+fn entry_label(entry: usize, settings: &Settings, rebinding: Option<&Rebinding>) -> String {
+    let percent = |value: f32| format!("{}%", (value * 100.0).round());
+    match entry {
+        ENTRY_DISPLAY => format!("Display      {}", settings.display.name()),
+        ENTRY_RESOLUTION => {
+            let (w, h) = settings.resolution;
+            format!("Resolution   {w} x {h}")
+        }
+        ENTRY_BACKGROUND => format!("Background   {}", settings.background.name()),
+        ENTRY_GRAPHICS => format!("Graphics     {}", settings.graphics.name()),
+        ENTRY_CRT_STYLE => format!("CRT style    {}", settings.crt_preset.name()),
+        ENTRY_CRT => format!("CRT strength {}", percent(settings.crt_strength)),
+        ENTRY_MUSIC => format!("Music        {}", percent(settings.music_volume)),
+        ENTRY_SFX => format!("Effects      {}", percent(settings.sfx_volume)),
+        ENTRY_BACK => "Back".to_string(),
+        _ => {
+            let index = entry - ENTRY_FIRST_BINDING;
+            let (player, action) = (index / ACTIONS, index % ACTIONS);
+            let key = match rebinding {
+                Some(r) if r.player == player && r.action == action => "press a key".to_string(),
+                _ => {
+                    let mut keys = settings.bindings.0[player];
+                    key_label(*binding_mut(&mut keys, action))
+                }
+            };
+            format!("P{} {:<13}{key}", player + 1, ACTION_NAMES[action])
+        }
+    }
+}
+
+/// Spawns the settings screen, living as long as `scope`.
+pub fn spawn_settings_menu<S: States>(commands: &mut Commands, scope: S) -> Entity {
+    let labels: Vec<String> = (0..=ENTRY_BACK)
+        .map(|entry| entry_label(entry, &Settings::default(), None))
+        .collect();
+    let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+    let menu = spawn_menu(commands, scope, "SETTINGS", &labels);
+    commands.entity(menu).insert(SettingsMenu);
+    menu
+}
+
+fn step<T: Copy + PartialEq>(options: &[T], current: T, delta: isize) -> T {
+    let index = options.iter().position(|o| *o == current).unwrap_or(0) as isize;
+    options[(index + delta).rem_euclid(options.len() as isize) as usize]
+}
+
+fn settings_menu(
+    mut commands: Commands,
+    buttons: MenuButtons,
+    mut settings: ResMut<Settings>,
+    mut menus: Query<(Entity, &mut Menu), With<SettingsMenu>>,
+    mut confirmed: EventReader<MenuConfirmed>,
+    mut back: EventReader<MenuBack>,
+    mut closed: EventWriter<SettingsClosed>,
+) {
+    let Ok((entity, mut menu)) = menus.get_single_mut() else {
+        return;
+    };
+
+    let input = buttons.read();
+    let delta = input.right as isize - input.left as isize;
+    if delta != 0 {
+        let settings = &mut *settings;
+        // Steps of 10%, rounded so they don't drift.
+        let fraction = |value: f32, max: f32| {
+            ((value * 10.0).round() + delta as f32).clamp(0.0, max * 10.0) / 10.0
+        };
+        match menu.selected {
+            ENTRY_DISPLAY => settings.display = step(&DisplayMode::ALL, settings.display, delta),
+            ENTRY_RESOLUTION => {
+                settings.resolution = step(&RESOLUTIONS, settings.resolution, delta)
+            }
+            ENTRY_BACKGROUND => {
+                settings.background = step(&BackgroundMode::ALL, settings.background, delta)
+            }
+            ENTRY_GRAPHICS => settings.graphics = step(&RenderStyle::ALL, settings.graphics, delta),
+            ENTRY_CRT_STYLE => {
+                settings.crt_preset = step(&CrtPreset::ALL, settings.crt_preset, delta)
+            }
+            ENTRY_CRT => settings.crt_strength = fraction(settings.crt_strength, 2.0),
+            ENTRY_MUSIC => settings.music_volume = fraction(settings.music_volume, 1.0),
+            ENTRY_SFX => settings.sfx_volume = fraction(settings.sfx_volume, 1.0),
+            _ => {}
+        }
+    }
+
+    for event in confirmed.read().filter(|event| event.menu == entity) {
+        match event.entry {
+            ENTRY_BACK => close(&settings, &mut closed),
+            entry if entry >= ENTRY_FIRST_BINDING => {
+                let index = entry - ENTRY_FIRST_BINDING;
+                menu.locked = true;
+                commands.insert_resource(Rebinding {
+                    player: index / ACTIONS,
+                    action: index % ACTIONS,
+                });
+            }
+            _ => {}
+        }
+    }
+    if back.read().any(|event| event.menu == entity) {
+        close(&settings, &mut closed);
+    }
+}
+
+fn close(settings: &Settings, closed: &mut EventWriter<SettingsClosed>) {
+    if let Err(err) = settings.save() {
+        error!("could not save settings: {err}");
+    }
+    closed.send(SettingsClosed);
+}
+
+fn capture_binding(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    rebinding: Res<Rebinding>,
+    mut settings: ResMut<Settings>,
+    mut menus: Query<&mut Menu, With<SettingsMenu>>,
+) {
+    let Some(pressed) = keys.get_just_pressed().next().copied() else {
+        return;
+    };
+    if pressed != KeyCode::Escape {
+        let Some(code) = BINDABLE_KEYS.into_iter().find(|key| *key == pressed) else {
+            return;
+        };
+        let keys = &mut settings.bindings.0[rebinding.player];
+        *binding_mut(keys, rebinding.action) = code;
+    }
+    commands.remove_resource::<Rebinding>();
+    for mut menu in menus.iter_mut() {
+        menu.locked = false;
+    }
+}
+
+fn update_settings_labels(
+    settings: Res<Settings>,
+    rebinding: Option<Res<Rebinding>>,
+    menus: Query<(), With<SettingsMenu>>,
+    mut entries: Query<(&MenuEntry, &Parent, &mut Text)>,
+) {
+    for (entry, parent, mut text) in entries.iter_mut() {
+        if !menus.contains(parent.get()) {
+            continue;
+        }
+        let label = entry_label(entry.0, &settings, rebinding.as_deref());
+        if text.sections[0].value != label {
+            text.sections[0].value = label;
+        }
+    }
+}
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Settings>()
+            .add_event::<SettingsClosed>()
+            .add_systems(
+                Update,
+                (
+                    capture_binding.run_if(resource_exists::<Rebinding>),
+                    settings_menu.run_if(not(resource_exists::<Rebinding>)),
+                    update_settings_labels,
+                    (apply_settings, apply_look).run_if(resource_changed::<Settings>),
+                )
+                    .chain(),
+            );
+    }
+}
@@ -0,0 +1,483 @@
+//! Sound effects and music, synthesized on the fly so the game needs no audio files.
+//!
+//! Every sound is a [`Synth`] asset whose decoder computes the waveform sample by
+//! sample. Effects play on the SFX bus and the soundtrack on the music bus, both
+//! scaled by the volumes in [`Settings`]. Sounds are panned by their x position in
+//! the arena; a ship's thrust hum follows the ship while it plays.
+
+use bevy::audio::{AddAudioSource, Source, Volume};
+use bevy::prelude::*;
+use std::f32::consts::{FRAC_PI_4, PI, TAU};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::arena::Warped;
+use crate::menu::{MenuBack, MenuConfirmed, MenuMoved};
+use crate::rocket::{Rocket, RocketDestroyed};
+use crate::settings::Settings;
+use crate::ArenaBounds;
+
+const SAMPLE_RATE: u32 = 44_100;
+/// Sounds are never panned all the way to one speaker.
+const MAX_PAN: f32 = 0.8;
+/// How long the thrust hum lingers after the fuel gauge last moved.
+const THRUST_LINGER: f32 = 0.1;
+
+/// One-shot sound effects.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Sfx {
+    Fire,
+    Explosion,
+    Hyperspace,
+    UiMove,
+    UiConfirm,
+    UiBack,
+}
+
+impl Sfx {
+    fn length(&self) -> f32 {
+        match self {
+            Sfx::Fire => 0.18,
+            Sfx::Explosion => 1.4,
+            Sfx::Hyperspace => 0.8,
+            Sfx::UiMove => 0.05,
+            Sfx::UiConfirm => 0.12,
+            Sfx::UiBack => 0.1,
+        }
+    }
+}
+
+/// Plays `sound` panned to where `x` is in the arena; `None` plays it centered.
+#[derive(Event, Clone, Copy)]
+pub struct PlaySfx {
+    pub sound: Sfx,
+    pub x: Option<f32>,
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub enum SoundBus {
+    Sfx,
+    Music,
+}
+
+impl SoundBus {
+    fn volume(&self, settings: &Settings) -> f32 {
+        match self {
+            SoundBus::Sfx => settings.sfx_volume,
+            SoundBus::Music => settings.music_volume,
+        }
+    }
+}
+
+/// Stereo position of a playing sound, from -1 (left) to 1 (right), shared with the
+/// audio thread so it can change while the sound plays.
+#[derive(Clone, Default)]
+pub struct Pan(Arc<AtomicU32>);
+
+impl Pan {
+    fn new(pan: f32) -> Self {
+        let shared = Self::default();
+        shared.set(pan);
+        shared
+    }
+
+    fn set(&self, pan: f32) {
+        self.0.store(pan.to_bits(), Ordering::Relaxed);
+    }
+
+    fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Voice {
+    Sfx(Sfx),
+    /// Loops until the sink is stopped.
+    Thrust,
+    /// Loops until the sink is stopped.
+    Music,
+}
+
+#[derive(Asset, TypePath)]
+pub struct Synth {
+    voice: Voice,
+    pan: Pan,
+}
+
+impl Decodable for Synth {
+    type DecoderItem = f32;
+    type Decoder = SynthDecoder;
+
+    fn decoder(&self) -> Self::Decoder {
+        SynthDecoder {
+            voice: self.voice,
+            pan: self.pan.clone(),
+            sample: 0,
+            noise: 0x2545_F491,
+            phase: 0.0,
+            filtered: 0.0,
+            right: None,
+        }
+    }
+}
+
+pub struct SynthDecoder {
+    voice: Voice,
+    pan: Pan,
+    sample: u32,
+    noise: u32,
+    phase: f32,
+    /// State of the one-pole low-pass filter the noise voices go through.
+    filtered: f32,
+    /// Right channel of the current frame, handed out after the left one.
+    right: Option<f32>,
+}
+
+/// Triangle wave with period 1, between -1 and 1.
+fn triangle(x: f32) -> f32 {
+    4.0 * (x.fract() - 0.5).abs() - 1.0
+}
+
+fn square(x: f32) -> f32 {
+    if x.fract() < 0.5 {
+        1.0
+    } else {
+        -1.0
+    }
+}
+
+/// Chord roots of the soundtrack, one per bar: A minor, F, C, G.
+const MUSIC_ROOTS: [(f32, bool); 4] = [
+    (110.0, true),
+    (87.31, false),
+    (130.81, false),
+    (98.0, false),
+];
+const MUSIC_BPM: f32 = 110.0;
+
+impl SynthDecoder {
+    fn white_noise(&mut self) -> f32 {
+        // Xorshift32.
+        self.noise ^= self.noise << 13;
+        self.noise ^= self.noise >> 17;
+        self.noise ^= self.noise << 5;
+        self.noise as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+
+    /// Low-passed noise; `amount` from 0 (nothing gets through) to 1 (unfiltered).
+    fn rumble(&mut self, amount: f32) -> f32 {
+        let noise = self.white_noise();
+        self.filtered += (noise - self.filtered) * amount;
+        self.filtered
+    }
+
+    /// Advances an oscillator by one sample at `frequency` and returns its phase.
+    fn oscillator(&mut self, frequency: f32) -> f32 {
+        self.phase = (self.phase + frequency / SAMPLE_RATE as f32).fract();
+        self.phase
+    }
+
+    /// Next mono sample, or `None` once a one-shot is over.
+    fn next_mono(&mut self) -> Option<f32> {
+        let t = self.sample as f32 / SAMPLE_RATE as f32;
+        let value = match self.voice {
+            Voice::Sfx(sfx) => {
+                let length = sfx.length();
+                if t >= length {
+                    return None;
+                }
+                let progress = t / length;
+                match sfx {
+                    Sfx::Fire => {
+                        let phase = self.oscillator(880.0 - 600.0 * progress);
+                        0.3 * square(phase) * (1.0 - progress).powi(2)
+                    }
+                    Sfx::Explosion => {
+                        let noise = self.rumble(0.4 * (1.0 - progress) + 0.02);
+                        0.9 * noise * (-t * 3.5).exp()
+                    }
+                    Sfx::Hyperspace => {
+                        let wobble = 1.0 + 0.05 * (t * 40.0 * TAU).sin();
+                        let phase =
+                            self.oscillator((200.0 + 1400.0 * progress * progress) * wobble);
+                        0.35 * (phase * TAU).sin() * (progress * PI).sin()
+                    }
+                    Sfx::UiMove => {
+                        let phase = self.oscillator(660.0);
+                        0.2 * (phase * TAU).sin() * (1.0 - progress)
+                    }
+                    Sfx::UiConfirm => {
+                        let frequency = if t < 0.05 { 660.0 } else { 990.0 };
+                        let phase = self.oscillator(frequency);
+                        0.2 * (phase * TAU).sin() * (1.0 - progress)
+                    }
+                    Sfx::UiBack => {
+                        let phase = self.oscillator(440.0 - 110.0 * progress);
+                        0.2 * (phase * TAU).sin() * (1.0 - progress)
+                    }
+                }
+            }
+            Voice::Thrust => {
+                let noise = self.rumble(0.06);
+                let hum = self.oscillator(55.0) * 2.0 - 1.0;
+                0.5 * noise + 0.08 * hum
+            }
+            Voice::Music => music(t),
+        };
+        self.sample = self.sample.wrapping_add(1);
+        // Keep looping voices' time small enough for f32 to stay precise.
+        if let Voice::Music = self.voice {
+            self.sample %= music_loop_samples();
+        }
+        Some(value)
+    }
+}
+
+fn music_loop_samples() -> u32 {
+    let bar = 4.0 * 60.0 / MUSIC_BPM;
+    (bar * MUSIC_ROOTS.len() as f32 * SAMPLE_RATE as f32) as u32
+}
+
+/// The soundtrack at `t` seconds into its loop: a plucked bass line in eighths and an
+/// arpeggio in sixteenths over four chords.
+fn music(t: f32) -> f32 {
+    let sixteenth = 60.0 / MUSIC_BPM / 4.0;
+    let step = (t / sixteenth) as usize;
+    let (root, minor) = MUSIC_ROOTS[(step / 16) % MUSIC_ROOTS.len()];
+    let third = if minor { 1.2 } else { 1.25 };
+
+    let since_eighth = t % (sixteenth * 2.0);
+    let bass = triangle(root * t) * (-since_eighth * 6.0).exp();
+
+    let since_sixteenth = t % sixteenth;
+    let arpeggio = [1.0, third, 1.5, 2.0][step % 4];
+    let lead = triangle(root * 4.0 * arpeggio * t) * (-since_sixteenth * 18.0).exp();
+
+    let pad = (root * 2.0 * TAU * t).sin() + (root * 3.0 * TAU * t).sin();
+
+    0.35 * bass + 0.12 * lead + 0.04 * pad
+}
+
+impl Iterator for SynthDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if let Some(right) = self.right.take() {
+            return Some(right);
+        }
+        let mono = self.next_mono()?;
+        // Equal-power panning.
+        let angle = (self.pan.get().clamp(-1.0, 1.0) + 1.0) * FRAC_PI_4;
+        self.right = Some(mono * angle.sin());
+        Some(mono * angle.cos())
+    }
+}
+
+impl Source for SynthDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        match self.voice {
+            Voice::Sfx(sfx) => Some(Duration::from_secs_f32(sfx.length())),
+            Voice::Thrust | Voice::Music => None,
+        }
+    }
+}
+
+/// Thrust hum of a rocket, and what the rocket looked like last frame so firing and
+/// burning can be told apart from the outside, whoever simulates the rocket.
+#[derive(Component)]
+struct ShipSound {
+    pan: Pan,
+    ammo: u32,
+    fuel: f32,
+    /// Seconds the hum keeps playing.
+    burning: f32,
+}
+
+fn pan_for(bounds: &ArenaBounds, x: f32) -> f32 {
+    (x / bounds.half_size.x).clamp(-1.0, 1.0) * MAX_PAN
+}
+
+fn start_music(mut commands: Commands, mut synths: ResMut<Assets<Synth>>, settings: Res<Settings>) {
+    commands.spawn((
+        AudioSourceBundle {
+            source: synths.add(Synth {
+                voice: Voice::Music,
+                pan: Pan::new(0.0),
+            }),
+            settings: PlaybackSettings::LOOP.with_volume(Volume::new(settings.music_volume)),
+        },
+        SoundBus::Music,
+    ));
+}
+
+fn attach_ship_sounds(
+    mut commands: Commands,
+    mut synths: ResMut<Assets<Synth>>,
+    settings: Res<Settings>,
+    bounds: Res<ArenaBounds>,
+    rockets: Query<(Entity, &Rocket, &Transform), Added<Rocket>>,
+) {
+    for (entity, rocket, transform) in rockets.iter() {
+        let pan = Pan::new(pan_for(&bounds, transform.translation.x));
+        commands.entity(entity).insert((
+            AudioSourceBundle {
+                source: synths.add(Synth {
+                    voice: Voice::Thrust,
+                    pan: pan.clone(),
+                }),
+                settings: PlaybackSettings::LOOP
+                    .paused()
+                    .with_volume(Volume::new(settings.sfx_volume)),
+            },
+            SoundBus::Sfx,
+            ShipSound {
+                pan,
+                ammo: rocket.ammo,
+                fuel: rocket.fuel,
+                burning: 0.0,
+            },
+        ));
+    }
+}
+
+fn update_ship_sounds(
+    time: Res<Time<Real>>,
+    bounds: Res<ArenaBounds>,
+    mut rockets: Query<(&Rocket, &Transform, &mut ShipSound, Option<&AudioSink>)>,
+    mut sfx: EventWriter<PlaySfx>,
+) {
+    for (rocket, transform, mut sound, sink) in rockets.iter_mut() {
+        let x = transform.translation.x;
+        sound.pan.set(pan_for(&bounds, x));
+
+        if rocket.ammo < sound.ammo {
+            sfx.send(PlaySfx {
+                sound: Sfx::Fire,
+                x: Some(x),
+            });
+        }
+        sound.burning = if rocket.fuel < sound.fuel {
+            THRUST_LINGER
+        } else {
+            (sound.burning - time.delta_seconds()).max(0.0)
+        };
+        sound.ammo = rocket.ammo;
+        sound.fuel = rocket.fuel;
+
+        if let Some(sink) = sink {
+            match (sound.burning > 0.0, sink.is_paused()) {
+                (true, true) => sink.play(),
+                (false, false) => sink.pause(),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn game_sounds(
+    mut destroyed: EventReader<RocketDestroyed>,
+    mut warped: EventReader<Warped>,
+    mut confirmed: EventReader<MenuConfirmed>,
+    mut back: EventReader<MenuBack>,
+    mut moved: EventReader<MenuMoved>,
+    mut sfx: EventWriter<PlaySfx>,
+) {
+    for event in destroyed.read() {
+        sfx.send(PlaySfx {
+            sound: Sfx::Explosion,
+            x: Some(event.position.x),
+        });
+    }
+    for event in warped.read() {
+        sfx.send(PlaySfx {
+            sound: Sfx::Hyperspace,
+            x: Some(event.position.x),
+        });
+    }
+    let ui = [
+        (confirmed.read().count(), Sfx::UiConfirm),
+        (back.read().count(), Sfx::UiBack),
+        (moved.read().count(), Sfx::UiMove),
+    ];
+    // One beep per kind, however many menus reacted.
+    for (_, sound) in ui.into_iter().filter(|(count, _)| *count > 0) {
+        sfx.send(PlaySfx { sound, x: None });
+    }
+}
+
+fn play_sfx(
+    mut commands: Commands,
+    mut synths: ResMut<Assets<Synth>>,
+    settings: Res<Settings>,
+    bounds: Res<ArenaBounds>,
+    mut events: EventReader<PlaySfx>,
+) {
+    for event in events.read() {
+        let pan = event.x.map_or(0.0, |x| pan_for(&bounds, x));
+        commands.spawn((
+            AudioSourceBundle {
+                source: synths.add(Synth {
+                    voice: Voice::Sfx(event.sound),
+                    pan: Pan::new(pan),
+                }),
+                settings: PlaybackSettings::DESPAWN.with_volume(Volume::new(settings.sfx_volume)),
+            },
+            SoundBus::Sfx,
+        ));
+    }
+}
+
+/// Follows the bus volumes in the settings, and plays a blip when the effects volume
+/// is changed so the new level can be heard.
+fn apply_volumes(
+    settings: Res<Settings>,
+    sinks: Query<(&AudioSink, &SoundBus)>,
+    mut last_sfx_volume: Local<Option<f32>>,
+    mut sfx: EventWriter<PlaySfx>,
+) {
+    for (sink, bus) in sinks.iter() {
+        sink.set_volume(bus.volume(&settings));
+    }
+    if last_sfx_volume.is_some_and(|volume| volume != settings.sfx_volume) {
+        sfx.send(PlaySfx {
+            sound: Sfx::UiMove,
+            x: None,
+        });
+    }
+    *last_sfx_volume = Some(settings.sfx_volume);
+}
+
+pub struct SoundPlugin;
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_audio_source::<Synth>()
+            .add_event::<PlaySfx>()
+            .add_systems(Startup, start_music)
+            .add_systems(
+                Update,
+                (
+                    attach_ship_sounds,
+                    update_ship_sounds,
+                    game_sounds,
+                    apply_volumes.run_if(resource_changed::<Settings>),
+                    play_sfx,
+                )
+                    .chain(),
+            );
+    }
+}
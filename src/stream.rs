@@ -0,0 +1,70 @@
+//! Live copy of a match for casting.
+//!
+//! The server can write what its spectators see to a file or a TCP connection. The
+//! stream is a plain sequence of [`ServerMessage`]s, each prefixed with its length as
+//! a little-endian `u16`: a [`ServerMessage::MatchStart`] with both loadouts, then
+//! one [`ServerMessage::Snapshot`] per tick. A stream saved to a file doubles as a
+//! replay of the match, see [`read_recording`].
+
+use bevy::prelude::*;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::net::TcpStream;
+use std::path::Path;
+
+use crate::protocol::ServerMessage;
+
+pub struct MatchStream {
+    out: BufWriter<Box<dyn Write + Send + Sync>>,
+    target: String,
+}
+
+impl MatchStream {
+    /// Opens `tcp:<host>:<port>` as a TCP connection, anything else as a file path.
+    pub fn open(target: &str) -> io::Result<Self> {
+        let out: Box<dyn Write + Send + Sync> = match target.strip_prefix("tcp:") {
+            Some(addr) => {
+                let socket = TcpStream::connect(addr)?;
+                socket.set_nodelay(true)?;
+                Box::new(socket)
+            }
+            None => Box::new(File::create(target)?),
+        };
+        info!("streaming the match to {target}");
+        Ok(Self {
+            out: BufWriter::new(out),
+            target: target.to_string(),
+        })
+    }
+
+    pub fn write(&mut self, message: &ServerMessage) -> io::Result<()> {
+        let bytes = message.encode();
+        self.out.write_all(&(bytes.len() as u16).to_le_bytes())?;
+        self.out.write_all(&bytes)?;
+        self.out.flush()
+    }
+
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+}
+
+/// Reads back a match stream that was saved to a file. A message cut off at the end,
+/// e.g. because the server was stopped mid-write, is dropped.
+pub fn read_recording(path: &Path) -> io::Result<Vec<ServerMessage>> {
+    let bytes = std::fs::read(path)?;
+    let mut messages = Vec::new();
+    let mut rest = bytes.as_slice();
+    while let [lo, hi, tail @ ..] = rest {
+        let len = u16::from_le_bytes([*lo, *hi]) as usize;
+        let Some(message) = tail.get(..len) else {
+            break;
+        };
+        match ServerMessage::decode(message) {
+            Some(message) => messages.push(message),
+            None => warn!("skipped an unreadable message in {}", path.display()),
+        }
+        rest = &tail[len..];
+    }
+    Ok(messages)
+}
@@ -0,0 +1,101 @@
+//! Client connections over TCP, for networks where UDP doesn't get through.
+//!
+//! The messages are the same ones sent as datagrams, each prefixed with its length as
+//! a little-endian `u16` like in a [`MatchStream`](crate::stream::MatchStream). The
+//! socket stays non-blocking on both ends: whatever it doesn't take right away is
+//! written on the next send, and a message is only handed out once it arrived whole.
+
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+
+use crate::protocol::MAX_PACKET;
+
+/// Unsent bytes a link holds on to before giving up on the other end.
+const MAX_BACKLOG: usize = 64 * MAX_PACKET;
+
+pub struct TcpLink {
+    stream: TcpStream,
+    peer: SocketAddr,
+    /// Received bytes that don't make up a whole message yet.
+    incoming: Vec<u8>,
+    /// Framed messages the socket hasn't taken yet.
+    outgoing: Vec<u8>,
+}
+
+impl TcpLink {
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            peer: stream.peer_addr()?,
+            stream,
+            incoming: Vec::new(),
+            outgoing: Vec::new(),
+        })
+    }
+
+    /// Address of the other end.
+    pub fn peer(&self) -> SocketAddr {
+        self.peer
+    }
+
+    /// Queues `message` and writes as much of the queue as the socket takes.
+    pub fn send(&mut self, message: &[u8]) -> io::Result<()> {
+        if self.outgoing.len() + message.len() > MAX_BACKLOG {
+            return Err(io::Error::new(
+                ErrorKind::TimedOut,
+                "the other end stopped reading",
+            ));
+        }
+        self.outgoing
+            .extend_from_slice(&(message.len() as u16).to_le_bytes());
+        self.outgoing.extend_from_slice(message);
+        self.flush()
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the messages that arrived in full to `messages`. Fails once the connection
+    /// is gone, after handing out what was sent before it closed.
+    pub fn receive(&mut self, messages: &mut Vec<Vec<u8>>) -> io::Result<()> {
+        let mut buf = [0u8; MAX_PACKET];
+        let status = loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => break Err(ErrorKind::UnexpectedEof.into()),
+                Ok(len) => self.incoming.extend_from_slice(&buf[..len]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break self.flush(),
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => break Err(err),
+            }
+        };
+
+        let mut rest = self.incoming.as_slice();
+        while let [lo, hi, tail @ ..] = rest {
+            let len = u16::from_le_bytes([*lo, *hi]) as usize;
+            if len > MAX_PACKET {
+                return Err(io::Error::new(ErrorKind::InvalidData, "oversized message"));
+            }
+            let Some(message) = tail.get(..len) else {
+                break;
+            };
+            messages.push(message.to_vec());
+            rest = &tail[len..];
+        }
+        let used = self.incoming.len() - rest.len();
+        self.incoming.drain(..used);
+        status
+    }
+}
@@ -0,0 +1,426 @@
+//! Vector display look: ships, torpedoes, the bodies of the arena and explosions
+//! drawn as glowing outlines, like on the PDP-1's Type 30 display that Spacewar! was
+//! written for.
+//!
+//! Outlines are point lists in each shape's own space, with the nose pointing up
+//! (+y), and are drawn with gizmos: a thin bright line over a wide dim one for the
+//! glow. Anything with a [`VectorShape`] has its sprite moved to [`SPRITE_LAYER`],
+//! which the cameras stop rendering while [`RenderStyle::Vector`] is picked, so the
+//! style can be switched at any time without touching the sprites themselves.
+
+use bevy::{prelude::*, render::view::RenderLayers};
+use bevy_hanabi::prelude::ParticleEffect;
+use std::f32::consts::TAU;
+
+use crate::asteroid::ROCK;
+use crate::bullet::Bullet;
+use crate::hud::player_color;
+use crate::input::ShipInput;
+use crate::powerup::PowerUp;
+use crate::rocket::{Loadouts, Player, Rocket, ShipClass};
+use crate::{Explosion, SimRng};
+
+/// Render layer of the sprites that have an outline.
+pub const SPRITE_LAYER: usize = 1;
+
+const LINE_WIDTH: f32 = 1.5;
+const GLOW_WIDTH: f32 = 6.0;
+/// Brightness of the glow compared to the line.
+const GLOW: f32 = 0.25;
+const EXPLOSION_SECONDS: f32 = 1.2;
+const EXPLOSION_SPARKS: usize = 24;
+
+const NEEDLE: &[&[Vec2]] = &[&[
+    Vec2::new(0.0, 24.0),
+    Vec2::new(3.0, 14.0),
+    Vec2::new(3.0, -10.0),
+    Vec2::new(7.0, -18.0),
+    Vec2::new(3.0, -16.0),
+    Vec2::new(-3.0, -16.0),
+    Vec2::new(-7.0, -18.0),
+    Vec2::new(-3.0, -10.0),
+    Vec2::new(-3.0, 14.0),
+    Vec2::new(0.0, 24.0),
+]];
+
+const WEDGE: &[&[Vec2]] = &[
+    &[
+        Vec2::new(0.0, 22.0),
+        Vec2::new(13.0, -14.0),
+        Vec2::new(5.0, -10.0),
+        Vec2::new(-5.0, -10.0),
+        Vec2::new(-13.0, -14.0),
+        Vec2::new(0.0, 22.0),
+    ],
+    // Cockpit
+    &[
+        Vec2::new(0.0, 9.0),
+        Vec2::new(3.0, 1.0),
+        Vec2::new(-3.0, 1.0),
+        Vec2::new(0.0, 9.0),
+    ],
+];
+
+const TORPEDO: &[&[Vec2]] = &[&[
+    Vec2::new(0.0, 3.0),
+    Vec2::new(2.0, 0.0),
+    Vec2::new(0.0, -3.0),
+    Vec2::new(-2.0, 0.0),
+    Vec2::new(0.0, 3.0),
+]];
+
+const ASTEROID: &[&[Vec2]] = &[&ROCK];
+
+/// How a game looks.
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum RenderStyle {
+    #[default]
+    Sprites,
+    Vector,
+}
+
+impl RenderStyle {
+    pub const ALL: [RenderStyle; 2] = [RenderStyle::Sprites, RenderStyle::Vector];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RenderStyle::Sprites => "Sprites",
+            RenderStyle::Vector => "Vector",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|style| style.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// Outline drawn for an entity in [`RenderStyle::Vector`]. Rockets and torpedoes get
+/// theirs automatically.
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
+pub enum VectorShape {
+    Ship(ShipClass),
+    Torpedo,
+    /// [`ROCK`], scaled by the transform.
+    Asteroid,
+    /// A flickering burst of rays the size of the sun.
+    Sun {
+        radius: f32,
+    },
+    /// A plain circle.
+    Planet {
+        radius: f32,
+    },
+    /// An event horizon with arms spiralling into it.
+    BlackHole {
+        radius: f32,
+    },
+    /// Rings shrinking towards the middle.
+    Wormhole {
+        radius: f32,
+    },
+    /// A spinning diamond in the color of the kind.
+    PowerUp {
+        kind: PowerUp,
+        radius: f32,
+    },
+}
+
+impl VectorShape {
+    fn strokes(&self) -> &'static [&'static [Vec2]] {
+        match self {
+            VectorShape::Ship(ShipClass::Needle) => NEEDLE,
+            VectorShape::Ship(ShipClass::Wedge) => WEDGE,
+            VectorShape::Torpedo => TORPEDO,
+            VectorShape::Asteroid => ASTEROID,
+            VectorShape::Sun { .. }
+            | VectorShape::Planet { .. }
+            | VectorShape::BlackHole { .. }
+            | VectorShape::Wormhole { .. }
+            | VectorShape::PowerUp { .. } => &[],
+        }
+    }
+
+    /// Where the exhaust flame starts, for ships.
+    fn tail(&self) -> Option<f32> {
+        match self {
+            VectorShape::Ship(ShipClass::Needle) => Some(-16.0),
+            VectorShape::Ship(ShipClass::Wedge) => Some(-10.0),
+            _ => None,
+        }
+    }
+}
+
+/// Thin bright outlines.
+#[derive(Default, Reflect, GizmoConfigGroup)]
+pub struct VectorLines;
+
+/// Wide dim lines under [`VectorLines`].
+#[derive(Default, Reflect, GizmoConfigGroup)]
+pub struct VectorGlow;
+
+/// Sparks flying out of a destroyed rocket.
+#[derive(Component)]
+struct VectorExplosion {
+    age: f32,
+    /// Direction and speed of each spark.
+    sparks: Vec<Vec2>,
+}
+
+pub struct VectorPlugin;
+
+impl Plugin for VectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RenderStyle>()
+            .insert_gizmo_config(
+                VectorLines,
+                GizmoConfig {
+                    line_width: LINE_WIDTH,
+                    ..default()
+                },
+            )
+            .insert_gizmo_config(
+                VectorGlow,
+                GizmoConfig {
+                    line_width: GLOW_WIDTH,
+                    ..default()
+                },
+            )
+            .add_systems(
+                Update,
+                (
+                    tag_shapes,
+                    move_sprites_to_layer,
+                    apply_render_style.run_if(resource_changed::<RenderStyle>),
+                    spawn_explosions,
+                    draw_outlines,
+                    draw_explosions,
+                )
+                    .chain(),
+            );
+    }
+}
+
+fn tag_shapes(
+    mut commands: Commands,
+    loadouts: Res<Loadouts>,
+    rockets: Query<(Entity, &Player), Added<Rocket>>,
+    bullets: Query<Entity, Added<Bullet>>,
+) {
+    for (entity, player) in &rockets {
+        let class = loadouts.0[player.0].class;
+        commands.entity(entity).insert(VectorShape::Ship(class));
+    }
+    for entity in &bullets {
+        commands.entity(entity).insert(VectorShape::Torpedo);
+    }
+}
+
+fn move_sprites_to_layer(
+    mut commands: Commands,
+    shapes: Query<Entity, (Added<VectorShape>, Without<RenderLayers>)>,
+) {
+    for entity in &shapes {
+        commands
+            .entity(entity)
+            .insert(RenderLayers::layer(SPRITE_LAYER));
+    }
+}
+
+/// Shows either the sprites and particle explosions or neither of them.
+fn apply_render_style(
+    mut commands: Commands,
+    style: Res<RenderStyle>,
+    cameras: Query<Entity, With<Camera2d>>,
+    mut particles: Query<&mut Visibility, With<ParticleEffect>>,
+) {
+    let (layers, particle_visibility) = match *style {
+        RenderStyle::Sprites => (
+            RenderLayers::from_layers(&[0, SPRITE_LAYER]),
+            Visibility::Inherited,
+        ),
+        RenderStyle::Vector => (RenderLayers::layer(0), Visibility::Hidden),
+    };
+    for camera in &cameras {
+        commands.entity(camera).insert(layers.clone());
+    }
+    for mut visibility in &mut particles {
+        visibility.set_if_neq(particle_visibility);
+    }
+}
+
+fn spawn_explosions(
+    mut commands: Commands,
+    style: Res<RenderStyle>,
+    mut explosions: EventReader<Explosion>,
+) {
+    for explosion in explosions.read() {
+        if *style != RenderStyle::Vector {
+            continue;
+        }
+        // Only for looks, so it doesn't touch the match's random numbers.
+        let position = explosion.position;
+        let mut rng =
+            SimRng::new(u64::from(position.x.to_bits()) << 32 | u64::from(position.y.to_bits()));
+        let sparks = (0..EXPLOSION_SPARKS)
+            .map(|_| Vec2::from_angle(rng.range(0.0, TAU)) * rng.range(20.0, 120.0))
+            .collect();
+        commands.spawn((
+            VectorExplosion { age: 0.0, sparks },
+            SpatialBundle::from_transform(Transform::from_translation(position)),
+        ));
+    }
+}
+
+type Outlined<'a> = (
+    &'a GlobalTransform,
+    &'a VectorShape,
+    &'a InheritedVisibility,
+    Option<&'a Player>,
+    Option<&'a Rocket>,
+    Option<&'a ShipInput>,
+);
+
+/// Draws `points` as one connected line with its glow.
+fn stroke(
+    lines: &mut Gizmos<VectorLines>,
+    glow: &mut Gizmos<VectorGlow>,
+    points: &[Vec2],
+    color: Color,
+) {
+    lines.linestrip_2d(points.iter().copied(), color);
+    glow.linestrip_2d(points.iter().copied(), color.with_alpha(GLOW));
+}
+
+/// Closed outline of a circle, starting at `angle`.
+fn circle(center: Vec2, radius: f32, angle: f32) -> Vec<Vec2> {
+    let sides = 24;
+    (0..=sides)
+        .map(|side| center + Vec2::from_angle(angle + side as f32 / sides as f32 * TAU) * radius)
+        .collect()
+}
+
+fn draw_outlines(
+    mut lines: Gizmos<VectorLines>,
+    mut glow: Gizmos<VectorGlow>,
+    style: Res<RenderStyle>,
+    loadouts: Res<Loadouts>,
+    time: Res<Time>,
+    shapes: Query<Outlined>,
+) {
+    if *style != RenderStyle::Vector {
+        return;
+    }
+    for (transform, shape, visibility, player, rocket, input) in &shapes {
+        if !visibility.get() {
+            continue;
+        }
+        let color = player.map_or(Color::WHITE, |player| player_color(&loadouts, *player));
+        let place = |point: Vec2| transform.transform_point(point.extend(0.0)).truncate();
+
+        for points in shape.strokes() {
+            let points: Vec<Vec2> = points.iter().map(|point| place(*point)).collect();
+            stroke(&mut lines, &mut glow, &points, color);
+        }
+
+        let thrusting = rocket.is_some_and(|rocket| rocket.fuel > 0.0)
+            && input.is_some_and(|input| input.pressed(ShipInput::ACCELERATE));
+        if let (Some(tail), true) = (shape.tail(), thrusting) {
+            let flicker = 6.0 + 4.0 * (time.elapsed_seconds() * 40.0).sin().abs();
+            let flame = [
+                Vec2::new(-2.5, tail),
+                Vec2::new(0.0, tail - flicker),
+                Vec2::new(2.5, tail),
+            ]
+            .map(place);
+            stroke(&mut lines, &mut glow, &flame, Color::srgb(1.0, 0.7, 0.3));
+        }
+
+        let center = transform.translation().truncate();
+        match *shape {
+            VectorShape::Sun { radius } => {
+                let rays = 16;
+                for ray in 0..rays {
+                    let angle = ray as f32 / rays as f32 * TAU;
+                    let flicker = (time.elapsed_seconds() * 13.0 + ray as f32 * 2.3).sin();
+                    let tip =
+                        center + Vec2::from_angle(angle) * radius * (0.6 + 0.4 * flicker.abs());
+                    stroke(
+                        &mut lines,
+                        &mut glow,
+                        &[center, tip],
+                        Color::srgb(1.0, 0.95, 0.6),
+                    );
+                }
+            }
+            VectorShape::Planet { radius } => {
+                let rim = circle(center, radius, 0.0);
+                stroke(&mut lines, &mut glow, &rim, Color::srgb(0.5, 0.8, 1.0));
+            }
+            VectorShape::BlackHole { radius } => {
+                let color = Color::srgb(1.0, 0.5, 0.2);
+                stroke(&mut lines, &mut glow, &circle(center, radius, 0.0), color);
+                // Arms wind in from twice the horizon, turning as they fall.
+                let spin = time.elapsed_seconds() * 1.5;
+                for arm in 0..3 {
+                    let start = spin + arm as f32 / 3.0 * TAU;
+                    let points: Vec<Vec2> = (0..=12)
+                        .map(|step| {
+                            let fall = step as f32 / 12.0;
+                            let angle = start + fall * 2.5;
+                            center + Vec2::from_angle(angle) * radius * (2.0 - fall)
+                        })
+                        .collect();
+                    stroke(&mut lines, &mut glow, &points, color.with_alpha(0.6));
+                }
+            }
+            VectorShape::Wormhole { radius } => {
+                // Three rings, each shrinking into the middle and starting over.
+                let cycle = time.elapsed_seconds() * 0.8;
+                for ring in 0..3 {
+                    let size = 1.0 - (cycle + ring as f32 / 3.0).fract();
+                    let rim = circle(center, radius * size, cycle * 2.0);
+                    let color = Color::srgb(0.8, 0.4, 1.0).with_alpha(0.3 + 0.7 * size);
+                    stroke(&mut lines, &mut glow, &rim, color);
+                }
+            }
+            VectorShape::PowerUp { kind, radius } => {
+                let spin = time.elapsed_seconds() * 2.0;
+                let diamond: Vec<Vec2> = (0..=4)
+                    .map(|corner| {
+                        center + Vec2::from_angle(spin + corner as f32 * TAU / 4.0) * radius
+                    })
+                    .collect();
+                stroke(&mut lines, &mut glow, &diamond, kind.color());
+            }
+            _ => {}
+        }
+    }
+}
+
+fn draw_explosions(
+    mut commands: Commands,
+    mut lines: Gizmos<VectorLines>,
+    mut glow: Gizmos<VectorGlow>,
+    style: Res<RenderStyle>,
+    time: Res<Time>,
+    mut explosions: Query<(Entity, &mut VectorExplosion, &Transform)>,
+) {
+    for (entity, mut explosion, transform) in &mut explosions {
+        explosion.age += time.delta_seconds();
+        if explosion.age >= EXPLOSION_SECONDS || *style != RenderStyle::Vector {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let center = transform.translation.truncate();
+        let fade = 1.0 - explosion.age / EXPLOSION_SECONDS;
+        let color = Color::srgb(1.0, 0.65, 0.0).with_alpha(fade);
+        for spark in &explosion.sparks {
+            // Sparks slow down as they fly out and leave a short streak behind.
+            let reach = |age: f32| center + *spark * age * (1.0 - 0.4 * age);
+            let streak = [reach((explosion.age - 0.08).max(0.0)), reach(explosion.age)];
+            stroke(&mut lines, &mut glow, &streak, color);
+        }
+    }
+}